@@ -0,0 +1,83 @@
+//! Timings of the toolchain resolution the proxies (`proxy_mode::main`)
+//! do before running a tool, from the proxy cache and from the
+//! settings. The tool itself isn't run, so the timings aren't swamped
+//! by starting processes. Run with `cargo bench`.
+
+extern crate rustup;
+extern crate rustup_mock;
+
+use rustup::{Cfg, Notification};
+use rustup_mock::clitools::{self, Config, Scenario, expect_ok};
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 1000;
+
+fn main() {
+    clitools::setup(Scenario::SimpleV2, &|config| {
+        expect_ok(config, &["rustup", "default", "stable"]);
+
+        // The environment the proxies would be run with
+        env::set_var("RUSTUP_HOME", &config.rustupdir);
+        env::set_var("CARGO_HOME", &config.cargodir);
+        env::set_var("HOME", &config.homedir);
+        env::set_var("RUSTUP_OVERRIDE_HOST_TRIPLE", clitools::this_host_triple());
+
+        // A deep directory under an override, so the overrides have to
+        // be searched through several ancestors
+        let dir = config.homedir.join("project");
+        let nested = dir.join("a/b/c/d/e/f");
+        fs::create_dir_all(&nested).unwrap();
+        clitools::change_dir(&dir, &|| {
+            expect_ok(config, &["rustup", "override", "add", "nightly"]);
+        });
+
+        report("directory override, settings", bench(config, &nested, false));
+        report("directory override, cache", bench(config, &nested, true));
+        report("default toolchain, settings", bench(config, &config.emptydir, false));
+        report("default toolchain, cache", bench(config, &config.emptydir, true));
+    });
+}
+
+/// Resolves the command for `rustc` run in `dir` repeatedly, the way
+/// the proxies do, either from the cache or by reading the settings
+/// and searching the overrides
+fn bench(config: &Config, dir: &Path, cached: bool) -> Duration {
+    let ref cache = config.rustupdir.join("proxy-cache");
+    let args: Vec<OsString> = vec!["--version".into()];
+
+    // Warm up the file system cache, and fill the proxy cache
+    resolve(dir, &args);
+
+    let mut total = Duration::new(0, 0);
+    for _ in 0..ITERATIONS {
+        if !cached {
+            let _ = fs::remove_file(cache);
+        }
+        let start = Instant::now();
+        if cached {
+            Cfg::cached_command_for_dir(&config.rustupdir, dir, "rustc")
+                .expect("the proxy cache should answer");
+        } else {
+            resolve(dir, &args);
+        }
+        total += start.elapsed();
+    }
+    total / ITERATIONS
+}
+
+/// What the proxies do when the cache can't answer
+fn resolve(dir: &Path, args: &[OsString]) {
+    let cfg = Cfg::from_env(Arc::new(|_: Notification| {})).unwrap();
+    cfg.check_metadata_version().unwrap();
+    cfg.create_command_for_dir(dir, "rustc", args).unwrap();
+}
+
+fn report(name: &str, per_run: Duration) {
+    let micros = per_run.as_secs() * 1_000_000 + per_run.subsec_nanos() as u64 / 1_000;
+    println!("{:<30} {:>8} us/run", name, micros);
+}
@@ -36,6 +36,7 @@ FLAGS:
 OPTIONS:
         --default-host <default-host>              Choose a default host triple
         --default-toolchain <default-toolchain>    Choose a default toolchain to install
+        --profile <profile>                        [default: default]  [values: minimal, default, complete]
 EOF
 }
 
@@ -1,7 +1,9 @@
 //! Just a dumping ground for cli stuff
 
 use rustup::{self, Cfg, Notification, Toolchain, UpdateStatus};
+use rustup::settings::SettingsFile;
 use rustup::telemetry_analysis::TelemetryAnalysis;
+use rustup_dist::dist::TargetTriple;
 use errors::*;
 use rustup_utils::utils;
 use rustup_utils::notify::NotificationLevel;
@@ -189,6 +191,79 @@ fn show_channel_updates(cfg: &Cfg, toolchains: Vec<(String, rustup::Result<Updat
     Ok(())
 }
 
+/// Prints a summary of changes applied to several toolchains at once,
+/// returning an error if any of them failed
+pub fn show_toolchain_changes(results: Vec<(String, Result<()>)>) -> Result<()> {
+    let results = results.into_iter()
+        .map(|(name, result)| (name, result.map(|()| "changed".to_string())))
+        .collect();
+    show_toolchain_results(results, ErrorKind::ToolchainChangesFailed)
+}
+
+/// Shows the exit code of a command run with each toolchain
+pub fn show_run_results(results: Vec<(String, Result<i32>)>) -> Result<()> {
+    let results = results.into_iter().map(|(name, result)| {
+        let result = result.and_then(|code| {
+            if code == 0 {
+                Ok("passed".to_string())
+            } else {
+                Err(format!("exit code {}", code).into())
+            }
+        });
+        (name, result)
+    }).collect();
+    show_toolchain_results(results, ErrorKind::RunFailed)
+}
+
+/// Prints the banner of each toolchain's result, or why it failed,
+/// returning `error` with the number of failures if there were any
+fn show_toolchain_results(results: Vec<(String, Result<String>)>,
+                          error: fn(usize) -> ErrorKind) -> Result<()> {
+    let max_width = results.iter().fold(0, |a, &(ref name, _)| cmp::max(a, name.len()));
+    let mut failed = 0;
+
+    let mut t = term2::stdout();
+
+    let _ = writeln!(t, "");
+    for (name, result) in results {
+        let padding: String = iter::repeat(' ').take(max_width - name.len()).collect();
+        let _ = write!(t, "  {}", padding);
+        let _ = t.attr(term2::Attr::Bold);
+        let banner = match result {
+            Ok(banner) => {
+                let _ = t.fg(term2::color::BRIGHT_GREEN);
+                banner
+            }
+            Err(e) => {
+                failed += 1;
+                let _ = t.fg(term2::color::BRIGHT_RED);
+                format!("failed - {}", e)
+            }
+        };
+        let _ = write!(t, "{} ", name);
+        let _ = write!(t, "{}", banner);
+        let _ = t.reset();
+        let _ = writeln!(t, "");
+    }
+    let _ = writeln!(t, "");
+
+    if failed > 0 {
+        return Err(error(failed).into());
+    }
+
+    Ok(())
+}
+
+/// The host triple to install for when none is given, which can be
+/// overridden by the environment or the `override-host-triple` setting
+pub fn default_host_triple() -> String {
+    let stored = utils::multirust_home().ok()
+        .and_then(|home| SettingsFile::new(home.join("settings.toml"))
+                  .peek(|s| s.override_host_triple.clone()).ok())
+        .and_then(|t| t);
+    TargetTriple::from_host_or_build_with_override(stored.as_ref().map(|s| &**s)).to_string()
+}
+
 pub fn update_all_channels(cfg: &Cfg, self_update: bool) -> Result<()> {
 
     let toolchains = try!(cfg.update_all_channels());
@@ -198,7 +273,7 @@ pub fn update_all_channels(cfg: &Cfg, self_update: bool) -> Result<()> {
     }
 
     let setup_path = if self_update {
-        try!(self_update::prepare_update())
+        try!(self_update::prepare_update(cfg))
     } else {
         None
     };
@@ -210,6 +285,7 @@ pub fn update_all_channels(cfg: &Cfg, self_update: bool) -> Result<()> {
     }
 
     if let Some(ref setup_path) = setup_path {
+        cfg.audit_log.record("self update", None, None, None);
         try!(self_update::run_update(setup_path));
 
         unreachable!(); // update exits on success
@@ -309,35 +385,65 @@ pub fn list_components(toolchain: &Toolchain) -> Result<()> {
     Ok(())
 }
 
-pub fn list_toolchains(cfg: &Cfg) -> Result<()> {
+pub fn list_toolchains(cfg: &Cfg, verbose: bool) -> Result<()> {
     let toolchains = try!(cfg.list_toolchains());
 
     if toolchains.is_empty() {
         println!("no installed toolchains");
     } else {
-        if let Ok(Some(def_toolchain)) = cfg.find_default() {
-            for toolchain in toolchains {
-                let if_default = if def_toolchain.name() == &*toolchain {
-                    " (default)"
-                } else {
-                    ""
-                };
-                println!("{}{}", &toolchain, if_default);
-            }
-
-        } else {
-            for toolchain in toolchains {
-                println!("{}", &toolchain);
-            }
+        let def_toolchain = cfg.find_default().ok().and_then(|t| t);
+        for toolchain in toolchains {
+            let if_default = match def_toolchain {
+                Some(ref d) if d.name() == &*toolchain => " (default)",
+                _ => "",
+            };
+            let if_system = match cfg.get_toolchain(&toolchain, false) {
+                Ok(ref t) if t.is_system() => " (system)",
+                _ => "",
+            };
+            let if_pinned = if try!(cfg.is_pinned(&toolchain)) { " (pinned)" } else { "" };
+            let details = if verbose {
+                try!(custom_toolchain_details(cfg, &toolchain))
+            } else {
+                String::new()
+            };
+            println!("{}{}{}{}{}", &toolchain, if_default, if_system, if_pinned, details);
         }
     }
     Ok(())
 }
 
+/// Where a custom toolchain links to, whether that still exists, and
+/// the version its rustc reported
+fn custom_toolchain_details(cfg: &Cfg, name: &str) -> Result<String> {
+    let toolchain = try!(cfg.get_toolchain(name, false));
+    if !toolchain.is_custom() {
+        return Ok(String::new());
+    }
+
+    let mut details = String::new();
+    if let Some(target) = toolchain.link_target() {
+        details.push_str(&format!("\t-> {}", target.display()));
+        if !utils::is_directory(&target) {
+            details.push_str(" (missing)");
+        }
+    }
+    if let Some(custom) = try!(toolchain.custom_info()) {
+        details.push_str(&format!("\trustc {}", custom.version));
+        if let Some(host) = custom.host {
+            details.push_str(&format!(" ({})", host));
+        }
+    }
+    Ok(details)
+}
+
 pub fn list_overrides(cfg: &Cfg) -> Result<()> {
     let overrides = try!(cfg.settings_file.with(|s| Ok(s.overrides.clone())));
+    let requirements = try!(cfg.settings_file.with(|s| Ok(s.override_requirements.clone())));
+    let envs = try!(cfg.settings_file.with(|s| Ok(s.override_env.clone())));
+    let tools = try!(cfg.settings_file.with(|s| Ok(s.tool_overrides.clone())));
 
-    if overrides.is_empty() {
+    if overrides.is_empty() && tools.is_empty() {
         println!("no overrides");
     } else {
         let mut any_not_exist = false;
@@ -353,7 +459,23 @@ pub fn list_overrides(cfg: &Cfg) -> Result<()> {
                      } else {
                          " (not a directory)"
                      },
-                     v)
+                     v);
+            if let Some(r) = requirements.get(&k) {
+                if !r.targets.is_empty() {
+                    println!("{:<40}\ttargets: {}", "", r.targets.join(", "));
+                }
+                if !r.components.is_empty() {
+                    println!("{:<40}\tcomponents: {}", "", r.components.join(", "));
+                }
+            }
+            if let Some(vars) = envs.get(&k) {
+                for (var, value) in vars {
+                    println!("{:<40}\t{}={}", "", var, value);
+                }
+            }
+        }
+        for (tool, toolchain) in tools {
+            println!("{:<40}\t{:<20}", format!("{} (tool)", tool), toolchain);
         }
         if any_not_exist {
             println!("");
@@ -38,5 +38,21 @@ error_chain! {
         WindowsUninstallMadness {
             description("failure during windows uninstall")
         }
+        NoMatchingToolchains(p: String) {
+            description("no installed toolchains match")
+            display("no installed toolchains match '{}'", p)
+        }
+        RunFailed(n: usize) {
+            description("the command failed with some toolchains")
+            display("the command failed with {} toolchain(s)", n)
+        }
+        InvalidEnvAssignment(s: String) {
+            description("invalid environment variable assignment")
+            display("invalid environment variable assignment '{}'; expected VAR=VALUE", s)
+        }
+        ToolchainChangesFailed(n: usize) {
+            description("changes could not be applied to some toolchains")
+            display("changes could not be applied to {} toolchain(s)", n)
+        }
     }
 }
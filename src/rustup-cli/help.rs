@@ -103,7 +103,18 @@ r"DISCUSSION:
         $ rustup override set latest-stage1
 
     If you now compile a crate in the current directory, the custom
-    toolchain 'latest-stage1' will be used.";
+    toolchain 'latest-stage1' will be used.
+
+    The directory must contain `bin/rustc`, which is run to record the
+    toolchain's version. `rustup toolchain list -v` shows the version
+    and where each linked toolchain links to.
+
+    If the toolchain was built from the same commit as a published
+    nightly, `rustup target add --toolchain <toolchain> <target>`
+    installs that nightly's standard library for the target. It goes
+    to a separate sysroot in rustup's own directory, leaving the linked
+    directory unchanged. `rustup target remove` removes it again. The
+    targets the toolchain was built with can't be added or removed.";
 
 pub static OVERRIDE_HELP: &'static str =
 r"DISCUSSION:
@@ -123,6 +134,15 @@ r"DISCUSSION:
 
         rustup override set 1.0.0
 
+    Or the release a package declares as its minimum with
+    `rust-version` in Cargo.toml, for the package's directory:
+
+        rustup override set --from-manifest
+
+    With `rustup set manifest-overrides true`, directories without an
+    override use the toolchain for that `rust-version` when it is
+    installed.
+
     To see the active toolchain use `rustup show`. To remove the
     override and use the default toolchain again, `rustup override
     unset`.";
@@ -135,6 +155,150 @@ r"DISCUSSION:
     directories. Otherwise, removes the override toolchain for the
     current directory.";
 
+pub static SET_CONFIG_HELP: &'static str =
+r"DISCUSSION:
+    The value is stored in rustup's settings and used whenever the
+    corresponding environment variable is not set; the environment
+    always takes priority. Pass an empty value to clear the setting.
+
+    Run `rustup config list` to see the value in effect for each
+    setting and where it comes from.";
+
+pub static OVERRIDE_TOOL_HELP: &'static str =
+r"DISCUSSION:
+    Makes the proxy for a tool always run it from the given toolchain,
+    whatever the directory override or default toolchain is. This is
+    useful for tools such as rustfmt and clippy that you want from a
+    particular nightly while cargo and rustc follow the project:
+
+        $ rustup override tool rustfmt nightly-2017-05-01
+        $ rustup override tool cargo-fmt nightly-2017-05-01
+
+    A `+toolchain` argument to the proxy, or the toolchain set with
+    `RUSTUP_TOOLCHAIN`, still takes priority. Use `--unset` to let the
+    tool follow the usual toolchain again, and `rustup which <tool>` to
+    see which binary will run.";
+
+pub static OVERRIDE_LOCK_HELP: &'static str =
+r"DISCUSSION:
+    Writes `rust-toolchain.lock` to the directory of the override that
+    applies to the current directory, or to `--path`. The lock records
+    the date of the release the override toolchain is installed at and
+    the hashes of its packages for every host, so that everyone working
+    in the directory can tell whether they run the same release. Commit
+    the lock file along with the project.
+
+    When the installed release doesn't match the lock, running a tool
+    in the directory prints a warning, or fails if the lock was written
+    with `--strict`. Run this command again to update the lock after
+    updating the toolchain.";
+
+pub static TOOLCHAIN_ENV_HELP: &'static str =
+r"DISCUSSION:
+    Sets environment variables for every command run with the
+    toolchain, for example `RUSTFLAGS` or `CARGO_TARGET_DIR`, so that
+    switching toolchains also switches the settings that go with them:
+
+        $ rustup toolchain env nightly RUSTFLAGS=-Zsanitizer=address
+
+    Without assignments, lists the variables set for the toolchain.
+    Use `--unset VAR` to stop setting a variable.
+
+    Variables can also be set for a directory override with
+    `rustup override set <toolchain> --env VAR=VALUE`; these take
+    priority over the variables of the toolchain.";
+
+pub static TOOLCHAIN_FALLBACK_HELP: &'static str =
+r"DISCUSSION:
+    When a toolchain doesn't have a tool, for example a custom
+    toolchain without `cargo` or `rustfmt`, rustup runs the tool from
+    the first of its fallback toolchains that has it:
+
+        $ rustup toolchain fallback my-toolchain nightly stable
+
+    The tool runs with the original toolchain selected, so `cargo`
+    from the fallback still builds with the original `rustc`.
+
+    Without fallbacks, lists the fallbacks of the toolchain. Unless
+    configured, custom toolchains fall back to nightly, beta and then
+    stable, and other toolchains don't fall back. Use `--unset` to
+    restore the default and `--none` to turn fallbacks off.";
+
+pub static LOG_HELP: &'static str =
+r"DISCUSSION:
+    rustup keeps a log of the changes it makes: toolchains installed,
+    updated and uninstalled, components and targets added and
+    removed, the default toolchain and directory overrides being set,
+    and rustup updating itself. Each change is listed with when it was
+    made, the toolchain and the date of its channel manifest after
+    the change, whether it succeeded, and the user and command that
+    made it.
+
+    The log is `audit.log` in `~/.rustup`. With `--json` the entries
+    are printed as they are stored, one JSON object per line.";
+
+pub static CLEAN_HELP: &'static str =
+r"DISCUSSION:
+    Removes what rustup leaves behind in `~/.rustup`: temporary files
+    and directories of runs that were interrupted, downloads that
+    were interrupted or not cleaned up, and the update hashes of
+    toolchains that have been removed. Each removed path is listed
+    with its size, followed by the total space freed.
+
+    With `--dry-run` nothing is removed, only listed.
+
+    Temporary files and downloads that have been modified in the last
+    hour may belong to a rustup command that's still running, so they
+    are kept.";
+
+pub static TOOLCHAIN_PIN_HELP: &'static str =
+r"DISCUSSION:
+    A pinned toolchain stays at the release it is at when `rustup
+    update` updates all toolchains, so that, for example, a project
+    can keep using the same nightly until it chooses to move:
+
+        $ rustup toolchain pin nightly
+
+    Naming the toolchain, as in `rustup update nightly`, still updates
+    it. `rustup toolchain unpin` lets `rustup update` update it again.";
+
+pub static TOOLCHAIN_INFO_HELP: &'static str =
+r"DISCUSSION:
+    Shows what rustup knows about an installed toolchain: its host,
+    whether it is custom or linked (and to where), its sysroot, the
+    date of the channel manifest it was installed from, the version
+    of each installed package, its components and targets, when it
+    was last installed or updated, and its size on disk.
+
+    Without a toolchain, shows the toolchain for the current
+    directory. Details that come from the channel manifest are only
+    known for toolchains installed by rustup. With `--json` the
+    details are printed as a JSON object for use by other tools.";
+
+pub static ENV_HELP: &'static str =
+r"DISCUSSION:
+    Prints the variables rustup sets when it runs a toolchain's tools,
+    so that IDEs and build systems that run the tools directly can use
+    the same environment. These are the loader path, `PATH`,
+    `RUSTUP_TOOLCHAIN`, `RUSTUP_HOME`, `CARGO_HOME`, and the variables
+    set with `rustup toolchain env`. The toolchain's sysroot is given
+    in a comment on the first line.
+
+    By default the toolchain is the one for the current directory,
+    and the syntax is that of the current shell. To apply the
+    environment to a bash shell:
+
+        $ eval "$(rustup env --toolchain nightly)"
+
+    `--shell json` prints the toolchain, its sysroot and the variables
+    as a JSON object.";
+
+pub static SHELL_HELP: &'static str =
+r"DISCUSSION:
+    Starts `$SHELL` (`%COMSPEC%` on Windows) with the environment of
+    the given toolchain, as printed by `rustup env`. Tools run from the
+    shell use the toolchain until the shell exits.";
+
 pub static RUN_HELP: &'static str =
 r"DISCUSSION:
     Configures an environment to use the given toolchain and then runs
@@ -149,7 +313,17 @@ r"DISCUSSION:
 
         cargo +nightly build
 
-        rustup run nightly cargo build";
+        rustup run nightly cargo build
+
+    With `--each`, the toolchain is instead a comma-separated list of
+    toolchains. The command is run once with each of them, and a
+    summary shows which it passed and failed with:
+
+        rustup run --each stable,beta,nightly,1.13.0 cargo test
+
+    Globs such as `nightly-*` select the installed toolchains that
+    match. Missing toolchains count as failures unless `--install` is
+    given.";
 
 pub static DOC_HELP: &'static str =
 r"DISCUSSION:
@@ -159,6 +333,30 @@ r"DISCUSSION:
     By default, it opens the documentation index. Use the various
     flags to open specific pieces of documentation.";
 
+pub static TOOLCHAIN_ARG_HELP: &'static str =
+    "Toolchain name, a comma-separated list of names, `all`, or a glob such as `nightly-*`";
+
+pub static EXPORT_HELP: &'static str =
+r"DISCUSSION:
+    `rustup export` captures the toolchains installed from a release
+    channel, each with the date of the manifest it was installed from
+    and its components and targets, along with the default toolchain,
+    the directory overrides and all other settings.
+
+    `rustup import` installs those toolchains at the same dates, adds
+    their components and targets, and then takes the default
+    toolchain, overrides, profile, fallbacks, pins and environment
+    variables from the exported settings. This rebuilds the same setup
+    on a new machine:
+
+        $ rustup export > rustup-state.toml
+        $ rustup import rustup-state.toml
+
+    Settings that describe the machine, such as the host triple, the
+    GPG key and the download backend, are kept as they are. Custom
+    toolchains can't be exported and are skipped, along with their
+    fallbacks.";
+
 pub static COMPLETIONS_HELP: &'static str =
 r"DISCUSSION:
     One can generate a completion script for `rustup` that is
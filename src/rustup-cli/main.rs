@@ -31,6 +31,7 @@ extern crate tempdir;
 extern crate sha2;
 extern crate markdown;
 extern crate toml;
+extern crate rustc_serialize;
 extern crate wait_timeout;
 
 #[cfg(windows)]
@@ -61,7 +62,6 @@ mod help;
 use std::env;
 use std::path::PathBuf;
 use errors::*;
-use rustup_dist::dist::TargetTriple;
 use rustup::env_var::RUST_RECURSION_COUNT_MAX;
 
 fn main() {
@@ -111,8 +111,9 @@ fn run_rustup() -> Result<()> {
             // `self install` as the arguments.  FIXME: Verify this
             // works.
             let opts = self_update::InstallOpts {
-                default_host_triple: TargetTriple::from_host_or_build().to_string(),
+                default_host_triple: common::default_host_triple(),
                 default_toolchain: "stable".to_string(),
+                profile: "default".to_string(),
                 no_modify_path: false,
             };
             if cfg!(windows) {
@@ -2,7 +2,7 @@ use common::set_globals;
 use rustup::{Cfg};
 use errors::*;
 use rustup_utils::utils;
-use rustup::command::run_command_for_dir;
+use rustup::command::{run_command_for_dir, run_command_for_dir_without_telemetry};
 use std::env;
 use std::ffi::OsString;
 use std::path::PathBuf;
@@ -39,6 +39,16 @@ pub fn main() -> Result<()> {
         env::args_os().skip(2).collect()
     };
 
+    // A directory the proxy cache knows about is run without reading
+    // the settings. Telemetry is never on for a cached toolchain.
+    if toolchain.is_none() {
+        let ref multirust_dir = try!(utils::multirust_home());
+        let ref cwd = try!(utils::current_dir());
+        if let Some(cmd) = Cfg::cached_command_for_dir(multirust_dir, cwd, arg0) {
+            return Ok(try!(run_command_for_dir_without_telemetry(cmd, arg0, &cmd_args)));
+        }
+    }
+
     let cfg = try!(set_globals(false));
     try!(cfg.check_metadata_version());
     try!(direct_proxy(&cfg, arg0, toolchain, &cmd_args));
@@ -47,9 +57,15 @@ pub fn main() -> Result<()> {
 }
 
 fn direct_proxy(cfg: &Cfg, arg0: &str, toolchain: Option<&str>, args: &[OsString]) -> Result<()> {
+    // A toolchain set for this tool wins over the directory override,
+    // but not over `+toolchain` or `RUSTUP_TOOLCHAIN`
+    let toolchain = match toolchain {
+        Some(tc) => Some(tc.to_owned()),
+        None => try!(cfg.tool_override(arg0)),
+    };
     let cmd = match toolchain {
-        None => try!(cfg.create_command_for_dir(&try!(utils::current_dir()), arg0)),
-        Some(tc) => try!(cfg.create_command_for_toolchain(tc, arg0)),
+        None => try!(cfg.create_command_for_dir(&try!(utils::current_dir()), arg0, args)),
+        Some(ref tc) => try!(cfg.create_command_for_toolchain(tc, arg0, args)),
     };
     Ok(try!(run_command_for_dir(cmd, arg0, args, &cfg)))
 }
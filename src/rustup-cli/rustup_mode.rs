@@ -1,18 +1,22 @@
 use clap::{App, Arg, ArgGroup, AppSettings, SubCommand, ArgMatches, Shell};
 use common;
 use rustup::{Cfg, Toolchain, command};
-use rustup::settings::TelemetryMode;
+use rustup::settings::{TelemetryMode, OverrideRequirements, CONFIG_KEYS};
+use rustup::state::State;
 use errors::*;
 use rustup_dist::manifest::Component;
-use rustup_dist::dist::{TargetTriple, PartialToolchainDesc, PartialTargetTriple};
+use rustup_dist::dist::{TargetTriple, PartialToolchainDesc, PartialTargetTriple, Profile};
 use rustup_utils::utils;
 use self_update;
-use std::path::Path;
+use std::env;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::iter;
 use term2;
 use std::io::{self, Write};
 use help::*;
+use download_tracker::HumanReadable;
+use time;
 
 pub fn main() -> Result<()> {
     try!(::self_update::cleanup_self_updater());
@@ -25,7 +29,14 @@ pub fn main() -> Result<()> {
         return Ok(())
     }
 
-    try!(cfg.check_metadata_version());
+    // Updating rustup doesn't read the metadata, so a rustup too old
+    // to read it can still update itself
+    let self_update = matches.subcommand_matches("self")
+        .map(|c| c.subcommand_name() == Some("update"))
+        .unwrap_or(false);
+    if !self_update {
+        try!(cfg.check_metadata_version());
+    }
 
     match matches.subcommand() {
         ("show", Some(_)) => try!(show(cfg)),
@@ -36,8 +47,13 @@ pub fn main() -> Result<()> {
         ("toolchain", Some(c)) => {
             match c.subcommand() {
                 ("install", Some(m)) => try!(update(cfg, m)),
-                ("list", Some(_)) => try!(common::list_toolchains(cfg)),
+                ("list", Some(m)) => try!(common::list_toolchains(cfg, m.is_present("verbose"))),
                 ("link", Some(m)) => try!(toolchain_link(cfg, m)),
+                ("env", Some(m)) => try!(toolchain_env(cfg, m)),
+                ("fallback", Some(m)) => try!(toolchain_fallback(cfg, m)),
+                ("info", Some(m)) => try!(toolchain_info(cfg, m)),
+                ("pin", Some(m)) => try!(toolchain_pin(cfg, m, true)),
+                ("unpin", Some(m)) => try!(toolchain_pin(cfg, m, false)),
                 ("uninstall", Some(m)) => try!(toolchain_remove(cfg, m)),
                 // Synonyms
                 ("update", Some(m)) => try!(update(cfg, m)),
@@ -70,6 +86,8 @@ pub fn main() -> Result<()> {
                 ("list", Some(_)) => try!(common::list_overrides(cfg)),
                 ("set", Some(m)) => try!(override_add(cfg, m)),
                 ("unset", Some(m)) => try!(override_remove(cfg, m)),
+                ("lock", Some(m)) => try!(override_lock(cfg, m)),
+                ("tool", Some(m)) => try!(override_tool(cfg, m)),
                 // Synonyms
                 ("add", Some(m)) => try!(override_add(cfg, m)),
                 ("remove", Some(m)) => try!(override_remove(cfg, m)),
@@ -78,11 +96,15 @@ pub fn main() -> Result<()> {
         }
         ("run", Some(m)) => try!(run(cfg, m)),
         ("which", Some(m)) => try!(which(cfg, m)),
+        ("env", Some(m)) => try!(env_(cfg, m)),
+        ("shell", Some(m)) => try!(shell(cfg, m)),
         ("doc", Some(m)) => try!(doc(cfg, m)),
         ("man", Some(m)) => try!(man(cfg,m)),
+        ("clean", Some(m)) => try!(clean(cfg, m)),
+        ("log", Some(m)) => try!(show_log(cfg, m)),
         ("self", Some(c)) => {
             match c.subcommand() {
-                ("update", Some(_)) => try!(self_update::update()),
+                ("update", Some(_)) => try!(self_update::update(cfg)),
                 ("uninstall", Some(m)) => try!(self_uninstall(m)),
                 (_ ,_) => unreachable!(),
             }
@@ -98,9 +120,21 @@ pub fn main() -> Result<()> {
         ("set", Some(c)) => {
             match c.subcommand() {
                 ("default-host", Some(m)) => try!(set_default_host_triple(&cfg, m)),
+                ("profile", Some(m)) => try!(set_profile(&cfg, m)),
+                ("auto-install", Some(m)) => try!(set_auto_install(&cfg, m)),
+                ("manifest-overrides", Some(m)) => try!(set_manifest_overrides(&cfg, m)),
+                (key, Some(m)) => try!(set_config(&cfg, key, m)),
+                (_, _) => unreachable!(),
+            }
+        }
+        ("config", Some(c)) => {
+            match c.subcommand() {
+                ("list", Some(_)) => try!(config_list(&cfg)),
                 (_, _) => unreachable!(),
             }
         }
+        ("export", Some(_)) => try!(export(&cfg)),
+        ("import", Some(m)) => try!(import(&cfg, m)),
         ("completions", Some(c)) => {
             if let Some(shell) = c.value_of("shell") {
                 cli().gen_completions_to("rustup", shell.parse::<Shell>().unwrap(), &mut io::stdout());
@@ -133,7 +167,8 @@ pub fn cli() -> App<'static, 'static> {
             .setting(AppSettings::Hidden) // synonym for 'toolchain install'
             .arg(Arg::with_name("toolchain")
                 .required(true)
-                .multiple(true)))
+                .multiple(true))
+            .arg(profile_arg()))
         .subcommand(SubCommand::with_name("uninstall")
             .about("Uninstall Rust toolchains")
             .setting(AppSettings::Hidden) // synonym for 'toolchain uninstall'
@@ -163,12 +198,17 @@ pub fn cli() -> App<'static, 'static> {
             .setting(AppSettings::DeriveDisplayOrder)
             .setting(AppSettings::SubcommandRequiredElseHelp)
             .subcommand(SubCommand::with_name("list")
-                .about("List installed toolchains"))
+                .about("List installed toolchains")
+                .arg(Arg::with_name("verbose")
+                    .help("Show where linked toolchains link to, and the version of custom toolchains")
+                    .short("v")
+                    .long("verbose")))
             .subcommand(SubCommand::with_name("install")
                 .about("Install or update a given toolchain")
                 .arg(Arg::with_name("toolchain")
                      .required(true)
-                     .multiple(true)))
+                     .multiple(true))
+                .arg(profile_arg()))
             .subcommand(SubCommand::with_name("uninstall")
                 .about("Uninstall a toolchain")
                 .arg(Arg::with_name("toolchain")
@@ -181,16 +221,64 @@ pub fn cli() -> App<'static, 'static> {
                     .required(true))
                 .arg(Arg::with_name("path")
                     .required(true)))
+            .subcommand(SubCommand::with_name("env")
+                .about("Show or change the variables set when running a toolchain's tools")
+                .after_help(TOOLCHAIN_ENV_HELP)
+                .arg(Arg::with_name("toolchain")
+                    .required(true))
+                .arg(Arg::with_name("assignment")
+                    .help("A variable to set, as VAR=VALUE")
+                    .multiple(true))
+                .arg(Arg::with_name("unset")
+                    .help("A variable to stop setting")
+                    .long("unset")
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1)))
+            .subcommand(SubCommand::with_name("fallback")
+                .about("Show or change the toolchains that provide the tools a toolchain lacks")
+                .after_help(TOOLCHAIN_FALLBACK_HELP)
+                .arg(Arg::with_name("toolchain")
+                    .required(true))
+                .arg(Arg::with_name("fallback")
+                    .help("Toolchains to search, in order")
+                    .multiple(true)
+                    .conflicts_with_all(&["unset", "none"]))
+                .arg(Arg::with_name("unset")
+                    .help("Restore the default fallbacks")
+                    .long("unset")
+                    .conflicts_with("none"))
+                .arg(Arg::with_name("none")
+                    .help("Don't fall back to any toolchain")
+                    .long("none")))
+            .subcommand(SubCommand::with_name("pin")
+                .about("Keep `rustup update` from updating a toolchain unless it's named")
+                .after_help(TOOLCHAIN_PIN_HELP)
+                .arg(Arg::with_name("toolchain")
+                    .required(true)))
+            .subcommand(SubCommand::with_name("unpin")
+                .about("Let `rustup update` update a pinned toolchain again")
+                .arg(Arg::with_name("toolchain")
+                    .required(true)))
+            .subcommand(SubCommand::with_name("info")
+                .about("Show details of an installed toolchain")
+                .after_help(TOOLCHAIN_INFO_HELP)
+                .arg(Arg::with_name("toolchain"))
+                .arg(Arg::with_name("json")
+                    .help("Print the details as JSON")
+                    .long("json")))
             .subcommand(SubCommand::with_name("update")
                 .setting(AppSettings::Hidden) // synonym for 'install'
                 .arg(Arg::with_name("toolchain")
                      .required(true)
-                     .multiple(true)))
+                     .multiple(true))
+                .arg(profile_arg()))
             .subcommand(SubCommand::with_name("add")
                 .setting(AppSettings::Hidden) // synonym for 'install'
                 .arg(Arg::with_name("toolchain")
                      .required(true)
-                     .multiple(true)))
+                     .multiple(true))
+                .arg(profile_arg()))
             .subcommand(SubCommand::with_name("remove")
                 .setting(AppSettings::Hidden) // synonym for 'uninstall'
                 .arg(Arg::with_name("toolchain")
@@ -212,6 +300,7 @@ pub fn cli() -> App<'static, 'static> {
                     .required(true)
                     .multiple(true))
                 .arg(Arg::with_name("toolchain")
+                    .help(TOOLCHAIN_ARG_HELP)
                     .long("toolchain")
                     .takes_value(true)))
             .subcommand(SubCommand::with_name("remove")
@@ -220,6 +309,7 @@ pub fn cli() -> App<'static, 'static> {
                     .required(true)
                     .multiple(true))
                 .arg(Arg::with_name("toolchain")
+                    .help(TOOLCHAIN_ARG_HELP)
                     .long("toolchain")
                     .takes_value(true)))
             .subcommand(SubCommand::with_name("install")
@@ -228,6 +318,7 @@ pub fn cli() -> App<'static, 'static> {
                     .required(true)
                     .multiple(true))
                 .arg(Arg::with_name("toolchain")
+                    .help(TOOLCHAIN_ARG_HELP)
                     .long("toolchain")
                     .takes_value(true)))
             .subcommand(SubCommand::with_name("uninstall")
@@ -236,6 +327,7 @@ pub fn cli() -> App<'static, 'static> {
                     .required(true)
                     .multiple(true))
                 .arg(Arg::with_name("toolchain")
+                    .help(TOOLCHAIN_ARG_HELP)
                     .long("toolchain")
                     .takes_value(true))))
         .subcommand(SubCommand::with_name("component")
@@ -254,6 +346,7 @@ pub fn cli() -> App<'static, 'static> {
                     .required(true)
                     .multiple(true))
                 .arg(Arg::with_name("toolchain")
+                    .help(TOOLCHAIN_ARG_HELP)
                     .long("toolchain")
                     .takes_value(true))
                 .arg(Arg::with_name("target")
@@ -265,6 +358,7 @@ pub fn cli() -> App<'static, 'static> {
                     .required(true)
                     .multiple(true))
                 .arg(Arg::with_name("toolchain")
+                    .help(TOOLCHAIN_ARG_HELP)
                     .long("toolchain")
                     .takes_value(true))
                 .arg(Arg::with_name("target")
@@ -281,7 +375,30 @@ pub fn cli() -> App<'static, 'static> {
             .subcommand(SubCommand::with_name("set")
                 .about("Set the override toolchain for a directory")
                 .arg(Arg::with_name("toolchain")
-                     .required(true)))
+                     .required_unless("from-manifest")
+                     .conflicts_with("from-manifest"))
+                .arg(Arg::with_name("from-manifest")
+                     .help("Use the rust-version declared in the nearest Cargo.toml, \
+                            for the directory containing it")
+                     .long("from-manifest"))
+                .arg(Arg::with_name("target")
+                     .help("A target the directory requires, installed with the toolchain")
+                     .long("target")
+                     .takes_value(true)
+                     .multiple(true)
+                     .number_of_values(1))
+                .arg(Arg::with_name("component")
+                     .help("A component the directory requires, installed with the toolchain")
+                     .long("component")
+                     .takes_value(true)
+                     .multiple(true)
+                     .number_of_values(1))
+                .arg(Arg::with_name("env")
+                     .help("A variable set when running tools in the directory, as VAR=VALUE")
+                     .long("env")
+                     .takes_value(true)
+                     .multiple(true)
+                     .number_of_values(1)))
             .subcommand(SubCommand::with_name("unset")
                 .about("Remove the override toolchain for a directory")
                 .after_help(OVERRIDE_UNSET_HELP)
@@ -293,10 +410,48 @@ pub fn cli() -> App<'static, 'static> {
                     .long("nonexistent")
                     .takes_value(false)
                     .help("Remove override toolchain for all nonexistent directories")))
+            .subcommand(SubCommand::with_name("tool")
+                .about("Set the toolchain a tool always runs from, in every directory")
+                .after_help(OVERRIDE_TOOL_HELP)
+                .arg(Arg::with_name("tool")
+                    .required(true))
+                .arg(Arg::with_name("toolchain")
+                    .required_unless("unset"))
+                .arg(Arg::with_name("unset")
+                    .long("unset")
+                    .help("Remove the toolchain set for the tool")))
+            .subcommand(SubCommand::with_name("lock")
+                .about("Pin the override toolchain to the release it is installed at")
+                .after_help(OVERRIDE_LOCK_HELP)
+                .arg(Arg::with_name("path")
+                    .long("path")
+                    .takes_value(true)
+                    .help("Path to the directory"))
+                .arg(Arg::with_name("strict")
+                    .long("strict")
+                    .help("Refuse to run tools when the installed release doesn't match")))
             .subcommand(SubCommand::with_name("add")
                 .setting(AppSettings::Hidden) // synonym for 'set'
                 .arg(Arg::with_name("toolchain")
-                     .required(true)))
+                     .required(true))
+                .arg(Arg::with_name("target")
+                     .help("A target the directory requires, installed with the toolchain")
+                     .long("target")
+                     .takes_value(true)
+                     .multiple(true)
+                     .number_of_values(1))
+                .arg(Arg::with_name("component")
+                     .help("A component the directory requires, installed with the toolchain")
+                     .long("component")
+                     .takes_value(true)
+                     .multiple(true)
+                     .number_of_values(1))
+                .arg(Arg::with_name("env")
+                     .help("A variable set when running tools in the directory, as VAR=VALUE")
+                     .long("env")
+                     .takes_value(true)
+                     .multiple(true)
+                     .number_of_values(1)))
             .subcommand(SubCommand::with_name("remove")
                 .setting(AppSettings::Hidden) // synonym for 'unset'
                 .about("Remove the override toolchain for a directory")
@@ -311,6 +466,14 @@ pub fn cli() -> App<'static, 'static> {
             .about("Run a command with an environment configured for a given toolchain")
             .after_help(RUN_HELP)
             .setting(AppSettings::TrailingVarArg)
+            .arg(Arg::with_name("each")
+                .help("Run the command with each of the toolchains given as a comma-separated \
+                       list of names or globs such as `nightly-*`")
+                .long("each"))
+            .arg(Arg::with_name("install")
+                .help("Install missing toolchains")
+                .long("install")
+                .requires("each"))
             .arg(Arg::with_name("toolchain")
                 .required(true))
             .arg(Arg::with_name("command")
@@ -319,6 +482,23 @@ pub fn cli() -> App<'static, 'static> {
             .about("Display which binary will be run for a given command")
             .arg(Arg::with_name("command")
                 .required(true)))
+        .subcommand(SubCommand::with_name("env")
+            .about("Print the environment the tools of a toolchain run with")
+            .after_help(ENV_HELP)
+            .arg(Arg::with_name("toolchain")
+                .help("Toolchain name, such as 'stable', 'nightly', or '1.8.0'")
+                .long("toolchain")
+                .takes_value(true))
+            .arg(Arg::with_name("shell")
+                .help("The syntax to print the variables in")
+                .long("shell")
+                .takes_value(true)
+                .possible_values(&["bash", "fish", "powershell", "json"])))
+        .subcommand(SubCommand::with_name("shell")
+            .about("Start a shell with the environment of a toolchain")
+            .after_help(SHELL_HELP)
+            .arg(Arg::with_name("toolchain")
+                .required(true)))
         .subcommand(SubCommand::with_name("doc")
             .alias("docs")
             .about("Open the documentation for the current toolchain")
@@ -338,6 +518,27 @@ pub fn cli() -> App<'static, 'static> {
                     .arg(Arg::with_name("toolchain")
                          .long("toolchain")
                          .takes_value(true)))
+        .subcommand(SubCommand::with_name("clean")
+            .about("Remove temporary files, interrupted downloads and stale update hashes")
+            .after_help(CLEAN_HELP)
+            .arg(Arg::with_name("dry-run")
+                .help("Only list what would be removed")
+                .long("dry-run")))
+        .subcommand(SubCommand::with_name("log")
+            .about("Show the changes rustup has made to toolchains and settings")
+            .after_help(LOG_HELP)
+            .arg(Arg::with_name("toolchain")
+                .help("Only show changes to this toolchain")
+                .long("toolchain")
+                .takes_value(true))
+            .arg(Arg::with_name("limit")
+                .help("Only show this many of the latest changes")
+                .short("n")
+                .long("limit")
+                .takes_value(true))
+            .arg(Arg::with_name("json")
+                .help("Print the changes as JSON, one object per line")
+                .long("json")))
         .subcommand(SubCommand::with_name("self")
             .about("Modify the rustup installation")
             .setting(AppSettings::VersionlessSubcommands)
@@ -363,13 +564,49 @@ pub fn cli() -> App<'static, 'static> {
                             .about("Disable rustup telemetry"))
             .subcommand(SubCommand::with_name("analyze")
                             .about("Analyze stored telemetry")))
-        .subcommand(SubCommand::with_name("set")
+        .subcommand(CONFIG_KEYS.iter().fold(SubCommand::with_name("set")
             .about("Alter rustup settings")
             .setting(AppSettings::SubcommandRequiredElseHelp)
             .subcommand(SubCommand::with_name("default-host")
                 .about("The triple used to identify toolchains when not specified")
                 .arg(Arg::with_name("host_triple")
-                    .required(true))))
+                    .required(true)))
+            .subcommand(SubCommand::with_name("profile")
+                .about("The default components installed with a new toolchain")
+                .arg(Arg::with_name("profile-name")
+                    .required(true)
+                    .possible_values(Profile::names())))
+            .subcommand(SubCommand::with_name("auto-install")
+                .about("Install missing targets and components required by overrides or by the tools being run")
+                .arg(Arg::with_name("enabled")
+                    .required(true)
+                    .possible_values(&["true", "false"])))
+            .subcommand(SubCommand::with_name("manifest-overrides")
+                .about("Use the rust-version declared in Cargo.toml in directories without an override")
+                .arg(Arg::with_name("enabled")
+                    .required(true)
+                    .possible_values(&["true", "false"]))),
+            |set, &(key, _, about)| {
+                set.subcommand(SubCommand::with_name(key)
+                    .about(about)
+                    .after_help(SET_CONFIG_HELP)
+                    .arg(Arg::with_name("value")
+                        .required(true)))
+            }))
+        .subcommand(SubCommand::with_name("config")
+            .about("Inspect rustup settings")
+            .setting(AppSettings::VersionlessSubcommands)
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(SubCommand::with_name("list")
+                .about("List settings, their values, and where each value comes from")))
+        .subcommand(SubCommand::with_name("export")
+            .about("Write the installed toolchains and settings to stdout")
+            .after_help(EXPORT_HELP))
+        .subcommand(SubCommand::with_name("import")
+            .about("Install the toolchains and restore the settings of an exported state")
+            .after_help(EXPORT_HELP)
+            .arg(Arg::with_name("file")
+                .required(true)))
         .subcommand(SubCommand::with_name("completions")
             .about("Generate completion scripts for your shell")
             .after_help(COMPLETIONS_HELP)
@@ -378,6 +615,15 @@ pub fn cli() -> App<'static, 'static> {
                 .possible_values(&Shell::variants())))
 }
 
+/// The `--profile` option of the commands that install toolchains
+fn profile_arg() -> Arg<'static, 'static> {
+    Arg::with_name("profile")
+        .help("Components to install with a new toolchain")
+        .long("profile")
+        .takes_value(true)
+        .possible_values(Profile::names())
+}
+
 fn maybe_upgrade_data(cfg: &Cfg, m: &ArgMatches) -> Result<bool> {
     match m.subcommand() {
         ("self", Some(c)) => {
@@ -480,13 +726,17 @@ fn default_(cfg: &Cfg, m: &ArgMatches) -> Result<()> {
 }
 
 fn update(cfg: &Cfg, m: &ArgMatches) -> Result<()> {
+    let profile = match m.value_of("profile") {
+        Some(p) => Some(try!(Profile::from_str(p))),
+        None => None,
+    };
     if let Some(names) = m.values_of("toolchain") {
         for name in names {
             try!(update_bare_triple_check(cfg, name));
             let toolchain = try!(cfg.get_toolchain(name, false));
 
             let status = if !toolchain.is_custom() {
-                Some(try!(toolchain.install_from_dist()))
+                Some(try!(toolchain.install_from_dist_with_profile(profile)))
             } else if !toolchain.exists() {
                 return Err(ErrorKind::ToolchainNotInstalled(toolchain.name().to_string()).into());
             } else {
@@ -509,11 +759,141 @@ fn run(cfg: &Cfg, m: &ArgMatches) -> Result<()> {
     let ref toolchain = m.value_of("toolchain").expect("");
     let args = m.values_of("command").unwrap();
     let args: Vec<_> = args.collect();
-    let cmd = try!(cfg.create_command_for_toolchain(toolchain, args[0]));
+
+    // With `--each` the toolchain argument lists the toolchains
+    if m.is_present("each") {
+        return run_each(cfg, toolchain, m.is_present("install"), &args);
+    }
+
+    let cmd = try!(cfg.create_command_for_toolchain(toolchain, args[0], &args[1..]));
 
     Ok(try!(command::run_command_for_dir(cmd, args[0], &args[1..], &cfg)))
 }
 
+/// Runs the command once with each toolchain of `spec`, then shows
+/// which toolchains it passed and failed on
+fn run_each(cfg: &Cfg, spec: &str, install: bool, args: &[&str]) -> Result<()> {
+    // Any toolchain can run a command, custom ones included
+    let names = try!(toolchain_names_from_spec(cfg, spec, true));
+
+    let mut results = Vec::new();
+    for name in names {
+        info!("running '{}' with toolchain '{}'", args.join(" "), name);
+        let result = run_with_toolchain(cfg, &name, install, args);
+        if let Err(ref e) = result {
+            err!("{}", e);
+        }
+        results.push((name, result));
+    }
+
+    common::show_run_results(results)
+}
+
+fn run_with_toolchain(cfg: &Cfg, name: &str, install: bool, args: &[&str]) -> Result<i32> {
+    let toolchain = try!(cfg.get_toolchain(name, false));
+    if !toolchain.exists() {
+        if install && !toolchain.is_custom() {
+            try!(toolchain.install_from_dist());
+        } else {
+            return Err(ErrorKind::ToolchainNotInstalled(name.to_owned()).into());
+        }
+    }
+
+    let mut cmd = try!(cfg.create_command_for_toolchain(name, args[0], &args[1..]));
+    cmd.args(&args[1..]);
+    let status = try!(cmd.status().chain_err(|| {
+        ::rustup_utils::ErrorKind::RunningCommand { name: args[0].into() }
+    }));
+    Ok(status.code().unwrap_or(1))
+}
+
+fn env_(cfg: &Cfg, m: &ArgMatches) -> Result<()> {
+    let toolchain = if let Some(name) = m.value_of("toolchain") {
+        try!(cfg.get_toolchain(name, false))
+    } else {
+        try!(cfg.toolchain_for_dir(&try!(utils::current_dir()))).0
+    };
+    if !toolchain.exists() {
+        return Err(ErrorKind::ToolchainNotInstalled(toolchain.name().to_owned()).into());
+    }
+
+    let vars: Vec<(String, String)> = toolchain.env_vars().into_iter()
+        .map(|(var, value)| (var, value.to_string_lossy().into_owned()))
+        .collect();
+    let sysroot = toolchain.path().to_string_lossy().into_owned();
+
+    let shell = m.value_of("shell").map(|s| s.to_owned()).unwrap_or_else(default_shell);
+    if shell == "json" {
+        use rustc_serialize::json::Json;
+        use std::collections::BTreeMap;
+
+        let mut env = BTreeMap::new();
+        for (var, value) in vars {
+            env.insert(var, Json::String(value));
+        }
+        let mut result = BTreeMap::new();
+        result.insert("toolchain".to_owned(), Json::String(toolchain.name().to_owned()));
+        result.insert("sysroot".to_owned(), Json::String(sysroot));
+        result.insert("env".to_owned(), Json::Object(env));
+        println!("{}", Json::Object(result).pretty());
+        return Ok(());
+    }
+
+    // No tool reads the sysroot from the environment, so it's only
+    // reported, in a comment all the shells accept
+    println!("# sysroot: {}", sysroot);
+    for &(ref var, ref value) in &vars {
+        println!("{}", match &*shell {
+            "fish" if var.ends_with("PATH") => {
+                let parts = env::split_paths(value)
+                    .map(|p| fish_quote(&p.to_string_lossy()))
+                    .collect::<Vec<_>>();
+                format!("set -gx {} {};", var, parts.join(" "))
+            }
+            "fish" => format!("set -gx {} {};", var, fish_quote(value)),
+            "powershell" => format!("$env:{} = '{}'", var, value.replace("'", "''")),
+            _ => format!("export {}='{}';", var, value.replace("'", "'\\''")),
+        });
+    }
+    Ok(())
+}
+
+/// The syntax `rustup env` prints in when no shell is given
+fn default_shell() -> String {
+    if cfg!(windows) {
+        "powershell".to_owned()
+    } else if env::var("SHELL").map(|s| s.ends_with("fish")).unwrap_or(false) {
+        "fish".to_owned()
+    } else {
+        "bash".to_owned()
+    }
+}
+
+fn fish_quote(value: &str) -> String {
+    format!("'{}'", value.replace("\\", "\\\\").replace("'", "\\'"))
+}
+
+fn shell(cfg: &Cfg, m: &ArgMatches) -> Result<()> {
+    let toolchain = try!(cfg.get_toolchain(m.value_of("toolchain").expect(""), false));
+    if !toolchain.exists() {
+        return Err(ErrorKind::ToolchainNotInstalled(toolchain.name().to_owned()).into());
+    }
+
+    let shell = if cfg!(windows) {
+        env::var_os("COMSPEC").unwrap_or("cmd.exe".into())
+    } else {
+        env::var_os("SHELL").unwrap_or("/bin/sh".into())
+    };
+    let mut cmd = Command::new(&shell);
+    for (var, value) in toolchain.env_vars() {
+        cmd.env(var, value);
+    }
+
+    info!("starting a shell for toolchain '{}'; exit it to return", toolchain.name());
+    let args: &[&str] = &[];
+    Ok(try!(command::run_command_for_dir(cmd, &shell.to_string_lossy(), args, cfg)))
+}
+
 fn which(cfg: &Cfg, m: &ArgMatches) -> Result<()> {
     let binary = m.value_of("command").expect("");
 
@@ -629,33 +1009,33 @@ fn target_list(cfg: &Cfg, m: &ArgMatches) -> Result<()> {
 }
 
 fn target_add(cfg: &Cfg, m: &ArgMatches) -> Result<()> {
-    let toolchain = try!(explicit_or_dir_toolchain(cfg, m));
-
-    for target in m.values_of("target").expect("") {
-        let new_component = Component {
-            pkg: "rust-std".to_string(),
-            target: Some(TargetTriple::from_str(target)),
-        };
+    for_each_toolchain(cfg, m, &|toolchain| {
+        for target in m.values_of("target").expect("") {
+            let new_component = Component {
+                pkg: "rust-std".to_string(),
+                target: Some(TargetTriple::from_str(target)),
+            };
 
-        try!(toolchain.add_component(new_component));
-    }
+            try!(toolchain.add_component(new_component));
+        }
 
-    Ok(())
+        Ok(())
+    })
 }
 
 fn target_remove(cfg: &Cfg, m: &ArgMatches) -> Result<()> {
-    let toolchain = try!(explicit_or_dir_toolchain(cfg, m));
-
-    for target in m.values_of("target").expect("") {
-        let new_component = Component {
-            pkg: "rust-std".to_string(),
-            target: Some(TargetTriple::from_str(target)),
-        };
+    for_each_toolchain(cfg, m, &|toolchain| {
+        for target in m.values_of("target").expect("") {
+            let new_component = Component {
+                pkg: "rust-std".to_string(),
+                target: Some(TargetTriple::from_str(target)),
+            };
 
-        try!(toolchain.remove_component(new_component));
-    }
+            try!(toolchain.remove_component(new_component));
+        }
 
-    Ok(())
+        Ok(())
+    })
 }
 
 fn component_list(cfg: &Cfg, m: &ArgMatches) -> Result<()> {
@@ -665,39 +1045,109 @@ fn component_list(cfg: &Cfg, m: &ArgMatches) -> Result<()> {
 }
 
 fn component_add(cfg: &Cfg, m: &ArgMatches) -> Result<()> {
-    let toolchain = try!(explicit_or_dir_toolchain(cfg, m));
-    let target = m.value_of("target").map(TargetTriple::from_str).or_else(|| {
-        toolchain.desc().as_ref().ok().map(|desc| desc.target.clone())
-    });
-
-    for component in m.values_of("component").expect("") {
-        let new_component = Component {
-            pkg: component.to_string(),
-            target: target.clone(),
-        };
+    for_each_toolchain(cfg, m, &|toolchain| {
+        let target = m.value_of("target").map(TargetTriple::from_str).or_else(|| {
+            toolchain.desc().as_ref().ok().map(|desc| desc.target.clone())
+        });
+
+        for component in m.values_of("component").expect("") {
+            let new_component = Component {
+                pkg: component.to_string(),
+                target: target.clone(),
+            };
 
-        try!(toolchain.add_component(new_component));
-    }
+            try!(toolchain.add_component(new_component));
+        }
 
-    Ok(())
+        Ok(())
+    })
 }
 
 fn component_remove(cfg: &Cfg, m: &ArgMatches) -> Result<()> {
-    let toolchain = try!(explicit_or_dir_toolchain(cfg, m));
-    let target = m.value_of("target").map(TargetTriple::from_str).or_else(|| {
-        toolchain.desc().as_ref().ok().map(|desc| desc.target.clone())
-    });
+    for_each_toolchain(cfg, m, &|toolchain| {
+        let target = m.value_of("target").map(TargetTriple::from_str).or_else(|| {
+            toolchain.desc().as_ref().ok().map(|desc| desc.target.clone())
+        });
+
+        for component in m.values_of("component").expect("") {
+            let new_component = Component {
+                pkg: component.to_string(),
+                target: target.clone(),
+            };
 
-    for component in m.values_of("component").expect("") {
-        let new_component = Component {
-            pkg: component.to_string(),
-            target: target.clone(),
-        };
+            try!(toolchain.remove_component(new_component));
+        }
+
+        Ok(())
+    })
+}
+
+// Applies `f` to every toolchain named by `--toolchain`, which may be a
+// comma-separated list of names, `all`, or globs such as `nightly-*`.
+// A single named toolchain reports errors directly; several get a summary.
+fn for_each_toolchain(cfg: &Cfg, m: &ArgMatches, f: &Fn(&Toolchain) -> Result<()>) -> Result<()> {
+    let spec = match m.value_of("toolchain") {
+        Some(spec) => spec,
+        None => return f(&try!(explicit_or_dir_toolchain(cfg, m))),
+    };
 
-        try!(toolchain.remove_component(new_component));
+    // Custom toolchains don't support components, so they are only
+    // included when named explicitly
+    let names = try!(toolchain_names_from_spec(cfg, spec, false));
+    if names.len() == 1 && !utils::is_glob(spec) && spec != "all" {
+        return f(&try!(cfg.get_toolchain(&names[0], false)));
     }
 
-    Ok(())
+    let mut results = Vec::new();
+    for name in names {
+        let toolchain = try!(cfg.get_toolchain(&name, false));
+        info!("applying changes to '{}'", name);
+        let result = f(&toolchain);
+        if let Err(ref e) = result {
+            err!("{}", e);
+        }
+        results.push((name, result));
+    }
+
+    common::show_toolchain_changes(results)
+}
+
+/// The toolchains named by a comma-separated list of names, globs and
+/// `all`. Globs and `all` only match custom toolchains if
+/// `include_custom`; otherwise those are only included when named.
+fn toolchain_names_from_spec(cfg: &Cfg, spec: &str, include_custom: bool) -> Result<Vec<String>> {
+    let mut names: Vec<String> = Vec::new();
+    for part in spec.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()) {
+        if part == "all" || utils::is_glob(part) {
+            let mut matched = false;
+            for name in try!(cfg.list_toolchains()) {
+                if part != "all" && !utils::glob_match(part, &name) {
+                    continue;
+                }
+                if !include_custom && try!(cfg.get_toolchain(&name, false)).is_custom() {
+                    continue;
+                }
+                matched = true;
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+            if !matched {
+                return Err(ErrorKind::NoMatchingToolchains(part.to_string()).into());
+            }
+        } else {
+            let name = try!(cfg.resolve_toolchain(part));
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+
+    if names.is_empty() {
+        return Err(ErrorKind::NoMatchingToolchains(spec.to_string()).into());
+    }
+
+    Ok(names)
 }
 
 fn explicit_or_dir_toolchain<'a>(cfg: &'a Cfg, m: &ArgMatches) -> Result<Toolchain<'a>> {
@@ -730,8 +1180,21 @@ fn toolchain_remove(cfg: &Cfg, m: &ArgMatches) -> Result<()> {
 }
 
 fn override_add(cfg: &Cfg, m: &ArgMatches) -> Result<()> {
-    let ref toolchain = m.value_of("toolchain").expect("");
-    let toolchain = try!(cfg.get_toolchain(toolchain, false));
+    let ref cwd = try!(utils::current_dir());
+
+    // With --from-manifest the override is for the package, wherever
+    // in it rustup was run
+    let (toolchain, dir) = if m.is_present("from-manifest") {
+        let (name, manifest) = try!(cfg.find_manifest_toolchain(cwd));
+        let dir = manifest.parent().expect("").to_owned();
+        info!("using toolchain '{}' for rust-version declared in '{}'",
+              name, manifest.display());
+        (name, dir)
+    } else {
+        (m.value_of("toolchain").expect("").to_owned(), cwd.clone())
+    };
+    let ref dir = dir;
+    let toolchain = try!(cfg.get_toolchain(&toolchain, false));
 
     let status = if !toolchain.is_custom() {
         Some(try!(toolchain.install_from_dist_if_not_installed()))
@@ -741,7 +1204,31 @@ fn override_add(cfg: &Cfg, m: &ArgMatches) -> Result<()> {
         None
     };
 
-    try!(toolchain.make_override(&try!(utils::current_dir())));
+    try!(toolchain.make_override(dir));
+
+    let requirements = OverrideRequirements {
+        targets: m.values_of("target").map(|v| v.map(|s| s.to_owned()).collect())
+            .unwrap_or(Vec::new()),
+        components: m.values_of("component").map(|v| v.map(|s| s.to_owned()).collect())
+            .unwrap_or(Vec::new()),
+    };
+    if !requirements.is_empty() {
+        try!(cfg.settings_file.with_mut(|s| {
+            s.set_override_requirements(dir, requirements, cfg.notify_handler.as_ref());
+            Ok(())
+        }));
+        try!(cfg.check_override_requirements(&toolchain, dir, true));
+    }
+
+    let vars = try!(env_assignments(m.values_of("env")));
+    if !vars.is_empty() {
+        try!(cfg.settings_file.with_mut(|s| {
+            for &(var, value) in &vars {
+                s.set_override_env(dir, var, Some(value), cfg.notify_handler.as_ref());
+            }
+            Ok(())
+        }));
+    }
 
     if let Some(status) = status {
         println!("");
@@ -751,6 +1238,199 @@ fn override_add(cfg: &Cfg, m: &ArgMatches) -> Result<()> {
     Ok(())
 }
 
+/// Splits `VAR=VALUE` arguments
+fn env_assignments<'a, I: Iterator<Item = &'a str>>(args: Option<I>) -> Result<Vec<(&'a str, &'a str)>> {
+    let mut result = Vec::new();
+    for arg in args.into_iter().flat_map(|a| a) {
+        match arg.find('=') {
+            Some(i) if i > 0 => result.push((&arg[..i], &arg[i + 1..])),
+            _ => return Err(ErrorKind::InvalidEnvAssignment(arg.to_owned()).into()),
+        }
+    }
+    Ok(result)
+}
+
+fn toolchain_env(cfg: &Cfg, m: &ArgMatches) -> Result<()> {
+    let toolchain = try!(cfg.get_toolchain(m.value_of("toolchain").expect(""), false));
+    let name = toolchain.name();
+    let vars = try!(env_assignments(m.values_of("assignment")));
+    let unset: Vec<&str> = m.values_of("unset").map(|v| v.collect()).unwrap_or(Vec::new());
+
+    if vars.is_empty() && unset.is_empty() {
+        let vars = try!(cfg.settings_file.with(|s| Ok(s.toolchain_env(name))));
+        for (var, value) in vars {
+            println!("{}={}", var, value);
+        }
+        return Ok(());
+    }
+
+    try!(cfg.settings_file.with_mut(|s| {
+        for &(var, value) in &vars {
+            s.set_toolchain_env(name, var, Some(value));
+        }
+        for var in &unset {
+            s.set_toolchain_env(name, var, None);
+        }
+        Ok(())
+    }));
+    Ok(())
+}
+
+fn toolchain_fallback(cfg: &Cfg, m: &ArgMatches) -> Result<()> {
+    let toolchain = try!(cfg.get_toolchain(m.value_of("toolchain").expect(""), false));
+
+    if m.is_present("unset") {
+        return Ok(try!(cfg.set_fallbacks(toolchain.name(), None)));
+    }
+    if m.is_present("none") {
+        return Ok(try!(cfg.set_fallbacks(toolchain.name(), Some(Vec::new()))));
+    }
+
+    if let Some(fallbacks) = m.values_of("fallback") {
+        let mut names = Vec::new();
+        for fallback in fallbacks {
+            names.push(try!(cfg.get_toolchain(fallback, false)).name().to_owned());
+        }
+        try!(cfg.set_fallbacks(toolchain.name(), Some(names)));
+    } else {
+        for fallback in try!(cfg.fallbacks(&toolchain)) {
+            println!("{}", fallback);
+        }
+    }
+    Ok(())
+}
+
+fn toolchain_pin(cfg: &Cfg, m: &ArgMatches, pinned: bool) -> Result<()> {
+    let toolchain = try!(cfg.get_toolchain(m.value_of("toolchain").expect(""), false));
+    if pinned && !toolchain.is_tracking() {
+        warn!("toolchain '{}' doesn't track a channel, so `rustup update` doesn't update it anyway",
+              toolchain.name());
+    }
+    Ok(try!(cfg.set_pinned(toolchain.name(), pinned)))
+}
+
+fn toolchain_info(cfg: &Cfg, m: &ArgMatches) -> Result<()> {
+    let toolchain = if let Some(name) = m.value_of("toolchain") {
+        try!(cfg.get_toolchain(name, false))
+    } else {
+        try!(cfg.toolchain_for_dir(&try!(utils::current_dir()))).0
+    };
+    let info = try!(toolchain.info());
+
+    let kind = match info.link_target {
+        Some(ref target) => format!("linked -> {}", target.display()),
+        None if info.custom => "custom".to_owned(),
+        None => "dist".to_owned(),
+    };
+    let install_time = info.install_time.map(|secs| {
+        let tm = time::at_utc(time::Timespec::new(secs as i64, 0));
+        time::strftime("%Y-%m-%d %H:%M:%S UTC", &tm).unwrap_or_else(|_| secs.to_string())
+    });
+
+    if m.is_present("json") {
+        use rustc_serialize::json::Json;
+        use std::collections::BTreeMap;
+
+        fn opt_string(s: Option<String>) -> Json {
+            s.map(Json::String).unwrap_or(Json::Null)
+        }
+        fn strings(v: Vec<String>) -> Json {
+            Json::Array(v.into_iter().map(Json::String).collect())
+        }
+
+        let mut packages = BTreeMap::new();
+        for (pkg, version) in info.packages {
+            packages.insert(pkg, Json::String(version));
+        }
+        let mut result = BTreeMap::new();
+        result.insert("toolchain".to_owned(), Json::String(info.name));
+        result.insert("host".to_owned(), opt_string(info.host));
+        result.insert("custom".to_owned(), Json::Boolean(info.custom));
+        result.insert("link_target".to_owned(),
+                      opt_string(info.link_target.map(|p| p.to_string_lossy().into_owned())));
+        result.insert("sysroot".to_owned(),
+                      Json::String(info.sysroot.to_string_lossy().into_owned()));
+        result.insert("manifest_date".to_owned(), opt_string(info.manifest_date));
+        result.insert("profile".to_owned(), opt_string(info.profile));
+        result.insert("packages".to_owned(), Json::Object(packages));
+        result.insert("components".to_owned(), strings(info.components));
+        result.insert("targets".to_owned(), strings(info.targets));
+        result.insert("install_time".to_owned(),
+                      info.install_time.map(Json::U64).unwrap_or(Json::Null));
+        result.insert("size".to_owned(), Json::U64(info.size));
+        println!("{}", Json::Object(result).pretty());
+        return Ok(());
+    }
+
+    let unknown = || "(unknown)".to_owned();
+    println!("toolchain: {}", info.name);
+    println!("host: {}", info.host.unwrap_or_else(unknown));
+    println!("kind: {}", kind);
+    println!("sysroot: {}", info.sysroot.display());
+    println!("manifest date: {}", info.manifest_date.unwrap_or_else(unknown));
+    println!("profile: {}", info.profile.unwrap_or_else(unknown));
+    println!("installed: {}", install_time.unwrap_or_else(unknown));
+    println!("size: {}", HumanReadable(info.size as f64).to_string().trim());
+
+    if !info.packages.is_empty() {
+        println!("");
+        println!("packages:");
+        for (pkg, version) in info.packages {
+            println!("  {} {}", pkg, version);
+        }
+    }
+    if !info.components.is_empty() {
+        println!("");
+        println!("components:");
+        for component in info.components {
+            println!("  {}", component);
+        }
+    }
+    if !info.targets.is_empty() {
+        println!("");
+        println!("targets:");
+        for target in info.targets {
+            println!("  {}", target);
+        }
+    }
+    Ok(())
+}
+
+fn override_tool(cfg: &Cfg, m: &ArgMatches) -> Result<()> {
+    let tool = m.value_of("tool").expect("");
+    if m.is_present("unset") {
+        return Ok(try!(cfg.set_tool_override(tool, None)));
+    }
+
+    let toolchain = try!(cfg.get_toolchain(m.value_of("toolchain").expect(""), false));
+    let status = if !toolchain.is_custom() {
+        Some(try!(toolchain.install_from_dist_if_not_installed()))
+    } else if !toolchain.exists() {
+        return Err(ErrorKind::ToolchainNotInstalled(toolchain.name().to_string()).into());
+    } else {
+        None
+    };
+
+    try!(cfg.set_tool_override(tool, Some(toolchain.name())));
+
+    if let Some(status) = status {
+        println!("");
+        try!(common::show_channel_update(cfg, toolchain.name(), Ok(status)));
+    }
+
+    Ok(())
+}
+
+fn override_lock(cfg: &Cfg, m: &ArgMatches) -> Result<()> {
+    let path = if let Some(path) = m.value_of("path") {
+        PathBuf::from(path)
+    } else {
+        try!(utils::current_dir())
+    };
+    try!(cfg.lock_override(&path, m.is_present("strict")));
+    Ok(())
+}
+
 fn override_remove(cfg: &Cfg, m: &ArgMatches) -> Result<()> {
     let paths = if m.is_present("nonexistent") {
         let list: Vec<_> = try!(cfg.settings_file.with(|s| Ok(s.overrides.iter().filter_map(|(k, _)|
@@ -776,6 +1456,7 @@ fn override_remove(cfg: &Cfg, m: &ArgMatches) -> Result<()> {
         if try!(cfg.settings_file.with_mut(|s| {
             Ok(s.remove_override(&Path::new(&path), cfg.notify_handler.as_ref()))
         })) {
+            cfg.audit_log.record("override unset", None, Some(&path), None);
             info!("override toolchain for '{}' removed", path);
         } else {
             info!("no override toolchain for '{}'", path);
@@ -816,6 +1497,68 @@ fn man(cfg: &Cfg, m: &ArgMatches) -> Result<()> {
     Ok(())
 }
 
+fn clean(cfg: &Cfg, m: &ArgMatches) -> Result<()> {
+    let dry_run = m.is_present("dry-run");
+    let removed = try!(cfg.clean(dry_run));
+
+    let mut total = 0;
+    for (path, size) in removed {
+        println!("{} ({})", path.display(), HumanReadable(size as f64).to_string().trim());
+        total += size;
+    }
+    let total = HumanReadable(total as f64).to_string();
+    if dry_run {
+        info!("{} would be freed", total.trim());
+    } else {
+        info!("{} freed", total.trim());
+    }
+    Ok(())
+}
+
+fn show_log(cfg: &Cfg, m: &ArgMatches) -> Result<()> {
+    let mut entries = try!(cfg.audit_log.entries());
+
+    if let Some(name) = m.value_of("toolchain") {
+        let name = try!(cfg.resolve_toolchain(name));
+        entries.retain(|e| e.toolchain.as_ref() == Some(&name));
+    }
+    if let Some(limit) = m.value_of("limit") {
+        let limit = try!(limit.parse::<usize>()
+                         .map_err(|_| format!("invalid limit: '{}'", limit)));
+        let skip = entries.len().saturating_sub(limit);
+        entries.drain(..skip);
+    }
+
+    if m.is_present("json") {
+        use rustc_serialize::json;
+        for entry in entries {
+            println!("{}", try!(json::encode(&entry).chain_err(|| "unable to encode log entry")));
+        }
+        return Ok(());
+    }
+
+    for entry in entries {
+        let tm = time::at_utc(time::Timespec::new(entry.time, 0));
+        let when = time::strftime("%Y-%m-%d %H:%M:%S UTC", &tm)
+            .unwrap_or_else(|_| entry.time.to_string());
+        let mut change = entry.action.clone();
+        if let Some(ref toolchain) = entry.toolchain {
+            change = format!("{} {}", change, toolchain);
+        }
+        if let Some(ref detail) = entry.detail {
+            change = format!("{} {}", change, detail);
+        }
+        if let Some(ref date) = entry.manifest_date {
+            change = format!("{} ({})", change, date);
+        }
+        println!("{} {}: {}", when, change, entry.outcome);
+        println!("    by {} running `{}`",
+                 entry.user.as_ref().map(|u| &**u).unwrap_or("(unknown user)"),
+                 entry.command.join(" "));
+    }
+    Ok(())
+}
+
 fn self_uninstall(m: &ArgMatches) -> Result<()> {
     let no_prompt = m.is_present("no-prompt");
 
@@ -838,3 +1581,72 @@ fn set_default_host_triple(cfg: &Cfg, m: &ArgMatches) -> Result<()> {
     try!(cfg.set_default_host_triple(m.value_of("host_triple").expect("")));
     Ok(())
 }
+
+fn set_profile(cfg: &Cfg, m: &ArgMatches) -> Result<()> {
+    try!(cfg.set_profile(m.value_of("profile-name").expect("")));
+    Ok(())
+}
+
+fn set_auto_install(cfg: &Cfg, m: &ArgMatches) -> Result<()> {
+    try!(cfg.set_auto_install(m.value_of("enabled") == Some("true")));
+    Ok(())
+}
+
+fn set_config(cfg: &Cfg, key: &str, m: &ArgMatches) -> Result<()> {
+    try!(cfg.set_config(key, m.value_of("value").expect("")));
+    Ok(())
+}
+
+fn set_manifest_overrides(cfg: &Cfg, m: &ArgMatches) -> Result<()> {
+    try!(cfg.set_manifest_overrides(m.value_of("enabled") == Some("true")));
+    Ok(())
+}
+
+fn export(cfg: &Cfg) -> Result<()> {
+    let state = try!(cfg.export_state());
+    print!("{}", state.stringify());
+    Ok(())
+}
+
+fn import(cfg: &Cfg, m: &ArgMatches) -> Result<()> {
+    let ref path = Path::new(m.value_of("file").expect(""));
+    let content = try!(utils::read_file("rustup state", path));
+    let state = try!(State::parse(&content));
+    let toolchains = state.toolchains.len();
+    try!(cfg.import_state(state));
+    info!("imported {} toolchain{} and settings from '{}'",
+          toolchains, if toolchains == 1 { "" } else { "s" }, path.display());
+    Ok(())
+}
+
+fn config_list(cfg: &Cfg) -> Result<()> {
+    for &(key, vars, _) in CONFIG_KEYS {
+        let from_env = vars.iter()
+            .filter_map(|v| env::var(v).ok().and_then(utils::if_not_empty).map(|s| (v, s)))
+            .next();
+        let stored = try!(cfg.settings_file.with(|s| s.get_config(key)));
+        let (value, source) = if let Some((var, value)) = from_env {
+            // The backend variables are flags that name the backend
+            let value = match *var {
+                "RUSTUP_USE_HYPER" => "hyper".to_owned(),
+                "RUSTUP_USE_RUSTLS" => "rustls".to_owned(),
+                _ => value,
+            };
+            (value, format!("environment: {}", var))
+        } else if let Some(value) = stored {
+            (value, "settings".to_owned())
+        } else {
+            let value = match key {
+                "dist-server" => cfg.dist_root_server.clone(),
+                "update-root" => self_update::UPDATE_ROOT.to_owned(),
+                "gpg-key" => "(built-in)".to_owned(),
+                "download-backend" => "curl".to_owned(),
+                "staged-manifest" => "false".to_owned(),
+                _ => "(none)".to_owned(),
+            };
+            (value, "default".to_owned())
+        };
+        println!("{} = {} ({})", key, value, source);
+    }
+    Ok(())
+}
@@ -32,6 +32,8 @@
 
 use common::{self, Confirm};
 use errors::*;
+use rustup::{self, Cfg};
+use rustup::settings::SettingsFile;
 use rustup_dist::dist;
 use rustup_utils::utils;
 use std::env;
@@ -46,6 +48,7 @@ use regex::Regex;
 pub struct InstallOpts {
     pub default_host_triple: String,
     pub default_toolchain: String,
+    pub profile: String,
     pub no_modify_path: bool,
 }
 
@@ -193,7 +196,7 @@ tools, but otherwise, install the C++ build tools before proceeding.
 static TOOLS: &'static [&'static str]
     = &["rustc", "rustdoc", "cargo", "rust-lldb", "rust-gdb", "rls"];
 
-static UPDATE_ROOT: &'static str
+pub static UPDATE_ROOT: &'static str
     = "https://static.rust-lang.org/rustup";
 
 /// `CARGO_HOME` suitable for display, possibly with $HOME
@@ -260,7 +263,8 @@ pub fn install(no_prompt: bool, verbose: bool,
         // FIXME: Someday we can stop setting up the symlink, and when
         // we do that we can stop creating ~/.rustup as well.
         try!(utils::create_rustup_home());
-        try!(maybe_install_rust(&opts.default_toolchain, &opts.default_host_triple, verbose));
+        try!(maybe_install_rust(&opts.default_toolchain, &opts.profile,
+                                &opts.default_host_triple, verbose));
 
         if cfg!(unix) {
             let ref env_file = try!(utils::cargo_home()).join("env");
@@ -520,10 +524,12 @@ fn current_install_opts(opts: &InstallOpts) -> String {
 
 - ` `default host triple: `{}`
 - `   `default toolchain: `{}`
+- `             `profile: `{}`
 - modify PATH variable: `{}`
 ",
         opts.default_host_triple,
         opts.default_toolchain,
+        opts.profile,
         if !opts.no_modify_path { "yes" } else { "no" }
     )
 }
@@ -589,6 +595,22 @@ fn install_bins() -> Result<()> {
     let ref this_exe_path = try!(utils::current_exe());
     let ref rustup_path = bin_path.join(&format!("rustup{}", EXE_SUFFIX));
 
+    // The proxies created for components' executables link to the old
+    // binary, so need linking to the new one as well. Those replaced
+    // by something else since aren't rustup's any more
+    let ref settings_file = SettingsFile::new(try!(utils::multirust_home()).join("settings.toml"));
+    let proxies = match settings_file.peek(|s| s.proxies.clone()) {
+        Ok(proxies) => proxies,
+        // Metadata newer than this rustup describes proxies made by
+        // the newer rustup, which can look after them itself
+        Err(rustup::Error(rustup::ErrorKind::MetadataTooNew(_), _)) => Vec::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let proxies: Vec<_> = proxies.iter()
+        .map(|name| bin_path.join(&format!("{}{}", name, EXE_SUFFIX)))
+        .filter(|path| utils::is_same_file(path, rustup_path))
+        .collect();
+
     try!(utils::ensure_dir_exists("bin", bin_path, &|_| {}));
     // NB: Even on Linux we can't just copy the new binary over the (running)
     // old binary; we must unlink it first.
@@ -604,11 +626,15 @@ fn install_bins() -> Result<()> {
         let ref tool_path = bin_path.join(&format!("{}{}", tool, EXE_SUFFIX));
         try!(utils::hard_or_symlink_file(rustup_path, tool_path));
     }
+    for proxy_path in &proxies {
+        try!(utils::hard_or_symlink_file(rustup_path, proxy_path));
+    }
 
     Ok(())
 }
 
-fn maybe_install_rust(toolchain_str: &str, default_host_triple: &str, verbose: bool) -> Result<()> {
+fn maybe_install_rust(toolchain_str: &str, profile_str: &str, default_host_triple: &str,
+                      verbose: bool) -> Result<()> {
     let ref cfg = try!(common::set_globals(verbose));
 
     // If there is already an install, then `toolchain_str` may not be
@@ -618,6 +644,7 @@ fn maybe_install_rust(toolchain_str: &str, default_host_triple: &str, verbose: b
     if try!(cfg.find_default()).is_none() {
         // Set host triple first as it will affect resolution of toolchain_str
         try!(cfg.set_default_host_triple(default_host_triple));
+        try!(cfg.set_profile(profile_str));
         let toolchain = try!(cfg.get_toolchain(toolchain_str, false));
         let status = try!(toolchain.install_from_dist());
         try!(cfg.set_default(toolchain_str));
@@ -667,12 +694,18 @@ pub fn uninstall(no_prompt: bool) -> Result<()> {
         }
     }
 
+    let ref rustup_dir = try!(utils::multirust_home());
+
+    // The proxies created for components' executables are links to
+    // rustup as well, so are removed along with it
+    let settings_file = SettingsFile::new(rustup_dir.join("settings.toml"));
+    let proxies = try!(settings_file.peek(|s| s.proxies.clone()));
+
     info!("removing rustup home");
 
     try!(utils::delete_legacy_multirust_symlink());
 
     // Delete RUSTUP_HOME
-    let ref rustup_dir = try!(utils::multirust_home());
     if rustup_dir.exists() {
         try!(utils::remove_dir("rustup_home", rustup_dir, &|_| {}));
     }
@@ -702,7 +735,8 @@ pub fn uninstall(no_prompt: bool) -> Result<()> {
     // Then everything in bin except rustup and tools. These can't be unlinked
     // until this process exits (on windows).
     let tools = TOOLS.iter().map(|t| format!("{}{}", t, EXE_SUFFIX));
-    let tools: Vec<_> = tools.chain(vec![format!("rustup{}", EXE_SUFFIX)]).collect();
+    let proxies = proxies.iter().map(|t| format!("{}{}", t, EXE_SUFFIX));
+    let tools: Vec<_> = tools.chain(proxies).chain(vec![format!("rustup{}", EXE_SUFFIX)]).collect();
     for dirent in try!(fs::read_dir(&cargo_home.join("bin")).chain_err(|| read_dir_err)) {
         let dirent = try!(dirent.chain_err(|| read_dir_err));
         let name = dirent.file_name();
@@ -1246,13 +1280,13 @@ fn do_remove_from_path(methods: &[PathUpdateMethod]) -> Result<()> {
 /// (and on windows this process will not be running to do it),
 /// rustup-init is stored in `CARGO_HOME`/bin, and then deleted next
 /// time rustup runs.
-pub fn update() -> Result<()> {
+pub fn update(cfg: &Cfg) -> Result<()> {
     if NEVER_SELF_UPDATE {
         err!("self-update is disabled for this build of rustup");
         err!("you should probably use your system package manager to update rustup");
         process::exit(1);
     }
-    let setup_path = try!(prepare_update());
+    let setup_path = try!(prepare_update(cfg));
     if let Some(ref p) = setup_path {
         let version = match get_new_rustup_version(p) {
             Some(new_version) => parse_new_rustup_version(new_version),
@@ -1263,6 +1297,7 @@ pub fn update() -> Result<()> {
         };
 
         info!("rustup updated successfully to {}", version);
+        cfg.audit_log.record("self update", None, Some(&version), None);
         try!(run_update(p));
     }
 
@@ -1289,7 +1324,7 @@ fn parse_new_rustup_version(version: String) -> String {
     String::from(matched_version)
 }
 
-pub fn prepare_update() -> Result<Option<PathBuf>> {
+pub fn prepare_update(cfg: &Cfg) -> Result<Option<PathBuf>> {
     use toml;
 
     let ref cargo_home = try!(utils::cargo_home());
@@ -1307,8 +1342,10 @@ pub fn prepare_update() -> Result<Option<PathBuf>> {
     // Get build triple
     let triple = dist::TargetTriple::from_build();
 
-    let update_root = env::var("RUSTUP_UPDATE_ROOT")
+    let update_root = env::var("RUSTUP_UPDATE_ROOT").ok()
+        .or(cfg.update_root.clone())
         .unwrap_or(String::from(UPDATE_ROOT));
+    let backend = cfg.download_backend.as_ref().map(|s| &**s);
 
     let tempdir = try!(TempDir::new("rustup-update")
         .chain_err(|| "error creating temp directory"));
@@ -1321,7 +1358,8 @@ pub fn prepare_update() -> Result<Option<PathBuf>> {
     let release_file_url = format!("{}/release-stable.toml", update_root);
     let release_file_url = try!(utils::parse_url(&release_file_url));
     let release_file = tempdir.path().join("release-stable.toml");
-    try!(utils::download_file(&release_file_url, &release_file, None, &|_| ()));
+    try!(utils::download_file_with_backend(&release_file_url, &release_file, None, false,
+                                           backend, &|_| ()));
     let release_toml_str = try!(utils::read_file("rustup release", &release_file));
     let release_toml = try!(toml::Parser::new(&release_toml_str).parse()
                             .ok_or(Error::from("unable to parse rustup release file")));
@@ -1352,10 +1390,12 @@ pub fn prepare_update() -> Result<Option<PathBuf>> {
 
     // Download new version
     info!("downloading self-update");
-    try!(utils::download_file(&download_url,
-                              &setup_path,
-                              None,
-                              &|_| ()));
+    try!(utils::download_file_with_backend(&download_url,
+                                           &setup_path,
+                                           None,
+                                           false,
+                                           backend,
+                                           &|_| ()));
 
     // Mark as executable
     try!(utils::make_executable(setup_path));
@@ -2,7 +2,7 @@ use std::env;
 use self_update::{self, InstallOpts};
 use errors::*;
 use clap::{App, Arg, AppSettings};
-use rustup_dist::dist::TargetTriple;
+use rustup_dist::dist::Profile;
 use common;
 
 pub fn main() -> Result<()> {
@@ -33,6 +33,10 @@ pub fn main() -> Result<()> {
              .long("default-toolchain")
              .takes_value(true)
              .help("Choose a default toolchain to install"))
+        .arg(Arg::with_name("profile")
+             .long("profile")
+             .possible_values(Profile::names())
+             .default_value("default"))
         .arg(Arg::with_name("no-modify-path")
              .long("no-modify-path")
              .help("Don't configure the PATH environment variable"));
@@ -40,15 +44,16 @@ pub fn main() -> Result<()> {
     let matches = cli.get_matches();
     let no_prompt = matches.is_present("no-prompt");
     let verbose = matches.is_present("verbose");
-    let default_host = matches.value_of("default-host").map(|s| s.to_owned()).unwrap_or_else(|| {
-        TargetTriple::from_host_or_build().to_string()
-    });
+    let default_host = matches.value_of("default-host").map(|s| s.to_owned())
+        .unwrap_or_else(common::default_host_triple);
     let default_toolchain = matches.value_of("default-toolchain").unwrap_or("stable");
+    let profile = matches.value_of("profile").expect("");
     let no_modify_path = matches.is_present("no-modify-path");
 
     let opts = InstallOpts {
         default_host_triple: default_host,
         default_toolchain: default_toolchain.to_owned(),
+        profile: profile.to_owned(),
         no_modify_path: no_modify_path,
     };
 
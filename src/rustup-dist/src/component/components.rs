@@ -48,9 +48,9 @@ impl Components {
     }
     fn write_version(&self, tx: &mut Transaction) -> Result<()> {
         try!(tx.modify_file(self.prefix.rel_manifest_file(VERSION_FILE)));
-        try!(utils::write_file(VERSION_FILE,
-                               &self.prefix.manifest_file(VERSION_FILE),
-                               INSTALLER_VERSION));
+        try!(utils::write_file_atomic(VERSION_FILE,
+                                      &self.prefix.manifest_file(VERSION_FILE),
+                                      INSTALLER_VERSION));
 
         Ok(())
     }
@@ -3,6 +3,7 @@ use toml;
 use rustup_utils::toml_utils::*;
 use errors::*;
 use super::manifest::Component;
+use super::dist::Profile;
 
 pub const SUPPORTED_CONFIG_VERSIONS: [&'static str; 1] = ["1"];
 pub const DEFAULT_CONFIG_VERSION: &'static str = "1";
@@ -11,6 +12,7 @@ pub const DEFAULT_CONFIG_VERSION: &'static str = "1";
 pub struct Config {
     pub config_version: String,
     pub components: Vec<Component>,
+    pub profile: Profile,
 }
 
 impl Config {
@@ -24,9 +26,15 @@ impl Config {
         let components = try!(Self::toml_to_components(components,
                                                        &format!("{}{}.", path, "components")));
 
+        let profile = match try!(get_opt_string(&mut table, "profile", path)) {
+            Some(p) => try!(Profile::from_str(&p)),
+            None => Profile::Default,
+        };
+
         Ok(Config {
             config_version: version,
             components: components,
+            profile: profile,
         })
     }
     pub fn to_toml(self) -> toml::Table {
@@ -37,6 +45,8 @@ impl Config {
         if !components.is_empty() {
             result.insert("components".to_owned(), toml::Value::Array(components));
         }
+        result.insert("profile".to_owned(),
+                      toml::Value::String(self.profile.name().to_owned()));
         result
     }
 
@@ -76,6 +86,7 @@ impl Config {
         Config {
             config_version: DEFAULT_CONFIG_VERSION.to_owned(),
             components: Vec::new(),
+            profile: Profile::Default,
         }
     }
 }
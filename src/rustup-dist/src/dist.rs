@@ -54,6 +54,21 @@ pub struct ToolchainDesc {
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct TargetTriple(String);
 
+// Install profiles select which components of the `rust` package are
+// installed when a toolchain is first installed. The profile is
+// recorded in the dist config so that later updates keep the same
+// set of components.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Profile {
+    Minimal,
+    Default,
+    Complete,
+}
+
+// Components the manifest marks as required which the minimal profile
+// leaves out.
+static MINIMAL_PROFILE_EXCLUDES: &'static [&'static str] = &["rust-docs"];
+
 // These lists contain the targets known to rustup, and used to build
 // the PartialTargetTriple.
 
@@ -113,6 +128,10 @@ impl TargetTriple {
     }
 
     pub fn from_host() -> Option<Self> {
+        Self::from_host_with_override(None)
+    }
+
+    fn detect_host() -> Option<Self> {
         #[cfg(windows)]
         fn inner() -> Option<TargetTriple> {
             use kernel32::GetNativeSystemInfo;
@@ -186,16 +205,76 @@ impl TargetTriple {
             host_triple.map(TargetTriple::from_str)
         }
 
+        inner()
+    }
+
+    /// Like `from_host`, but `override_triple` is used instead of
+    /// detecting the host. `RUSTUP_OVERRIDE_HOST_TRIPLE` still wins.
+    pub fn from_host_with_override(override_triple: Option<&str>) -> Option<Self> {
         if let Ok(triple) = env::var("RUSTUP_OVERRIDE_HOST_TRIPLE") {
             Some(TargetTriple(triple))
+        } else if let Some(triple) = override_triple {
+            Some(TargetTriple::from_str(triple))
         } else {
-            inner()
+            Self::detect_host()
         }
     }
 
     pub fn from_host_or_build() -> Self {
         Self::from_host().unwrap_or_else(Self::from_build)
     }
+
+    pub fn from_host_or_build_with_override(override_triple: Option<&str>) -> Self {
+        Self::from_host_with_override(override_triple).unwrap_or_else(Self::from_build)
+    }
+}
+
+impl Profile {
+    pub fn from_str(name: &str) -> Result<Self> {
+        match name {
+            "minimal" => Ok(Profile::Minimal),
+            "default" => Ok(Profile::Default),
+            "complete" => Ok(Profile::Complete),
+            _ => Err(ErrorKind::InvalidProfile(name.to_string()).into()),
+        }
+    }
+
+    pub fn names() -> &'static [&'static str] {
+        &["minimal", "default", "complete"]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Profile::Minimal => "minimal",
+            Profile::Default => "default",
+            Profile::Complete => "complete",
+        }
+    }
+
+    /// Whether a component the manifest lists as required by the
+    /// `rust` package is installed under this profile
+    pub fn includes_component(&self, component: &Component) -> bool {
+        match *self {
+            Profile::Minimal => !MINIMAL_PROFILE_EXCLUDES.contains(&&*component.pkg),
+            Profile::Default | Profile::Complete => true,
+        }
+    }
+
+    /// Whether an optional extension of the `rust` package is
+    /// installed under this profile. Only the complete profile
+    /// installs extensions, and then only those for the host.
+    pub fn includes_extension(&self, extension: &Component, host: &TargetTriple) -> bool {
+        match *self {
+            Profile::Complete => extension.target.as_ref().map_or(true, |t| t == host),
+            Profile::Minimal | Profile::Default => false,
+        }
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Profile::Default
+    }
 }
 
 impl PartialTargetTriple {
@@ -334,9 +413,10 @@ impl ToolchainDesc {
             .ok_or(ErrorKind::InvalidToolchainName(name.to_string()).into())
     }
 
-    pub fn manifest_v1_url(&self, dist_root: &str) -> String {
-        let do_manifest_staging = env::var("RUSTUP_STAGED_MANIFEST").is_ok();
-        match (self.date.as_ref(), do_manifest_staging) {
+    /// The staging manifests are used when `staged` is set, which
+    /// takes `RUSTUP_STAGED_MANIFEST` into account
+    pub fn manifest_v1_url(&self, dist_root: &str, staged: bool) -> String {
+        match (self.date.as_ref(), staged) {
             (None, false) => format!("{}/channel-rust-{}", dist_root, self.channel),
             (Some(date), false) => format!("{}/{}/channel-rust-{}", dist_root, date, self.channel),
             (None, true) => format!("{}/staging/channel-rust-{}", dist_root, self.channel),
@@ -344,8 +424,8 @@ impl ToolchainDesc {
         }
     }
 
-    pub fn manifest_v2_url(&self, dist_root: &str) -> String {
-        format!("{}.toml", self.manifest_v1_url(dist_root))
+    pub fn manifest_v2_url(&self, dist_root: &str, staged: bool) -> String {
+        format!("{}.toml", self.manifest_v1_url(dist_root, staged))
     }
     /// Either "$channel" or "channel-$date"
     pub fn manifest_name(&self) -> String {
@@ -402,6 +482,12 @@ impl fmt::Display for TargetTriple {
     }
 }
 
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.name().fmt(f)
+    }
+}
+
 impl fmt::Display for PartialToolchainDesc {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         try!(write!(f, "{}", &self.channel));
@@ -438,13 +524,15 @@ impl fmt::Display for ToolchainDesc {
 
 
 // Installs or updates a toolchain from a dist server. If an initial
-// install then it will be installed with the default components. If
-// an upgrade then all the existing components will be upgraded.
+// install then it will be installed with the components selected by
+// `profile`. If an upgrade then all the existing components will be
+// upgraded.
 //
 // Returns the manifest's hash if anything changed.
 pub fn update_from_dist<'a>(download: DownloadCfg<'a>,
                             update_hash: Option<&Path>,
                             toolchain: &ToolchainDesc,
+                            profile: Option<Profile>,
                             prefix: &InstallPrefix,
                             add: &[Component],
                             remove: &[Component])
@@ -455,6 +543,7 @@ pub fn update_from_dist<'a>(download: DownloadCfg<'a>,
     let res = update_from_dist_(download,
                                 update_hash,
                                 toolchain,
+                                profile,
                                 prefix,
                                 add,
                                 remove);
@@ -472,6 +561,7 @@ pub fn update_from_dist<'a>(download: DownloadCfg<'a>,
 pub fn update_from_dist_<'a>(download: DownloadCfg<'a>,
                             update_hash: Option<&Path>,
                             toolchain: &ToolchainDesc,
+                            profile: Option<Profile>,
                             prefix: &InstallPrefix,
                             add: &[Component],
                             remove: &[Component])
@@ -483,6 +573,7 @@ pub fn update_from_dist_<'a>(download: DownloadCfg<'a>,
     let changes = Changes {
         add_extensions: add.to_owned(),
         remove_extensions: remove.to_owned(),
+        profile: profile,
     };
 
     // TODO: Add a notification about which manifest version is going to be used
@@ -540,11 +631,26 @@ pub fn update_from_dist_<'a>(download: DownloadCfg<'a>,
     }
 }
 
+/// Downloads the v2 manifest of `toolchain` without installing
+/// anything, or `None` if the dist server doesn't have one
+pub fn dl_manifest<'a>(download: DownloadCfg<'a>,
+                       toolchain: &ToolchainDesc)
+                       -> Result<Option<ManifestV2>> {
+    (download.notify_handler)(Notification::DownloadingManifest(&toolchain.to_string()));
+    match dl_v2_manifest(download, None, toolchain) {
+        Ok(m) => Ok(m.map(|(m, _)| m)),
+        Err(Error(ErrorKind::Utils(::rustup_utils::ErrorKind::DownloadNotExists { .. }), _)) => {
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
 fn dl_v2_manifest<'a>(download: DownloadCfg<'a>,
                       update_hash: Option<&Path>,
                       toolchain: &ToolchainDesc)
                       -> Result<Option<(ManifestV2, String)>> {
-    let manifest_url = toolchain.manifest_v2_url(download.dist_root);
+    let manifest_url = toolchain.manifest_v2_url(download.dist_root, download.staged_manifest);
     let manifest_dl_res = download.download_and_check(&manifest_url, update_hash, ".toml");
 
     if let Ok(manifest_dl) = manifest_dl_res {
@@ -584,7 +690,7 @@ fn dl_v1_manifest<'a>(download: DownloadCfg<'a>, toolchain: &ToolchainDesc) -> R
         return Ok(vec![installer_name]);
     }
 
-    let manifest_url = toolchain.manifest_v1_url(download.dist_root);
+    let manifest_url = toolchain.manifest_v1_url(download.dist_root, download.staged_manifest);
     let manifest_dl = try!(download.download_and_check(&manifest_url, None, ""));
     let (manifest_file, _) = manifest_dl.unwrap();
     let manifest_str = try!(utils::read_file("manifest", &manifest_file));
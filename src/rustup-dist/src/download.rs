@@ -17,6 +17,8 @@ pub struct DownloadCfg<'a> {
     pub dist_root: &'a str,
     pub temp_cfg: &'a temp::Cfg,
     pub download_dir: &'a PathBuf,
+    pub download_backend: Option<&'a str>,
+    pub staged_manifest: bool,
     pub notify_handler: &'a Fn(Notification),
 }
 
@@ -68,10 +70,11 @@ impl<'a> DownloadCfg<'a> {
 
         let mut hasher = Sha256::new();
 
-        try!(utils::download_file_with_resume(&url,
+        try!(utils::download_file_with_backend(&url,
                                   &partial_file_path,
                                   Some(&mut hasher),
                                   true,
+                                  self.download_backend,
                                   &|n| (self.notify_handler)(n.into())));
 
         let actual_hash = hasher.result_str();
@@ -104,9 +107,11 @@ impl<'a> DownloadCfg<'a> {
         let hash_url = try!(utils::parse_url(&(url.to_owned() + ".sha256")));
         let hash_file = try!(self.temp_cfg.new_file());
 
-        try!(utils::download_file(&hash_url,
+        try!(utils::download_file_with_backend(&hash_url,
                                 &hash_file,
                                 None,
+                                false,
+                                self.download_backend,
                                 &|n| (self.notify_handler)(n.into())));
 
         Ok(try!(utils::read_file("hash", &hash_file).map(|s| s[0..64].to_owned())))
@@ -143,9 +148,11 @@ impl<'a> DownloadCfg<'a> {
         let file = try!(self.temp_cfg.new_file_with_ext("", ext));
 
         let mut hasher = Sha256::new();
-        try!(utils::download_file(&url,
+        try!(utils::download_file_with_backend(&url,
                                 &file,
                                 Some(&mut hasher),
+                                false,
+                                self.download_backend,
                                 &|n| (self.notify_handler)(n.into())));
         let actual_hash = hasher.result_str();
 
@@ -24,6 +24,10 @@ error_chain! {
             description("invalid custom toolchain name")
             display("invalid custom toolchain name: '{}'", t)
         }
+        InvalidProfile(p: String) {
+            description("invalid install profile")
+            display("invalid install profile: '{}'", p)
+        }
         ChecksumFailed {
             url: String,
             expected: String,
@@ -25,6 +25,8 @@ pub struct Manifest {
     pub manifest_version: String,
     pub date: String,
     pub packages: HashMap<String, Package>,
+    // Maps the old name of a renamed package to its new name
+    pub renames: HashMap<String, String>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -78,6 +80,7 @@ impl Manifest {
         Ok(Manifest {
             manifest_version: version,
             date: try!(get_string(&mut table, "date", path)),
+            renames: try!(Self::table_to_renames(&mut table, path)),
             packages: try!(Self::table_to_packages(table, path)),
         })
     }
@@ -88,6 +91,11 @@ impl Manifest {
         result.insert("manifest-version".to_owned(),
                       toml::Value::String(self.manifest_version));
 
+        let renames = Self::renames_to_table(self.renames);
+        if !renames.is_empty() {
+            result.insert("renames".to_owned(), toml::Value::Table(renames));
+        }
+
         let packages = Self::packages_to_table(self.packages);
         result.insert("pkg".to_owned(), toml::Value::Table(packages));
 
@@ -114,6 +122,40 @@ impl Manifest {
         result
     }
 
+    fn table_to_renames(table: &mut toml::Table, path: &str) -> Result<HashMap<String, String>> {
+        let mut result = HashMap::new();
+        let rename_table = try!(get_table(table, "renames", path));
+
+        for (k, v) in rename_table {
+            if let toml::Value::Table(mut t) = v {
+                let path = format!("{}renames.{}.", path, k);
+                result.insert(k, try!(get_string(&mut t, "to", &path)));
+            }
+        }
+
+        Ok(result)
+    }
+    fn renames_to_table(renames: HashMap<String, String>) -> toml::Table {
+        let mut result = toml::Table::new();
+        for (from, to) in renames {
+            let mut t = toml::Table::new();
+            t.insert("to".to_owned(), toml::Value::String(to));
+            result.insert(from, toml::Value::Table(t));
+        }
+        result
+    }
+
+    /// If the package of `component` has been renamed, returns the
+    /// component under its new name
+    pub fn rename_component(&self, component: &Component) -> Option<Component> {
+        self.renames.get(&component.pkg).map(|new_pkg| {
+            Component {
+                pkg: new_pkg.to_owned(),
+                target: component.target.clone(),
+            }
+        })
+    }
+
     pub fn get_package(&self, name: &str) -> Result<&Package> {
         self.packages.get(name).ok_or_else(
             || format!("package not found: '{}'", name).into())
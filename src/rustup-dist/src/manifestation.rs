@@ -3,7 +3,7 @@
 
 use config::Config;
 use manifest::{Component, Manifest, TargetedPackage};
-use dist::{TargetTriple, DEFAULT_DIST_SERVER};
+use dist::{TargetTriple, Profile, DEFAULT_DIST_SERVER};
 use component::{Components, Transaction, TarGzPackage, TarXzPackage, Package};
 use temp;
 use errors::*;
@@ -12,6 +12,7 @@ use rustup_utils::utils;
 use download::{DownloadCfg, File};
 use prefix::InstallPrefix;
 use std::path::Path;
+use std::env::consts::EXE_SUFFIX;
 
 pub const DIST_MANIFEST: &'static str = "multirust-channel-manifest.toml";
 pub const CONFIG_FILE: &'static str = "multirust-config.toml";
@@ -31,6 +32,9 @@ pub struct Manifestation {
 pub struct Changes {
     pub add_extensions: Vec<Component>,
     pub remove_extensions: Vec<Component>,
+    /// The profile to use if this is a fresh install. Existing
+    /// installs keep the profile recorded in their config.
+    pub profile: Option<Profile>,
 }
 
 impl Changes {
@@ -38,6 +42,7 @@ impl Changes {
         Changes {
             add_extensions: Vec::new(),
             remove_extensions: Vec::new(),
+            profile: None,
         }
     }
 }
@@ -84,8 +89,6 @@ impl Manifestation {
         // Some vars we're going to need a few times
         let temp_cfg = download_cfg.temp_cfg;
         let prefix = self.installation.prefix();
-        let ref rel_installed_manifest_path = prefix.rel_manifest_file(DIST_MANIFEST);
-        let ref installed_manifest_path = prefix.path().join(rel_installed_manifest_path);
         let rust_package = try!(new_manifest.get_package("rust"));
         let rust_target_package = try!(rust_package.get_target(Some(&self.target_triple)));
 
@@ -95,9 +98,15 @@ impl Manifestation {
         // Load the configuration and list of installed components.
         let ref config = try!(self.read_config());
 
+        // An existing install keeps the profile it was installed with
+        let profile = config.as_ref().map(|c| c.profile)
+            .or(changes.profile)
+            .unwrap_or(Profile::Default);
+
         // Create the lists of components needed for installation
         let component_lists = try!(build_update_component_lists(new_manifest, old_manifest, config,
                                                                 changes, &rust_target_package,
+                                                                &self.target_triple, profile,
                                                                 notify_handler));
         let (components_to_uninstall,
              components_to_install,
@@ -107,8 +116,129 @@ impl Manifestation {
             return Ok(UpdateStatus::Unchanged);
         }
 
+        let (things_to_install, things_downloaded) =
+            try!(self.download_components(new_manifest, components_to_install,
+                                          download_cfg, notify_handler));
+
+        // Begin transaction
+        let mut tx = Transaction::new(prefix.clone(), temp_cfg, notify_handler);
+
+        // If the previous installation was from a v1 manifest we need
+        // to uninstall it first.
+        tx = try!(self.maybe_handle_v2_upgrade(config, tx));
+
+        // Uninstall components
+        for component in components_to_uninstall {
+
+            notify_handler(Notification::RemovingComponent(&component.pkg,
+                                                           &self.target_triple,
+                                                           component.target.as_ref()));
+
+            tx = try!(self.uninstall_component(&component, tx, notify_handler.clone()));
+        }
+
+        tx = try!(self.install_components(things_to_install, tx, temp_cfg, notify_handler));
+        tx = try!(self.write_metadata(new_manifest, final_component_list, profile, tx));
+
+        // End transaction
+        tx.commit();
+
+        try!(download_cfg.clean(&things_downloaded));
+
+        Ok(UpdateStatus::Changed)
+    }
+
+    /// Install components from a channel manifest without the rest of
+    /// the package, leaving out even the components the manifest marks
+    /// as required.
+    ///
+    /// This is for a prefix that adds to an installation made some
+    /// other way, such as the standard library for another target
+    /// beside a locally built compiler.
+    pub fn install_standalone(&self,
+                              new_manifest: &Manifest,
+                              components: Vec<Component>,
+                              download_cfg: &DownloadCfg,
+                              notify_handler: &Fn(Notification)) -> Result<UpdateStatus> {
+        let temp_cfg = download_cfg.temp_cfg;
+        let prefix = self.installation.prefix();
+        let config = try!(self.read_config());
+        let profile = config.as_ref().map(|c| c.profile).unwrap_or(Profile::Minimal);
+
+        let mut final_component_list = config.map(|c| c.components).unwrap_or(Vec::new());
+        let mut components_to_install = Vec::new();
+        for component in components {
+            if !final_component_list.contains(&component) {
+                final_component_list.push(component.clone());
+                components_to_install.push(component);
+            }
+        }
+
+        if components_to_install.is_empty() {
+            return Ok(UpdateStatus::Unchanged);
+        }
+
+        let (things_to_install, things_downloaded) =
+            try!(self.download_components(new_manifest, components_to_install,
+                                          download_cfg, notify_handler));
+
+        let mut tx = Transaction::new(prefix.clone(), temp_cfg, notify_handler);
+        tx = try!(self.install_components(things_to_install, tx, temp_cfg, notify_handler));
+        tx = try!(self.write_metadata(new_manifest, final_component_list, profile, tx));
+        tx.commit();
+
+        try!(download_cfg.clean(&things_downloaded));
+
+        Ok(UpdateStatus::Changed)
+    }
+
+    /// Removes components installed with `install_standalone`. Those
+    /// that aren't installed are ignored.
+    pub fn uninstall_standalone(&self,
+                                components: Vec<Component>,
+                                temp_cfg: &temp::Cfg,
+                                notify_handler: &Fn(Notification)) -> Result<UpdateStatus> {
+        let prefix = self.installation.prefix();
+        let (config, manifest) = match (try!(self.read_config()), try!(self.load_manifest())) {
+            (Some(config), Some(manifest)) => (config, manifest),
+            _ => return Ok(UpdateStatus::Unchanged),
+        };
+
+        let mut final_component_list = config.components;
+        let components_to_uninstall: Vec<Component> = components.into_iter()
+            .filter(|c| final_component_list.contains(c))
+            .collect();
+        if components_to_uninstall.is_empty() {
+            return Ok(UpdateStatus::Unchanged);
+        }
+
+        let mut tx = Transaction::new(prefix.clone(), temp_cfg, notify_handler);
+        for component in components_to_uninstall {
+            notify_handler(Notification::RemovingComponent(&component.pkg,
+                                                           &self.target_triple,
+                                                           component.target.as_ref()));
+            tx = try!(self.uninstall_component(&component, tx, notify_handler.clone()));
+            final_component_list.retain(|c| *c != component);
+        }
+        tx = try!(self.write_metadata(&manifest, final_component_list, config.profile, tx));
+        tx.commit();
+
+        Ok(UpdateStatus::Changed)
+    }
+
+    /// Downloads the packages of `components`, after checking that the
+    /// manifest has them all available. Returns each component with
+    /// its downloaded package, and the hashes of the downloads.
+    fn download_components(&self,
+                           new_manifest: &Manifest,
+                           components: Vec<Component>,
+                           download_cfg: &DownloadCfg,
+                           notify_handler: &Fn(Notification))
+                           -> Result<(Vec<(Component, Format, File)>, Vec<String>)> {
+        let temp_cfg = download_cfg.temp_cfg;
+
         // Validate that the requested components are available
-        let unavailable_components: Vec<Component> = components_to_install.iter().filter(|c| {
+        let unavailable_components: Vec<Component> = components.iter().filter(|c| {
             use manifest::*;
             let pkg: Option<&Package> = new_manifest.get_package(&c.pkg).ok();
             let target_pkg: Option<&TargetedPackage> = pkg.and_then(|p| p.get_target(c.target.as_ref()).ok());
@@ -121,7 +251,7 @@ impl Manifestation {
 
         // Map components to urls and hashes
         let mut components_urls_and_hashes: Vec<(Component, Format, String, String)> = Vec::new();
-        for component in components_to_install {
+        for component in components {
             let package = try!(new_manifest.get_package(&component.pkg));
             let target_package = try!(package.get_target(component.target.as_ref()));
             let c_u_h =
@@ -160,23 +290,14 @@ impl Manifestation {
             things_to_install.push((component, format, dowloaded_file));
         }
 
-        // Begin transaction
-        let mut tx = Transaction::new(prefix.clone(), temp_cfg, notify_handler);
-
-        // If the previous installation was from a v1 manifest we need
-        // to uninstall it first.
-        tx = try!(self.maybe_handle_v2_upgrade(config, tx));
-
-        // Uninstall components
-        for component in components_to_uninstall {
-
-            notify_handler(Notification::RemovingComponent(&component.pkg,
-                                                           &self.target_triple,
-                                                           component.target.as_ref()));
-
-            tx = try!(self.uninstall_component(&component, tx, notify_handler.clone()));
-        }
+        Ok((things_to_install, things_downloaded))
+    }
 
+    fn install_components<'a>(&self,
+                              things_to_install: Vec<(Component, Format, File)>,
+                              mut tx: Transaction<'a>,
+                              temp_cfg: &temp::Cfg,
+                              notify_handler: &Fn(Notification)) -> Result<Transaction<'a>> {
         // Install components
         for (component, format, installer_file) in things_to_install {
 
@@ -215,10 +336,24 @@ impl Manifestation {
                                       tx));
         }
 
+        Ok(tx)
+    }
+
+    /// Writes the distribution manifest and the configuration listing
+    /// the installed components
+    fn write_metadata<'a>(&self,
+                          new_manifest: &Manifest,
+                          components: Vec<Component>,
+                          profile: Profile,
+                          mut tx: Transaction<'a>) -> Result<Transaction<'a>> {
+        let prefix = self.installation.prefix();
+        let ref rel_installed_manifest_path = prefix.rel_manifest_file(DIST_MANIFEST);
+        let ref installed_manifest_path = prefix.path().join(rel_installed_manifest_path);
+
         // Install new distribution manifest
         let ref new_manifest_str = new_manifest.clone().stringify();
         try!(tx.modify_file(rel_installed_manifest_path.to_owned()));
-        try!(utils::write_file("manifest", installed_manifest_path, new_manifest_str));
+        try!(utils::write_file_atomic("manifest", installed_manifest_path, new_manifest_str));
 
         // Write configuration.
         //
@@ -227,19 +362,15 @@ impl Manifestation {
         // `Components` *also* tracks what is installed, but it only tracks names, not
         // name/target. Needs to be fixed in rust-installer.
         let mut config = Config::new();
-        config.components = final_component_list;
+        config.components = components;
+        config.profile = profile;
         let ref config_str = config.stringify();
         let ref rel_config_path = prefix.rel_manifest_file(CONFIG_FILE);
         let ref config_path = prefix.path().join(rel_config_path);
         try!(tx.modify_file(rel_config_path.to_owned()));
-        try!(utils::write_file("dist config", config_path, config_str));
+        try!(utils::write_file_atomic("dist config", config_path, config_str));
 
-        // End transaction
-        tx.commit();
-
-        try!(download_cfg.clean(&things_downloaded));
-
-        Ok(UpdateStatus::Changed)
+        Ok(tx)
     }
 
     pub fn uninstall(&self, temp_cfg: &temp::Cfg, notify_handler: &Fn(Notification)) -> Result<()> {
@@ -305,6 +436,30 @@ impl Manifestation {
         }
     }
 
+    /// The names of the executables the installed components put in
+    /// `bin/`, without the executable suffix
+    pub fn installed_binaries(&self) -> Result<Vec<String>> {
+        let bin_dir = Path::new("bin");
+        let mut binaries = Vec::new();
+        for component in try!(self.installation.list()) {
+            for part in try!(component.parts()) {
+                if part.0 != "file" || part.1.parent() != Some(bin_dir) {
+                    continue;
+                }
+                let name = match part.1.file_name().and_then(|n| n.to_str()) {
+                    Some(n) if n.ends_with(EXE_SUFFIX) => n[..n.len() - EXE_SUFFIX.len()].to_owned(),
+                    _ => continue,
+                };
+                if !binaries.contains(&name) {
+                    binaries.push(name);
+                }
+            }
+        }
+        binaries.sort();
+
+        Ok(binaries)
+    }
+
     /// Installation using the legacy v1 manifest format
     pub fn update_v1(&self,
                      new_manifest: &[String],
@@ -334,6 +489,8 @@ impl Manifestation {
             dist_root: "bogus",
             download_dir: &dld_dir,
             temp_cfg: temp_cfg,
+            download_backend: None,
+            staged_manifest: false,
             notify_handler: notify_handler
         };
 
@@ -400,21 +557,38 @@ fn build_update_component_lists(
     config: &Option<Config>,
     changes: Changes,
     rust_target_package: &TargetedPackage,
+    target_triple: &TargetTriple,
+    profile: Profile,
     notify_handler: &Fn(Notification),
     ) -> Result<(Vec<Component>, Vec<Component>, Vec<Component>)> {
 
+    // Components and extensions both count as part of the package,
+    // since a profile may leave out some of the required components
+    let in_package = |c: &Component| {
+        rust_target_package.extensions.contains(c) || rust_target_package.components.contains(c)
+    };
+
+    // Components may be referred to by the name they had before the
+    // manifest renamed them
+    let rename = |c: &Component| new_manifest.rename_component(c).unwrap_or_else(|| c.clone());
+    let changes = Changes {
+        add_extensions: changes.add_extensions.iter().map(&rename).collect(),
+        remove_extensions: changes.remove_extensions.iter().map(&rename).collect(),
+        profile: changes.profile,
+    };
+
     // Check some invariantns
     for component_to_add in &changes.add_extensions {
-        assert!(rust_target_package.extensions.contains(component_to_add),
+        assert!(in_package(component_to_add),
                 "package must contain extension to add");
         assert!(!changes.remove_extensions.contains(component_to_add),
                 "can't both add and remove extensions");
     }
     for component_to_remove in &changes.remove_extensions {
-        assert!(rust_target_package.extensions.contains(component_to_remove),
+        assert!(in_package(component_to_remove),
                 "package must contain extension to remove");
         let config = config.as_ref().expect("removing extension on fresh install?");
-        assert!(config.components.contains(component_to_remove),
+        assert!(config.components.iter().any(|c| rename(c) == *component_to_remove),
                 "removing package that isn't installed");
     }
 
@@ -433,24 +607,55 @@ fn build_update_component_lists(
     // installed extensions.
 
     // Add components required by the package, according to the
-    // manifest
+    // manifest, that the profile selects. These can't be removed
+    // while the profile selects them, so adding them on an update
+    // only picks up components the manifest newly requires
     for required_component in &rust_target_package.components {
-        final_component_list.push(required_component.clone());
+        if profile.includes_component(required_component) &&
+            !changes.remove_extensions.contains(required_component) {
+            final_component_list.push(required_component.clone());
+        }
+    }
+
+    // Add the extensions the profile selects, if they can be
+    // installed. This is only for a fresh install: on an update the
+    // installed components are the baseline, so extensions removed
+    // since stay removed
+    if config.is_none() {
+        for extension in &rust_target_package.extensions {
+            let available = new_manifest.get_package(&extension.pkg).ok()
+                .and_then(|p| p.get_target(extension.target.as_ref()).ok())
+                .map(|tp| tp.available) == Some(true);
+            let is_removed = changes.remove_extensions.contains(extension);
+            let is_already_included = final_component_list.contains(extension);
+
+            if profile.includes_extension(extension, target_triple) && available &&
+                !is_removed && !is_already_included {
+                final_component_list.push(extension.clone());
+            }
+        }
     }
 
     // Add requested extension components
     for extension in &changes.add_extensions {
-        final_component_list.push(extension.clone());
+        if !final_component_list.contains(extension) {
+            final_component_list.push(extension.clone());
+        }
     }
 
-    // Add extensions that are already installed
+    // Add extensions, and components the profile left out, that are
+    // already installed, migrating any that have been renamed
     for existing_component in &starting_list {
-        let is_extension = rust_target_package.extensions.contains(existing_component);
-        let is_removed = changes.remove_extensions.contains(existing_component);
-        let is_already_included = final_component_list.contains(existing_component);
+        let component = rename(existing_component);
+        let is_removed = changes.remove_extensions.contains(&component);
+        let is_already_included = final_component_list.contains(&component);
+
+        if component != *existing_component && !is_removed {
+            notify_handler(Notification::ComponentRenamed(existing_component, &component));
+        }
 
-        if is_extension && !is_removed && !is_already_included{
-            final_component_list.push(existing_component.clone());
+        if in_package(&component) && !is_removed && !is_already_included {
+            final_component_list.push(component);
         }
     }
 
@@ -14,6 +14,7 @@ pub enum Notification<'a> {
 
     Extracting(&'a Path, &'a Path),
     ComponentAlreadyInstalled(&'a Component),
+    ComponentRenamed(&'a Component, &'a Component),
     CantReadUpdateHash(&'a Path),
     NoUpdateHash(&'a Path),
     ChecksumValid(&'a str),
@@ -58,6 +59,7 @@ impl<'a> Notification<'a> {
             InstallingComponent(_, _, _) |
             RemovingComponent(_, _, _) |
             ComponentAlreadyInstalled(_)  |
+            ComponentRenamed(_, _) |
             ManifestChecksumFailedHack |
             RollingBack | DownloadingManifest(_) => NotificationLevel::Info,
             CantReadUpdateHash(_) | ExtensionNotInstalled(_) |
@@ -77,6 +79,9 @@ impl<'a> Display for Notification<'a> {
             ComponentAlreadyInstalled(ref c) => {
                 write!(f, "component {} is up to date", c.description())
             }
+            ComponentRenamed(ref from, ref to) => {
+                write!(f, "component '{}' has been renamed to '{}'", from.pkg, to.pkg)
+            }
             CantReadUpdateHash(path) => {
                 write!(f,
                        "can't read update hash file: '{}', can't skip update...",
@@ -140,6 +140,11 @@ impl Cfg {
         }
     }
 
+    /// The directory the temporary files and directories are created in
+    pub fn root_directory(&self) -> &Path {
+        &self.root_directory
+    }
+
     pub fn create_root(&self) -> Result<bool> {
         raw::ensure_dir_exists(&self.root_directory, |p| {
             (self.notify_handler)(Notification::CreatingRoot(p));
@@ -27,6 +27,7 @@ use rustup_dist::manifestation::{Manifestation, UpdateStatus, Changes};
 use rustup_dist::manifest::{Manifest, Component};
 use url::Url;
 use std::cell::Cell;
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 use std::sync::Arc;
@@ -52,7 +53,7 @@ pub fn create_mock_channel(channel: &str, date: &str,
 
     let rust_pkg = MockPackage {
         name: "rust",
-        version: "1.0.0",
+        version: "1.0.0".to_owned(),
         targets: vec![
             MockTargetedPackage {
                 target: "x86_64-apple-darwin".to_string(),
@@ -104,7 +105,7 @@ pub fn create_mock_channel(channel: &str, date: &str,
 
     let rustc_pkg = MockPackage {
         name: "rustc",
-        version: "1.0.0",
+        version: "1.0.0".to_owned(),
         targets: vec![
             MockTargetedPackage {
                 target: "x86_64-apple-darwin".to_string(),
@@ -138,7 +139,7 @@ pub fn create_mock_channel(channel: &str, date: &str,
 
     let std_pkg = MockPackage {
         name: "rust-std",
-        version: "1.0.0",
+        version: "1.0.0".to_owned(),
         targets: vec![
             MockTargetedPackage {
                 target: "x86_64-apple-darwin".to_string(),
@@ -201,7 +202,7 @@ pub fn create_mock_channel(channel: &str, date: &str,
     // for various tests
     let bonus_pkg = MockPackage {
         name: "bonus",
-        version: "1.0.0",
+        version: "1.0.0".to_owned(),
         targets: vec![
             MockTargetedPackage {
                 target: "x86_64-apple-darwin".to_string(),
@@ -237,7 +238,8 @@ pub fn create_mock_channel(channel: &str, date: &str,
             rustc_pkg,
             std_pkg,
             bonus_pkg,
-            ]
+            ],
+        renames: HashMap::new(),
     }
 }
 
@@ -286,6 +288,7 @@ fn update_from_dist(dist_server: &Url,
     let changes = Changes {
         add_extensions: add.to_owned(),
         remove_extensions: remove.to_owned(),
+        profile: None,
     };
 
     manifestation.update(&manifest, changes, download_cfg, download_cfg.notify_handler.clone())
@@ -309,8 +312,13 @@ fn uninstall(toolchain: &ToolchainDesc, prefix: &InstallPrefix, temp_cfg: &temp:
 
 fn setup(edit: Option<&Fn(&str, &mut MockPackage)>, enable_xz: bool,
          f: &Fn(&Url, &ToolchainDesc, &InstallPrefix, &DownloadCfg, &temp::Cfg)) {
+    setup_from_dist_server(&|path| create_mock_dist_server(path, edit), enable_xz, f);
+}
+
+fn setup_from_dist_server(server: &Fn(&Path) -> MockDistServer, enable_xz: bool,
+                          f: &Fn(&Url, &ToolchainDesc, &InstallPrefix, &DownloadCfg, &temp::Cfg)) {
     let dist_tempdir = TempDir::new("multirust").unwrap();
-    create_mock_dist_server(dist_tempdir.path(), edit).write(&[ManifestVersion::V2], enable_xz);
+    server(dist_tempdir.path()).write(&[ManifestVersion::V2], enable_xz);
 
     let prefix_tempdir = TempDir::new("multirust").unwrap();
 
@@ -326,6 +334,8 @@ fn setup(edit: Option<&Fn(&str, &mut MockPackage)>, enable_xz: bool,
         dist_root: "phony",
         temp_cfg: temp_cfg,
         download_dir: &prefix.path().to_owned().join("downloads"),
+        download_backend: None,
+        staged_manifest: false,
         notify_handler: &|_|{}
     };
 
@@ -497,6 +507,70 @@ fn update_preserves_components_that_became_extensions() {
     });
 }
 
+// On day 2 the 'bonus' package is renamed to 'bonus-renamed'
+fn create_mock_dist_server_with_rename(path: &Path) -> MockDistServer {
+    let edit = &|date: &str, pkg: &mut MockPackage| {
+        let name = if date == "2016-02-01" { "bonus" } else { "bonus-renamed" };
+        let mut tpkg = pkg.targets.iter_mut().find(|p| p.target == "x86_64-apple-darwin").unwrap();
+        tpkg.extensions.push(MockComponent {
+            name: name.to_string(),
+            target: "x86_64-apple-darwin".to_string(),
+        });
+    };
+    let mut server = create_mock_dist_server(path, Some(edit));
+    {
+        let ref mut channel = server.channels[1];
+        channel.renames.insert("bonus".to_string(), "bonus-renamed".to_string());
+        let bonus_pkg = channel.packages.iter_mut().find(|p| p.name == "bonus").unwrap();
+        bonus_pkg.name = "bonus-renamed";
+        bonus_pkg.targets[0].installer.components[0].0 = "bonus-renamed-x86_64-apple-darwin".to_string();
+    }
+    server
+}
+
+#[test]
+fn update_migrates_renamed_extensions() {
+    setup_from_dist_server(&create_mock_dist_server_with_rename, false,
+                           &|url, toolchain, prefix, download_cfg, temp_cfg| {
+        let ref adds = vec![
+            Component {
+                pkg: "bonus".to_string(), target: Some(TargetTriple::from_str("x86_64-apple-darwin"))
+            },
+            ];
+
+        change_channel_date(url, "nightly", "2016-02-01");
+        update_from_dist(url, toolchain, prefix, adds, &[], download_cfg, temp_cfg).unwrap();
+        assert!(utils::path_exists(&prefix.path().join("bin/bonus")));
+
+        change_channel_date(url, "nightly", "2016-02-02");
+        update_from_dist(url, toolchain, prefix, &[], &[], download_cfg, temp_cfg).unwrap();
+        assert!(utils::path_exists(&prefix.path().join("bin/bonus")));
+
+        let manifestation = Manifestation::open(prefix.clone(), toolchain.target.clone()).unwrap();
+        let config = manifestation.read_config().unwrap().unwrap();
+        assert!(config.components.contains(&Component {
+            pkg: "bonus-renamed".to_string(), target: Some(TargetTriple::from_str("x86_64-apple-darwin"))
+        }));
+        assert!(!config.components.iter().any(|c| c.pkg == "bonus"));
+    });
+}
+
+#[test]
+fn add_extension_by_old_name_after_rename() {
+    setup_from_dist_server(&create_mock_dist_server_with_rename, false,
+                           &|url, toolchain, prefix, download_cfg, temp_cfg| {
+        let ref adds = vec![
+            Component {
+                pkg: "bonus".to_string(), target: Some(TargetTriple::from_str("x86_64-apple-darwin"))
+            },
+            ];
+
+        change_channel_date(url, "nightly", "2016-02-02");
+        update_from_dist(url, toolchain, prefix, adds, &[], download_cfg, temp_cfg).unwrap();
+        assert!(utils::path_exists(&prefix.path().join("bin/bonus")));
+    });
+}
+
 #[test]
 fn update_makes_no_changes_for_identical_manifest() {
     setup(None, false, &|url, toolchain, prefix, download_cfg, temp_cfg| {
@@ -957,6 +1031,8 @@ fn reuse_downloaded_file() {
             dist_root: download_cfg.dist_root,
             temp_cfg: download_cfg.temp_cfg,
             download_dir: download_cfg.download_dir,
+            download_backend: None,
+            staged_manifest: false,
             notify_handler: &|n| {
                 if let Notification::FileAlreadyDownloaded = n {
                     reuse_notification_fired.set(true);
@@ -991,6 +1067,8 @@ fn checks_files_hashes_before_reuse() {
             dist_root: download_cfg.dist_root,
             temp_cfg: download_cfg.temp_cfg,
             download_dir: download_cfg.download_dir,
+            download_backend: None,
+            staged_manifest: false,
             notify_handler: &|n| {
                 if let Notification::CachedFileChecksumFailed = n {
                     noticed_bad_checksum.set(true);
@@ -1,6 +1,6 @@
 extern crate rustup_dist;
 
-use rustup_dist::manifest::Manifest;
+use rustup_dist::manifest::{Manifest, Component};
 use rustup_dist::ErrorKind;
 use rustup_dist::dist::TargetTriple;
 
@@ -96,3 +96,40 @@ fn manifest_can_contain_unknown_targets() {
 
     assert!(Manifest::parse(&manifest).is_ok());
 }
+
+#[test]
+fn parse_renames() {
+    let manifest = r#"
+manifest-version = "2"
+date = "2015-10-10"
+[renames.rls]
+  to = "rls-preview"
+[pkg.rust]
+  version = "rustc 1.3.0 (9a92aaf19 2015-09-15)"
+  [pkg.rust.target.x86_64-unknown-linux-gnu]
+    available = true
+    url = "example.com"
+    hash = "..."
+    [[pkg.rust.target.x86_64-unknown-linux-gnu.extensions]]
+      pkg = "rls-preview"
+      target = "x86_64-unknown-linux-gnu"
+[pkg.rls-preview]
+  version = "rls 0.1.0"
+  [pkg.rls-preview.target.x86_64-unknown-linux-gnu]
+    available = true
+    url = "example.com"
+    hash = "..."
+"#;
+
+    let x86_64_unknown_linux_gnu = TargetTriple::from_str("x86_64-unknown-linux-gnu");
+    let pkg = Manifest::parse(manifest).unwrap();
+    assert_eq!(pkg.renames.get("rls").map(|s| &**s), Some("rls-preview"));
+
+    let rls = Component { pkg: "rls".to_string(), target: Some(x86_64_unknown_linux_gnu.clone()) };
+    let renamed = pkg.rename_component(&rls).unwrap();
+    assert_eq!(renamed.pkg, "rls-preview");
+    assert_eq!(renamed.target, Some(x86_64_unknown_linux_gnu));
+
+    let serialized = pkg.clone().stringify();
+    assert_eq!(Manifest::parse(&serialized).unwrap(), pkg);
+}
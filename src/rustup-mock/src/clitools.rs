@@ -6,6 +6,7 @@ use std::env;
 use std::process::{Command, Stdio};
 use std::env::consts::EXE_SUFFIX;
 use std::fs::{self, File};
+use std::collections::HashMap;
 use std::io::{self, Read, Write};
 use std::sync::Mutex;
 use std::time::Duration;
@@ -193,6 +194,17 @@ pub fn expect_not_stdout_ok(config: &Config, args: &[&str], expected: &str) {
     assert!(! out.stdout.contains(expected), args);
 }
 
+pub fn expect_not_stderr_ok(config: &Config, args: &[&str], expected: &str) {
+    let out = run(config, args[0], &args[1..], &[]);
+    println!("out.ok: {}", out.ok);
+    println!("out.stdout:\n\n{}", out.stdout);
+    println!("out.stderr:\n\n{}", out.stderr);
+    println!("expected: {}", expected);
+    let args = format!("{:?}", args);
+    assert!(out.ok, args);
+    assert!(! out.stderr.contains(expected), args);
+}
+
 pub fn expect_stderr_ok(config: &Config, args: &[&str], expected: &str) {
     let out = run(config, args[0], &args[1..], &[]);
     println!("out.ok: {}", out.ok);
@@ -426,6 +438,13 @@ fn build_mock_channel(s: Scenario, channel: &str, date: &str,
             }
         });
 
+        // rustc is versioned with the commit it's built from, like
+        // the real one, which rustup matches linked toolchains against
+        let version = if name == "rustc" {
+            format!("{} ({} {})", version, version_hash, date)
+        } else {
+            version.to_string()
+        };
         MockPackage {
             name: name,
             version: version,
@@ -482,6 +501,7 @@ fn build_mock_channel(s: Scenario, channel: &str, date: &str,
         name: channel.to_string(),
         date: date.to_string(),
         packages: packages,
+        renames: HashMap::new(),
     }
 }
 
@@ -74,13 +74,15 @@ pub struct MockChannel {
     // YYYY-MM-DD
     pub date: String,
     pub packages: Vec<MockPackage>,
+    // Old package names mapped to the names they were renamed to
+    pub renames: HashMap<String, String>,
 }
 
 // A single rust-installer package
 pub struct MockPackage {
     // rust, rustc, rust-std-$triple, rust-doc, etc.
     pub name: &'static str,
-    pub version: &'static str,
+    pub version: String,
     pub targets: Vec<MockTargetedPackage>,
 }
 
@@ -290,6 +292,15 @@ impl MockDistServer {
         }
         toml_manifest.insert(String::from("pkg"), toml::Value::Table(toml_packages));
 
+        // [renames.*]
+        let mut toml_renames = toml::Table::new();
+        for (from, to) in &channel.renames {
+            let mut toml_rename = toml::Table::new();
+            toml_rename.insert(String::from("to"), toml::Value::String(to.to_owned()));
+            toml_renames.insert(from.to_owned(), toml::Value::Table(toml_rename));
+        }
+        toml_manifest.insert(String::from("renames"), toml::Value::Table(toml_renames));
+
         let manifest_name = format!("dist/channel-rust-{}", channel.name);
         let ref manifest_path = self.path.join(format!("{}.toml", manifest_name));
         write_file(manifest_path, &toml::encode_str(&toml_manifest));
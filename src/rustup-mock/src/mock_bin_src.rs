@@ -2,10 +2,23 @@ use std::process::Command;
 use std::io::{self, BufWriter, Write};
 use std::env::consts::EXE_SUFFIX;
 
+// The version is patched into the binary where it first appears, so
+// it must only appear once
+static VERSION: &'static str = "%EXAMPLE_VERSION% (%EXAMPLE_VERSION_HASH%)";
+
 fn main() {
-    let args: Vec<_> = ::std::env::args().collect();
+    let mut args: Vec<_> = ::std::env::args().collect();
+    // The sysroot rustup passes for a toolchain with an overlay
+    if args.get(1) == Some(&"--sysroot".to_string()) {
+        args.remove(1);
+        args.remove(1);
+    }
     if args.get(1) == Some(&"--version".to_string()) {
-        println!("%EXAMPLE_VERSION% (%EXAMPLE_VERSION_HASH%)");
+        println!("{}", VERSION);
+    } else if args.get(1) == Some(&"-vV".to_string()) {
+        println!("rustc {}", VERSION);
+        println!("commit-hash: {}", VERSION.split(' ').nth(1).unwrap().trim_matches(&['(', ')'][..]));
+        println!("release: {}", VERSION.split(' ').next().unwrap());
     } else if args.get(1) == Some(&"--empty-arg-test".to_string()) {
         assert!(args.get(2) == Some(&"".to_string()));
     } else if args.get(1) == Some(&"--huge-output".to_string()) {
@@ -21,6 +34,10 @@ fn main() {
         // will actually invoke the wrapper
         let rustc = &format!("rustc{}", EXE_SUFFIX);
         Command::new(rustc).arg("--version").status().unwrap();
+    } else if args.get(1) == Some(&"--echo-env".to_string()) {
+        // Prints a variable of the environment the proxy set up
+        let var = args.get(2).expect("--echo-env needs a variable name");
+        println!("{}={}", var, ::std::env::var(var).unwrap_or(String::new()));
     } else {
         panic!("bad mock proxy commandline");
     }
@@ -62,6 +62,37 @@ pub fn write_file(path: &Path, contents: &str) -> io::Result<()> {
     Ok(())
 }
 
+/// Writes `contents` to a temporary file next to `path`, syncs it,
+/// and renames it over `path`, so that a crash leaves either the old
+/// or the new contents but never a partial file.
+pub fn write_file_atomic(path: &Path, contents: &str) -> io::Result<()> {
+    let file_name = path.file_name().map(|n| n.to_string_lossy().into_owned())
+                                    .unwrap_or_default();
+    let tmp_name = format!(".{}.{}.tmp", file_name, random_string(8));
+    let tmp_path = match path.parent() {
+        Some(parent) => parent.join(tmp_name),
+        None => Path::new(&tmp_name).to_owned(),
+    };
+
+    let result = write_file(&tmp_path, contents).and_then(|()| fs::rename(&tmp_path, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    try!(result);
+
+    // Make the rename itself durable. Directories can't be opened
+    // this way on Windows, where the rename is already durable.
+    if cfg!(unix) {
+        if let Some(parent) = path.parent() {
+            if let Ok(dir) = fs::File::open(parent) {
+                let _ = dir.sync_all();
+            }
+        }
+    }
+
+    Ok(())
+}
+
 pub fn read_file(path: &Path) -> io::Result<String> {
     let mut file = try!(fs::OpenOptions::new()
                             .read(true)
@@ -252,6 +283,36 @@ pub fn hardlink(src: &Path, dest: &Path) -> io::Result<()> {
     fs::hard_link(src, dest)
 }
 
+/// Whether `a` and `b` are the same file, as with a hard link or a
+/// symlink, rather than two files with the same contents
+pub fn is_same_file(a: &Path, b: &Path) -> io::Result<bool> {
+    #[cfg(unix)]
+    fn file_id(path: &Path) -> io::Result<(u64, u64)> {
+        use std::os::unix::fs::MetadataExt;
+        let metadata = try!(fs::metadata(path));
+        Ok((metadata.dev(), metadata.ino()))
+    }
+    #[cfg(windows)]
+    fn file_id(path: &Path) -> io::Result<(u64, u64)> {
+        use winapi::*;
+        use kernel32::*;
+        use std::mem;
+        use std::os::windows::io::AsRawHandle;
+
+        let file = try!(fs::File::open(path));
+        unsafe {
+            let mut info: BY_HANDLE_FILE_INFORMATION = mem::zeroed();
+            if GetFileInformationByHandle(file.as_raw_handle() as HANDLE, &mut info) == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok((info.dwVolumeSerialNumber as u64,
+                (info.nFileIndexHigh as u64) << 32 | info.nFileIndexLow as u64))
+        }
+    }
+
+    Ok(try!(file_id(a)) == try!(file_id(b)))
+}
+
 #[derive(Debug)]
 pub enum CommandError {
     Io(io::Error),
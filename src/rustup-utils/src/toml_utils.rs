@@ -73,3 +73,15 @@ pub fn get_array(table: &mut toml::Table, key: &str, path: &str) -> Result<toml:
         Ok(toml::Array::new())
     }
 }
+
+pub fn get_string_array(table: &mut toml::Table, key: &str, path: &str) -> Result<Vec<String>> {
+    let mut result = Vec::new();
+    for v in try!(get_array(table, key, path)) {
+        if let toml::Value::String(s) = v {
+            result.push(s);
+        } else {
+            return Err(ErrorKind::ExpectedType("string", path.to_owned() + key).into());
+        }
+    }
+    Ok(result)
+}
@@ -50,6 +50,17 @@ pub fn write_file(name: &'static str, path: &Path, contents: &str) -> Result<()>
     })
 }
 
+/// Like `write_file`, but replaces the file atomically. Used for
+/// metadata that rustup can't function without.
+pub fn write_file_atomic(name: &'static str, path: &Path, contents: &str) -> Result<()> {
+    raw::write_file_atomic(path, contents).chain_err(|| {
+        ErrorKind::WritingFile {
+            name: name,
+            path: PathBuf::from(path),
+        }
+    })
+}
+
 pub fn append_file(name: &'static str, path: &Path, line: &str) -> Result<()> {
     raw::append_file(path, line).chain_err(|| {
         ErrorKind::WritingFile {
@@ -153,8 +164,20 @@ pub fn download_file_with_resume(url: &Url,
                      resume_from_partial: bool,
                      notify_handler: &Fn(Notification))
                      -> Result<()> {
+    download_file_with_backend(url, path, hasher, resume_from_partial, None, notify_handler)
+}
+
+/// Like `download_file_with_resume`, but downloads with `backend`
+/// ("curl", "hyper" or "rustls") unless the environment picks one
+pub fn download_file_with_backend(url: &Url,
+                     path: &Path,
+                     hasher: Option<&mut Sha256>,
+                     resume_from_partial: bool,
+                     backend: Option<&str>,
+                     notify_handler: &Fn(Notification))
+                     -> Result<()> {
     use download::ErrorKind as DEK;
-    match download_file_(url, path, hasher, resume_from_partial, notify_handler) {
+    match download_file_(url, path, hasher, resume_from_partial, backend, notify_handler) {
         Ok(_) => Ok(()),
         Err(e) => {
             let is_client_error = match e.kind() {
@@ -181,6 +204,7 @@ fn download_file_(url: &Url,
                   path: &Path,
                   hasher: Option<&mut Sha256>,
                   resume_from_partial: bool,
+                  backend: Option<&str>,
                   notify_handler: &Fn(Notification))
                   -> Result<()> {
 
@@ -221,14 +245,17 @@ fn download_file_(url: &Url,
     };
 
     // Download the file
-    let use_hyper_backend = env::var_os("RUSTUP_USE_HYPER").is_some();
-    let use_rustls_backend = env::var_os("RUSTUP_USE_RUSTLS").is_some();
-    let (backend, notification) = if use_hyper_backend {
-        (Backend::Hyper, Notification::UsingHyper)
-    } else if use_rustls_backend {
-        (Backend::Rustls, Notification::UsingRustls)
+    let backend = if env::var_os("RUSTUP_USE_HYPER").is_some() {
+        Some("hyper")
+    } else if env::var_os("RUSTUP_USE_RUSTLS").is_some() {
+        Some("rustls")
     } else {
-        (Backend::Curl, Notification::UsingCurl)
+        backend
+    };
+    let (backend, notification) = match backend {
+        Some("hyper") => (Backend::Hyper, Notification::UsingHyper),
+        Some("rustls") => (Backend::Rustls, Notification::UsingRustls),
+        _ => (Backend::Curl, Notification::UsingCurl),
     };
     notify_handler(notification);
     try!(download_to_path_with_backend(backend, url, path, resume_from_partial, Some(callback)));
@@ -283,6 +310,12 @@ pub fn hard_or_symlink_file(src: &Path, dest: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Whether `a` and `b` are links to the same file. False if either
+/// can't be read
+pub fn is_same_file(a: &Path, b: &Path) -> bool {
+    raw::is_same_file(a, b).unwrap_or(false)
+}
+
 pub fn hardlink_file(src: &Path, dest: &Path) -> Result<()> {
     raw::hardlink(src, dest).chain_err(|| {
         ErrorKind::LinkingFile {
@@ -780,6 +813,53 @@ pub fn string_from_winreg_value(val: &winreg::RegValue) -> Option<String> {
     }
 }
 
+/// Matches a toolchain name against a shell-style pattern where `*`
+/// matches any run of characters and `?` matches a single character
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = backtrack {
+            p = star_p + 1;
+            n = star_n + 1;
+            backtrack = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+pub fn is_glob(pattern: &str) -> bool {
+    pattern.contains('*') || pattern.contains('?')
+}
+
+/// The total size of the files under `path`, not following symlinks
+/// other than `path` itself
+pub fn dir_size(path: &Path) -> u64 {
+    let entries = match fs::read_dir(path) {
+        Ok(e) => e,
+        Err(_) => return 0,
+    };
+    entries.filter_map(|e| e.ok()).map(|e| {
+        match e.file_type() {
+            Ok(t) if t.is_dir() => dir_size(&e.path()),
+            Ok(t) if t.is_file() => e.metadata().map(|m| m.len()).unwrap_or(0),
+            _ => 0,
+        }
+    }).fold(0, |a, b| a + b)
+}
+
 pub fn toolchain_sort<T: AsRef<str>>(v: &mut Vec<T>) {
     use semver::{Version, Identifier};
 
@@ -845,4 +925,16 @@ mod tests {
 
         assert_eq!(expected, v);
     }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("nightly-*", "nightly-x86_64-unknown-linux-gnu"));
+        assert!(glob_match("*-msvc", "stable-x86_64-pc-windows-msvc"));
+        assert!(glob_match("1.?.0-*", "1.8.0-x86_64-unknown-linux-gnu"));
+        assert!(glob_match("*", "beta-x86_64-apple-darwin"));
+        assert!(glob_match("n*ly*gnu", "nightly-x86_64-unknown-linux-gnu"));
+        assert!(!glob_match("nightly-*", "stable-x86_64-unknown-linux-gnu"));
+        assert!(!glob_match("1.?.0-*", "1.10.0-x86_64-unknown-linux-gnu"));
+        assert!(!glob_match("stable", "stable-x86_64-unknown-linux-gnu"));
+    }
 }
@@ -0,0 +1,127 @@
+//! The log of the changes rustup makes to toolchains and settings, kept
+//! in `RUSTUP_HOME` with one JSON object per line and only appended to
+
+use errors::*;
+use notifications::*;
+use rustup_dist::manifest::Manifest;
+use rustup_dist::manifestation::DIST_MANIFEST;
+use rustup_dist::prefix::InstallPrefix;
+use rustup_utils::utils;
+use rustc_serialize::json;
+use time;
+
+use std::cell::RefCell;
+use std::env;
+use std::path::PathBuf;
+
+pub const AUDIT_LOG_FILE: &'static str = "audit.log";
+
+#[derive(RustcDecodable, RustcEncodable, Debug, Clone, PartialEq)]
+pub struct AuditEntry {
+    /// Seconds since the Unix epoch
+    pub time: i64,
+    pub user: Option<String>,
+    /// The command line of the process that made the change
+    pub command: Vec<String>,
+    /// What changed, such as `toolchain install` or `component add`
+    pub action: String,
+    pub toolchain: Option<String>,
+    /// The component, target, directory or version the change was to
+    pub detail: Option<String>,
+    /// The date of the toolchain's channel manifest after the change
+    pub manifest_date: Option<String>,
+    /// `ok`, or the error the change failed with
+    pub outcome: String,
+}
+
+pub struct AuditLog {
+    path: PathBuf,
+    toolchains_dir: PathBuf,
+    /// The toolchain being installed or updated, to tell which of the
+    /// two `InstalledToolchain` finished
+    installing: RefCell<Option<(String, &'static str)>>,
+}
+
+impl AuditLog {
+    pub fn new(path: PathBuf, toolchains_dir: PathBuf) -> Self {
+        AuditLog {
+            path: path,
+            toolchains_dir: toolchains_dir,
+            installing: RefCell::new(None),
+        }
+    }
+
+    /// Appends an entry for a change made by this process. The entry
+    /// is lost if the log can't be written, as the change itself was
+    /// still made.
+    pub fn record(&self, action: &str, toolchain: Option<&str>, detail: Option<&str>,
+                  error: Option<&Error>) {
+        let entry = AuditEntry {
+            time: time::now_utc().to_timespec().sec,
+            user: env::var("USER").or_else(|_| env::var("USERNAME")).ok(),
+            command: env::args_os().map(|a| a.to_string_lossy().into_owned()).collect(),
+            action: action.to_owned(),
+            toolchain: toolchain.map(|t| t.to_owned()),
+            detail: detail.map(|d| d.to_owned()),
+            manifest_date: toolchain.and_then(|t| self.manifest_date(t)),
+            outcome: error.map(|e| e.to_string()).unwrap_or_else(|| "ok".to_owned()),
+        };
+        if let Ok(line) = json::encode(&entry) {
+            let _ = utils::append_file("audit log", &self.path, &line);
+        }
+    }
+
+    /// Records the changes that notifications report
+    pub fn observe(&self, n: &Notification) {
+        match *n {
+            Notification::InstallingToolchain(name) => {
+                *self.installing.borrow_mut() = Some((name.to_owned(), "toolchain install"));
+            }
+            Notification::UpdatingToolchain(name) => {
+                *self.installing.borrow_mut() = Some((name.to_owned(), "toolchain update"));
+            }
+            Notification::InstalledToolchain(name) => {
+                let action = match self.installing.borrow_mut().take() {
+                    Some((ref n, action)) if n == name => action,
+                    _ => "toolchain install",
+                };
+                self.record(action, Some(name), None, None);
+            }
+            Notification::UninstalledToolchain(name) => {
+                self.record("toolchain uninstall", Some(name), None, None);
+            }
+            Notification::SetDefaultToolchain(name) => {
+                self.record("default", Some(name), None, None);
+            }
+            Notification::SetOverrideToolchain(path, name) => {
+                self.record("override set", Some(name), Some(&path.display().to_string()), None);
+            }
+            Notification::SetPinned(name, pinned) => {
+                self.record(if pinned { "toolchain pin" } else { "toolchain unpin" },
+                            Some(name), None, None);
+            }
+            _ => {}
+        }
+    }
+
+    /// Every entry in the log, oldest first. Lines that can't be read
+    /// are skipped.
+    pub fn entries(&self) -> Result<Vec<AuditEntry>> {
+        if !utils::is_file(&self.path) {
+            return Ok(Vec::new());
+        }
+        let content = try!(utils::read_file("audit log", &self.path));
+        Ok(content.lines().filter_map(|l| json::decode(l).ok()).collect())
+    }
+
+    fn manifest_date(&self, toolchain: &str) -> Option<String> {
+        let prefix = InstallPrefix::from(self.toolchains_dir.join(toolchain));
+        let manifest_file = prefix.manifest_file(DIST_MANIFEST);
+        if !utils::is_file(&manifest_file) {
+            return None;
+        }
+        utils::read_file("manifest", &manifest_file).ok()
+            .and_then(|content| Manifest::parse(&content).ok())
+            .map(|manifest| manifest.date)
+    }
+}
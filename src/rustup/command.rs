@@ -132,7 +132,7 @@ fn telemetry_rustc<S: AsRef<OsStr>>(mut cmd: Command,
     }
 }
 
-fn run_command_for_dir_without_telemetry<S: AsRef<OsStr>>(
+pub fn run_command_for_dir_without_telemetry<S: AsRef<OsStr>>(
     mut cmd: Command, arg0: &str, args: &[S]) -> Result<()>
 {
     cmd.args(args);
@@ -1,23 +1,45 @@
 use std::path::{Path, PathBuf};
 use std::borrow::Cow;
+use std::cmp;
+use std::collections::BTreeMap;
 use std::env;
+use std::fs;
+use std::env::consts::EXE_SUFFIX;
+use std::ffi::OsStr;
 use std::io;
 use std::process::Command;
 use std::fmt::{self, Display};
 use std::sync::Arc;
+use std::time::SystemTime;
 
 use errors::*;
 use notifications::*;
 use rustup_dist::{temp, dist};
+use rustup_dist::manifest::Component;
 use rustup_utils::utils;
-use toolchain::{Toolchain, UpdateStatus};
+use toolchain::{Toolchain, UpdateStatus, base_env_vars, tool_name};
 use telemetry_analysis::*;
 use settings::{TelemetryMode, SettingsFile, DEFAULT_METADATA_VERSION};
+use state::{State, ToolchainState};
+use lockfile::{ToolchainLock, LOCK_FILE};
+use proxy_cache::{CachedToolchain, ProxyCache, PROXY_CACHE_FILE};
+use migrations;
+use audit::{AuditLog, AUDIT_LOG_FILE};
+use rust_version;
+use env_var;
+
+/// The fallbacks of custom toolchains that haven't been configured
+pub static DEFAULT_FALLBACKS: &'static [&'static str] = &["nightly", "beta", "stable"];
+
+/// How long temporary files and downloads must have been left alone
+/// before `rustup clean` removes them
+pub const CLEAN_MIN_AGE_SECS: u64 = 60 * 60;
 
 #[derive(Debug)]
 pub enum OverrideReason {
     Environment,
     OverrideDB(PathBuf),
+    CargoManifest(PathBuf),
 }
 
 
@@ -29,6 +51,9 @@ impl Display for OverrideReason {
             OverrideReason::OverrideDB(ref path) => {
                 write!(f, "directory override for '{}'", path.display())
             }
+            OverrideReason::CargoManifest(ref path) => {
+                write!(f, "rust-version declared in '{}'", path.display())
+            }
         }
     }
 }
@@ -37,14 +62,23 @@ pub struct Cfg {
     pub multirust_dir: PathBuf,
     pub settings_file: SettingsFile,
     pub toolchains_dir: PathBuf,
+    pub system_toolchains_dir: Option<PathBuf>,
     pub update_hash_dir: PathBuf,
     pub download_dir: PathBuf,
+    /// Where the standard libraries added to custom toolchains are
+    /// installed, since the toolchains themselves aren't rustup's to
+    /// change
+    pub overlays_dir: PathBuf,
     pub temp_cfg: temp::Cfg,
     pub gpg_key: Cow<'static, str>,
     pub env_override: Option<String>,
     pub dist_root_url: String,
     pub dist_root_server: String,
+    pub update_root: Option<String>,
+    pub download_backend: Option<String>,
+    pub staged_manifest: bool,
     pub notify_handler: Arc<Fn(Notification)>,
+    pub audit_log: Arc<AuditLog>,
 }
 
 impl Cfg {
@@ -62,11 +96,54 @@ impl Cfg {
         let toolchains_dir = multirust_dir.join("toolchains");
         let update_hash_dir = multirust_dir.join("update-hashes");
         let download_dir = multirust_dir.join("downloads");
+        let overlays_dir = multirust_dir.join("overlays");
+
+        // The changes notifications report go to the audit log
+        let audit_log = Arc::new(AuditLog::new(multirust_dir.join(AUDIT_LOG_FILE),
+                                               toolchains_dir.clone()));
+        let notify_handler: Arc<Fn(Notification)> = {
+            let audit_log = audit_log.clone();
+            Arc::new(move |n: Notification| {
+                audit_log.observe(&n);
+                notify_handler(n)
+            })
+        };
+
+        // Values stored with `rustup set`, which the environment
+        // overrides. Settings from a newer rustup can't be read, but
+        // `rustup self update` has to work to fix that, so keep the
+        // servers it downloads from, use the defaults for the rest and
+        // leave the commands that need the settings to report the error
+        let stored = settings_file.with(|s| {
+            Ok((s.gpg_key.clone(), s.dist_server.clone(), s.update_root.clone(),
+                s.download_backend.clone(), s.staged_manifest, s.system_toolchains.clone()))
+        });
+        let (stored_gpg_key, stored_dist_server, update_root, download_backend, staged_manifest,
+             stored_system_toolchains) = match stored {
+            Ok(stored) => stored,
+            Err(Error(ErrorKind::MetadataTooNew(_), _)) => {
+                (None, settings_file.read_newer_string("dist_server"),
+                 settings_file.read_newer_string("update_root"), None, false, None)
+            }
+            Err(e) => return Err(e),
+        };
+
+        // Set the same way `rustup config list` reports it
+        let staged_manifest = staged_manifest ||
+            env::var("RUSTUP_STAGED_MANIFEST").ok().and_then(utils::if_not_empty).is_some();
+
+        // Toolchains installed by an administrator for all users
+        let system_toolchains_dir = env::var_os("RUSTUP_SYSTEM_TOOLCHAINS")
+                                        .and_then(utils::if_not_empty)
+                                        .map(PathBuf::from)
+                                        .or(stored_system_toolchains.map(PathBuf::from));
 
         // GPG key
         let gpg_key = if let Some(path) = env::var_os("RUSTUP_GPG_KEY")
                                               .and_then(utils::if_not_empty) {
             Cow::Owned(try!(utils::read_file("public key", Path::new(&path))))
+        } else if let Some(ref path) = stored_gpg_key {
+            Cow::Owned(try!(utils::read_file("public key", Path::new(path))))
         } else {
             Cow::Borrowed(include_str!("rust-key.gpg.ascii"))
         };
@@ -85,6 +162,7 @@ impl Cfg {
                 env::var("RUSTUP_DIST_ROOT")
                     .ok()
                     .and_then(utils::if_not_empty)
+                    .or(stored_dist_server)
                     .map_or(Cow::Borrowed(dist::DEFAULT_DIST_ROOT), Cow::Owned)
                     .as_ref()
                     .trim_right_matches("/dist")
@@ -104,17 +182,29 @@ impl Cfg {
             multirust_dir: multirust_dir,
             settings_file: settings_file,
             toolchains_dir: toolchains_dir,
+            system_toolchains_dir: system_toolchains_dir,
             update_hash_dir: update_hash_dir,
             download_dir: download_dir,
+            overlays_dir: overlays_dir,
             temp_cfg: temp_cfg,
             gpg_key: gpg_key,
             notify_handler: notify_handler,
+            audit_log: audit_log,
             env_override: env_override,
             dist_root_url: dist_root,
             dist_root_server: dist_root_server,
+            update_root: update_root,
+            download_backend: download_backend,
+            staged_manifest: staged_manifest,
         })
     }
 
+    pub fn set_config(&self, key: &str, value: &str) -> Result<()> {
+        try!(self.settings_file.with_mut(|s| s.set_config(key, value)));
+        (self.notify_handler)(Notification::SetConfig(key, value));
+        Ok(())
+    }
+
     pub fn set_default(&self, toolchain: &str) -> Result<()> {
         try!(self.settings_file.with_mut(|s| {
             s.default_toolchain = Some(toolchain.to_owned());
@@ -151,6 +241,14 @@ impl Cfg {
     }
 
     pub fn which_binary(&self, path: &Path, binary: &str) -> Result<Option<PathBuf>> {
+        if let Some(name) = try!(self.tool_override(binary)) {
+            let toolchain = try!(self.get_toolchain(&name, false));
+            let binary_path = toolchain.binary_file(binary);
+            if !utils::is_file(&binary_path) {
+                return Err(ErrorKind::BinaryNotFound(name, binary.to_owned()).into());
+            }
+            return Ok(Some(binary_path));
+        }
 
         if let Some((toolchain, _)) = try!(self.find_override_toolchain_or_default(path)) {
             Ok(Some(toolchain.binary_file(binary)))
@@ -169,35 +267,15 @@ impl Cfg {
             return Ok(());
         }
 
+        let steps = try!(migrations::steps_from(&current_version));
+
         (self.notify_handler)
             (Notification::UpgradingMetadata(&current_version, DEFAULT_METADATA_VERSION));
 
-        match &*current_version {
-            "2" => {
-                // The toolchain installation format changed. Just delete them all.
-                (self.notify_handler)(Notification::UpgradeRemovesToolchains);
-
-                let dirs = try!(utils::read_dir("toolchains", &self.toolchains_dir));
-                for dir in dirs {
-                    let dir = try!(dir.chain_err(|| ErrorKind::UpgradeIoError));
-                    try!(utils::remove_dir("toolchain", &dir.path(),
-                                           &|n| (self.notify_handler)(n.into())));
-                }
-
-                // Also delete the update hashes
-                let files = try!(utils::read_dir("update hashes", &self.update_hash_dir));
-                for file in files {
-                    let file = try!(file.chain_err(|| ErrorKind::UpgradeIoError));
-                    try!(utils::remove_file("update hash", &file.path()));
-                }
-
-                self.settings_file.with_mut(|s| {
-                    s.version = DEFAULT_METADATA_VERSION.to_owned();
-                    Ok(())
-                })
-            }
-            _ => Err(ErrorKind::UnknownMetadataVersion(current_version).into()),
+        for step in steps {
+            try!(migrations::run(self, step));
         }
+        Ok(())
     }
 
     pub fn delete_data(&self) -> Result<()> {
@@ -209,6 +287,66 @@ impl Cfg {
         }
     }
 
+    /// Finds what rustup leaves behind: temporary files and directories
+    /// of runs that didn't finish, downloads that were interrupted or
+    /// not cleaned up, and the update hashes of removed toolchains.
+    /// Returns their paths and sizes, and unless `dry_run` removes them.
+    ///
+    /// Temporary files and downloads modified in the last
+    /// `CLEAN_MIN_AGE_SECS` may belong to a rustup that's still running,
+    /// so they're left alone.
+    pub fn clean(&self, dry_run: bool) -> Result<Vec<(PathBuf, u64)>> {
+        let mut garbage = Vec::new();
+
+        let now = SystemTime::now();
+        for dir in &[self.temp_cfg.root_directory(), &*self.download_dir] {
+            if !utils::is_directory(dir) {
+                continue;
+            }
+            for entry in try!(utils::read_dir("directory", dir)) {
+                let entry = try!(entry.chain_err(|| "unable to read directory entry"));
+                let path = entry.path();
+                let age = last_modified(&path).and_then(|t| now.duration_since(t).ok());
+                if age.map(|a| a.as_secs() >= CLEAN_MIN_AGE_SECS).unwrap_or(false) {
+                    garbage.push(path);
+                }
+            }
+        }
+
+        if utils::is_directory(&self.update_hash_dir) {
+            for entry in try!(utils::read_dir("update hashes", &self.update_hash_dir)) {
+                let entry = try!(entry.chain_err(|| "unable to read update hash"));
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let installed = self.get_toolchain(&name, false)
+                    .map(|t| t.exists())
+                    .unwrap_or(false);
+                if !installed {
+                    garbage.push(entry.path());
+                }
+            }
+        }
+
+        garbage.sort();
+        let mut result = Vec::new();
+        for path in garbage {
+            let size = if utils::is_directory(&path) {
+                utils::dir_size(&path)
+            } else {
+                fs::symlink_metadata(&path).map(|m| m.len()).unwrap_or(0)
+            };
+            if !dry_run {
+                if utils::is_directory(&path) {
+                    try!(utils::remove_dir("directory", &path,
+                                           &|n| (self.notify_handler)(n.into())));
+                } else {
+                    try!(utils::remove_file("file", &path));
+                }
+            }
+            result.push((path, size));
+        }
+        Ok(result)
+    }
+
     pub fn find_default(&self) -> Result<Option<Toolchain>> {
         let opt_name = try!(self.settings_file.with(|s| Ok(s.default_toolchain.clone())));
 
@@ -248,9 +386,30 @@ impl Cfg {
             return Ok(Some((toolchain, OverrideReason::OverrideDB(reason_path))));
         }
 
+        // A Cargo.toml without a usable rust-version is not an error
+        // here, since most packages don't declare one
+        let manifest_overrides = try!(self.settings_file.with(|s| Ok(s.manifest_overrides)));
+        if manifest_overrides {
+            if let Ok((name, manifest)) = self.find_manifest_toolchain(path) {
+                if let Ok(toolchain) = self.verify_toolchain(&name) {
+                    return Ok(Some((toolchain, OverrideReason::CargoManifest(manifest))));
+                }
+            }
+        }
+
         Ok(None)
     }
 
+    /// The toolchain for the `rust-version` declared in the nearest
+    /// Cargo.toml, and the path of that Cargo.toml
+    pub fn find_manifest_toolchain(&self, path: &Path) -> Result<(String, PathBuf)> {
+        let manifest = try!(rust_version::find_cargo_manifest(path)
+                            .ok_or_else(|| ErrorKind::NoCargoManifest(path.to_owned())));
+        let channel = try!(rust_version::declared_channel(&manifest));
+        let toolchain = try!(self.get_toolchain(&channel, false));
+        Ok((toolchain.name().to_owned(), manifest))
+    }
+
     pub fn find_override_toolchain_or_default
         (&self,
          path: &Path)
@@ -262,17 +421,30 @@ impl Cfg {
         })
     }
 
+    /// Lists the user's toolchains together with those in the system
+    /// store
     pub fn list_toolchains(&self) -> Result<Vec<String>> {
-        if utils::is_directory(&self.toolchains_dir) {
-            let mut toolchains: Vec<_> = try!(utils::read_dir("toolchains", &self.toolchains_dir))
-                                         .filter_map(io::Result::ok)
-                                         .filter(|e| e.file_type().map(|f| !f.is_file()).unwrap_or(false))
-                                         .filter_map(|e| e.file_name().into_string().ok())
-                                         .collect();
+        let mut toolchains = try!(Self::list_toolchains_in(&self.toolchains_dir));
+        if let Some(ref system_dir) = self.system_toolchains_dir {
+            for name in try!(Self::list_toolchains_in(system_dir)) {
+                if !toolchains.contains(&name) {
+                    toolchains.push(name);
+                }
+            }
+        }
+
+        utils::toolchain_sort(&mut toolchains);
 
-            utils::toolchain_sort(&mut toolchains);
+        Ok(toolchains)
+    }
 
-            Ok(toolchains)
+    fn list_toolchains_in(dir: &Path) -> Result<Vec<String>> {
+        if utils::is_directory(dir) {
+            Ok(try!(utils::read_dir("toolchains", dir))
+                   .filter_map(io::Result::ok)
+                   .filter(|e| e.file_type().map(|f| !f.is_file()).unwrap_or(false))
+                   .filter_map(|e| e.file_name().into_string().ok())
+                   .collect())
         } else {
             Ok(Vec::new())
         }
@@ -285,9 +457,21 @@ impl Cfg {
         let toolchains = toolchains.into_iter();
         let toolchains = toolchains.map(|n| (n.clone(), self.get_toolchain(&n, true)));
 
-        // Filter out toolchains that don't track a release channel
+        // Filter out toolchains that don't track a release channel, and
+        // those in the system store, which only its owner can update
         let toolchains = toolchains.filter(|&(_, ref t)| {
-            t.as_ref().map(|t| t.is_tracking()).unwrap_or(false)
+            t.as_ref().map(|t| t.is_tracking() && !t.is_system()).unwrap_or(false)
+        });
+
+        // Pinned toolchains are only updated when named
+        let pinned = try!(self.settings_file.with(|s| Ok(s.pinned.clone())));
+        let toolchains = toolchains.filter(|&(ref n, _)| {
+            if pinned.contains(n) {
+                (self.notify_handler)(Notification::SkippingPinnedToolchain(n));
+                false
+            } else {
+                true
+            }
         });
 
         // Update toolchains and collect the results
@@ -324,53 +508,231 @@ impl Cfg {
             .and_then(|r| r.ok_or("no default toolchain configured".into()))
     }
 
-    pub fn create_command_for_dir(&self, path: &Path, binary: &str) -> Result<Command> {
-        let (ref toolchain, _) = try!(self.toolchain_for_dir(path));
+    /// Like `toolchain_for_dir`, but records the result in the proxy
+    /// cache when running the toolchain needs nothing else from the
+    /// settings, so that `cached_command_for_dir` can find it next time
+    pub fn toolchain_for_dir_cached(&self, path: &Path) -> Result<(Toolchain, Option<OverrideReason>)> {
+        let (toolchain, reason) = try!(self.toolchain_for_dir(path));
+        let recordable = match reason {
+            None | Some(OverrideReason::OverrideDB(_)) => true,
+            // RUSTUP_TOOLCHAIN is cheap to resolve, and a rust-version
+            // can be edited in Cargo.toml at any time
+            Some(OverrideReason::Environment) |
+            Some(OverrideReason::CargoManifest(_)) => false,
+        };
+        let override_path = match reason {
+            Some(OverrideReason::OverrideDB(ref p)) => Some(p.clone()),
+            _ => None,
+        };
+        if recordable && try!(self.is_cacheable(&toolchain, override_path.as_ref().map(|p| &**p))) {
+            let cache = ProxyCache::new(self.multirust_dir.join(PROXY_CACHE_FILE),
+                                        self.settings_file.path());
+            cache.insert(path, &CachedToolchain {
+                name: toolchain.name().to_owned(),
+                path: toolchain.path().to_owned(),
+                override_path: override_path,
+            });
+        }
+        Ok((toolchain, reason))
+    }
+
+    /// Whether a proxy can run `toolchain` from the proxy cache alone:
+    /// nothing in the settings changes the command it runs, and the
+    /// toolchain is one of the user's own dist toolchains. A custom
+    /// toolchain's overlay and the user's copy of a system toolchain
+    /// can appear without the settings changing.
+    fn is_cacheable(&self, toolchain: &Toolchain, override_path: Option<&Path>) -> Result<bool> {
+        if toolchain.is_custom() || toolchain.is_system() {
+            return Ok(false);
+        }
+        self.settings_file.with(|s| {
+            let override_settings = override_path.map(|p| {
+                !s.override_requirements(p, self.notify_handler.as_ref()).is_empty() ||
+                    !s.override_env(p, self.notify_handler.as_ref()).is_empty()
+            }).unwrap_or(false);
+            Ok(!override_settings &&
+               !s.manifest_overrides &&
+               s.tool_overrides.is_empty() &&
+               s.toolchain_env(toolchain.name()).is_empty() &&
+               s.telemetry == TelemetryMode::Off)
+        })
+    }
 
-        if let Some(cmd) = try!(self.maybe_do_cargo_fallback(toolchain, binary)) {
-            Ok(cmd)
+    /// Creates the command running `binary` for `path` from the proxy
+    /// cache, without building a `Cfg`. `None` if the cache has nothing
+    /// for `path`, or if the toolchain lacks `binary` or has a lock to
+    /// check, all of which need the `Cfg`.
+    pub fn cached_command_for_dir(multirust_dir: &Path, path: &Path, binary: &str) -> Option<Command> {
+        if env::var("RUSTUP_TOOLCHAIN").ok().and_then(utils::if_not_empty).is_some() {
+            return None;
+        }
+        let cache = ProxyCache::new(multirust_dir.join(PROXY_CACHE_FILE),
+                                    &multirust_dir.join("settings.toml"));
+        let toolchain = match cache.get(path) {
+            Some(t) => t,
+            None => return None,
+        };
+        if let Some(ref override_path) = toolchain.override_path {
+            if utils::is_file(override_path.join(LOCK_FILE)) {
+                return None;
+            }
+        }
+        let binary_file = toolchain.path.join("bin")
+            .join(format!("{}{}", tool_name(binary), EXE_SUFFIX));
+        if !utils::is_file(&binary_file) {
+            return None;
+        }
+
+        let mut cmd = Command::new(binary_file);
+        for (var, value) in base_env_vars(&toolchain.name, &toolchain.path, multirust_dir) {
+            cmd.env(var, value);
+        }
+        env_var::inc("RUST_RECURSION_COUNT", &mut cmd);
+        Some(cmd)
+    }
+
+    /// Creates the command running `binary` from the toolchain for
+    /// `path`. `args` are the arguments it will be run with, which
+    /// aren't added here.
+    pub fn create_command_for_dir<S: AsRef<OsStr>>(&self, path: &Path, binary: &str,
+                                                   args: &[S]) -> Result<Command> {
+        let (ref toolchain, ref reason) = try!(self.toolchain_for_dir_cached(path));
+
+        if let Some(OverrideReason::OverrideDB(ref override_path)) = *reason {
+            let install = try!(self.settings_file.with(|s| Ok(s.auto_install)));
+            try!(self.check_override_requirements(toolchain, override_path, install));
+            try!(self.check_toolchain_lock(toolchain, override_path));
+        }
+
+        let mut cmd = if let Some(cmd) = try!(self.maybe_do_fallback(toolchain, binary)) {
+            cmd
         } else {
-            toolchain.create_command(binary)
+            try!(self.check_component_for_binary(toolchain, binary));
+            let mut cmd = try!(toolchain.create_command(binary));
+            toolchain.set_overlay_sysroot(&mut cmd, binary, args);
+            cmd
+        };
+
+        // Variables set for the override take priority over those set
+        // for the toolchain
+        if let Some(OverrideReason::OverrideDB(ref override_path)) = *reason {
+            let vars = try!(self.settings_file.with(|s| {
+                Ok(s.override_env(override_path, self.notify_handler.as_ref()))
+            }));
+            for (var, value) in vars {
+                cmd.env(var, value);
+            }
         }
+
+        Ok(cmd)
     }
 
-    pub fn create_command_for_toolchain(&self, toolchain: &str, binary: &str) -> Result<Command> {
+    /// Creates the command running `binary` from `toolchain`, to be run
+    /// with `args`
+    pub fn create_command_for_toolchain<S: AsRef<OsStr>>(&self, toolchain: &str, binary: &str,
+                                                         args: &[S]) -> Result<Command> {
         let ref toolchain = try!(self.get_toolchain(toolchain, false));
 
-        if let Some(cmd) = try!(self.maybe_do_cargo_fallback(toolchain, binary)) {
+        if let Some(cmd) = try!(self.maybe_do_fallback(toolchain, binary)) {
             Ok(cmd)
         } else {
-            toolchain.create_command(binary)
+            try!(self.check_component_for_binary(toolchain, binary));
+            let mut cmd = try!(toolchain.create_command(binary));
+            toolchain.set_overlay_sysroot(&mut cmd, binary, args);
+            Ok(cmd)
         }
     }
 
-    // Custom toolchains don't have cargo, so here we detect that situation and
-    // try to find a different cargo.
-    fn maybe_do_cargo_fallback(&self, toolchain: &Toolchain, binary: &str) -> Result<Option<Command>> {
-        if !toolchain.is_custom() {
+    // Toolchains may lack some tools, custom toolchains often not having
+    // cargo, so here we detect that situation and try to find the tool in
+    // one of the toolchain's fallbacks.
+    fn maybe_do_fallback(&self, toolchain: &Toolchain, binary: &str) -> Result<Option<Command>> {
+        if utils::is_file(toolchain.binary_file(tool_name(binary))) {
             return Ok(None);
         }
 
-        if binary != "cargo" && binary != "cargo.exe" {
-            return Ok(None);
+        for fallback in try!(self.fallbacks(toolchain)) {
+            let fallback = try!(self.get_toolchain(&fallback, false));
+            if fallback.exists() && utils::is_file(fallback.binary_file(tool_name(binary))) {
+                let cmd = try!(fallback.create_fallback_command(binary, toolchain));
+                return Ok(Some(cmd));
+            }
         }
 
-        let cargo_path = toolchain.path().join("bin/cargo");
-        let cargo_exe_path = toolchain.path().join("bin/cargo.exe");
+        Ok(None)
+    }
 
-        if cargo_path.exists() || cargo_exe_path.exists() {
-            return Ok(None);
+    /// If `toolchain` lacks `binary` but one of its components ships
+    /// it, installs the component when auto-install is on and otherwise
+    /// fails with the command to install it
+    fn check_component_for_binary(&self, toolchain: &Toolchain, binary: &str) -> Result<()> {
+        let tool = tool_name(binary);
+        if utils::is_file(toolchain.binary_file(tool)) {
+            return Ok(());
         }
+        let component = match try!(toolchain.component_for_binary(tool)) {
+            Some(c) => c,
+            None => return Ok(()),
+        };
 
-        for fallback in &["nightly", "beta", "stable"] {
-            let fallback = try!(self.get_toolchain(fallback, false));
-            if fallback.exists() {
-                let cmd = try!(fallback.create_fallback_command("cargo", toolchain));
-                return Ok(Some(cmd));
-            }
+        let install = try!(self.settings_file.with(|s| Ok(s.auto_install)));
+        if !install {
+            return Err(ErrorKind::BinaryComponentNotInstalled(toolchain.name().to_owned(),
+                                                              format!("{}{}", tool, EXE_SUFFIX),
+                                                              component.pkg).into());
         }
 
-        Ok(None)
+        (self.notify_handler)(Notification::InstallingComponentForBinary(&component.pkg, tool));
+        toolchain.add_component(component)
+    }
+
+    /// Whether `rustup update` skips `toolchain` unless it's named
+    pub fn is_pinned(&self, toolchain: &str) -> Result<bool> {
+        self.settings_file.with(|s| Ok(s.pinned.iter().any(|p| p == toolchain)))
+    }
+
+    pub fn set_pinned(&self, toolchain: &str, pinned: bool) -> Result<()> {
+        try!(self.settings_file.with_mut(|s| {
+            s.pinned.retain(|p| p != toolchain);
+            if pinned {
+                s.pinned.push(toolchain.to_owned());
+                s.pinned.sort();
+            }
+            Ok(())
+        }));
+        (self.notify_handler)(Notification::SetPinned(toolchain, pinned));
+        Ok(())
+    }
+
+    /// The toolchains searched, in order, for a binary `toolchain`
+    /// doesn't have. Unless configured, custom toolchains fall back to
+    /// the release channels and others don't fall back at all.
+    pub fn fallbacks(&self, toolchain: &Toolchain) -> Result<Vec<String>> {
+        let configured = try!(self.settings_file.with(|s| {
+            Ok(s.fallbacks.get(toolchain.name()).cloned())
+        }));
+        Ok(configured.unwrap_or_else(|| {
+            if toolchain.is_custom() {
+                DEFAULT_FALLBACKS.iter().map(|s| s.to_string()).collect()
+            } else {
+                Vec::new()
+            }
+        }))
+    }
+
+    /// Sets the fallbacks of `toolchain`, or restores the default if
+    /// `fallbacks` is `None`
+    pub fn set_fallbacks(&self, toolchain: &str, fallbacks: Option<Vec<String>>) -> Result<()> {
+        try!(self.settings_file.with_mut(|s| {
+            match fallbacks {
+                Some(ref f) => { s.fallbacks.insert(toolchain.to_owned(), f.clone()); }
+                None => { s.fallbacks.remove(toolchain); }
+            }
+            Ok(())
+        }));
+        (self.notify_handler)(Notification::SetFallbacks(toolchain,
+                                                         fallbacks.as_ref().map(|f| &**f)));
+        Ok(())
     }
 
     pub fn doc_path_for_dir(&self, path: &Path, relative: &str) -> Result<PathBuf> {
@@ -396,6 +758,371 @@ impl Cfg {
         })).unwrap_or_else(dist::TargetTriple::from_build))
     }
 
+    /// Checks that the targets and components required by the override
+    /// set for `override_path` are installed in `toolchain`, installing
+    /// any that are missing if `install` is set
+    pub fn check_override_requirements(&self, toolchain: &Toolchain, override_path: &Path,
+                                       install: bool) -> Result<()> {
+        let requirements = try!(self.settings_file.with(|s| {
+            Ok(s.override_requirements(override_path, self.notify_handler.as_ref()))
+        }));
+        if requirements.is_empty() {
+            return Ok(());
+        }
+
+        let host = try!(toolchain.desc()
+                        .chain_err(|| ErrorKind::ComponentsUnsupported(toolchain.name().to_string())))
+            .target;
+        // This runs for every proxied command, so it looks at what's
+        // installed rather than reading the channel manifest. A target
+        // without a directory in `lib/rustlib` is surely missing, and
+        // otherwise requirements are checked against the components
+        // the dist config records.
+        let rustlib = toolchain.path().join("lib").join("rustlib");
+        let installed = try!(toolchain.installed_components());
+        let is_installed = |pkg: &str, target: &dist::TargetTriple| {
+            installed.iter().any(|c| {
+                c.pkg == pkg && c.target.as_ref().map(|t| t == target).unwrap_or(true)
+            })
+        };
+
+        let missing_targets: Vec<&String> = requirements.targets.iter().filter(|t| {
+            !utils::is_directory(rustlib.join(t)) ||
+                !is_installed("rust-std", &dist::TargetTriple::from_str(t))
+        }).collect();
+        let missing_components: Vec<&String> = requirements.components.iter().filter(|c| {
+            !is_installed(c, &host)
+        }).collect();
+
+        if missing_targets.is_empty() && missing_components.is_empty() {
+            return Ok(());
+        }
+
+        if !install {
+            let mut cmds = Vec::new();
+            if !missing_targets.is_empty() {
+                let names: Vec<&str> = missing_targets.iter().map(|s| &***s).collect();
+                cmds.push(format!("rustup target add --toolchain {} {}",
+                                  toolchain.name(), names.join(" ")));
+            }
+            if !missing_components.is_empty() {
+                let names: Vec<&str> = missing_components.iter().map(|s| &***s).collect();
+                cmds.push(format!("rustup component add --toolchain {} {}",
+                                  toolchain.name(), names.join(" ")));
+            }
+            return Err(ErrorKind::OverrideRequirementsNotInstalled(override_path.to_owned(),
+                                                                    cmds.join(" && ")).into());
+        }
+
+        (self.notify_handler)(Notification::InstallingOverrideRequirements(override_path));
+        for target in missing_targets {
+            try!(toolchain.add_component(Component {
+                pkg: "rust-std".to_string(),
+                target: Some(dist::TargetTriple::from_str(target)),
+            }));
+        }
+        for component in missing_components {
+            try!(toolchain.add_component(Component {
+                pkg: component.to_string(),
+                target: Some(host.clone()),
+            }));
+        }
+
+        Ok(())
+    }
+
+    /// The lock pinning the release `toolchain` is installed at, if it
+    /// was installed from dist
+    fn toolchain_lock(&self, toolchain: &Toolchain, strict: bool) -> Result<Option<ToolchainLock>> {
+        if let Some(manifest) = try!(toolchain.installed_manifest()) {
+            let desc = try!(toolchain.desc());
+            Ok(Some(try!(ToolchainLock::from_manifest(&desc, &manifest, strict))))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Checks `toolchain` against the lock file next to the override
+    /// set for `override_path`, if there is one. A mismatch is an error
+    /// for strict locks and a warning otherwise.
+    pub fn check_toolchain_lock(&self, toolchain: &Toolchain, override_path: &Path) -> Result<()> {
+        let ref lock_path = override_path.join(LOCK_FILE);
+        if !utils::is_file(lock_path) {
+            return Ok(());
+        }
+        let lock = try!(ToolchainLock::parse(&try!(utils::read_file("toolchain lock", lock_path))));
+        let installed = try!(self.toolchain_lock(toolchain, lock.strict));
+        let host = try!(toolchain.desc()).target;
+        if installed.as_ref().map(|i| lock.matches(i, &host)).unwrap_or(false) {
+            return Ok(());
+        }
+
+        let installed_date = installed.map(|i| i.date).unwrap_or_else(|| "(unknown)".to_owned());
+        if lock.strict {
+            Err(ErrorKind::ToolchainLockMismatch(lock_path.clone(), lock.toolchain,
+                                                 lock.date, installed_date).into())
+        } else {
+            (self.notify_handler)(Notification::ToolchainLockMismatch(lock_path, &lock.toolchain,
+                                                                      &lock.date, &installed_date));
+            Ok(())
+        }
+    }
+
+    /// Writes the lock file for the directory override that applies to
+    /// `path`, pinning the release its toolchain is installed at
+    pub fn lock_override(&self, path: &Path, strict: bool) -> Result<()> {
+        let found = try!(self.settings_file.with(|s| {
+            Ok(s.find_override(path, self.notify_handler.as_ref()))
+        }));
+        let (name, override_path) = try!(found.ok_or_else(|| ErrorKind::NoOverrideToLock(path.to_owned())));
+        let toolchain = try!(self.get_toolchain(&name, false));
+        let lock = try!(try!(self.toolchain_lock(&toolchain, strict))
+                        .ok_or_else(|| ErrorKind::CannotLockToolchain(toolchain.name().to_owned())));
+
+        let ref lock_path = override_path.join(LOCK_FILE);
+        try!(utils::write_file_atomic("toolchain lock", lock_path, &lock.stringify()));
+        (self.notify_handler)(Notification::WroteToolchainLock(lock_path));
+        Ok(())
+    }
+
+    /// The toolchain set to always provide `tool`, overriding the
+    /// directory override and the default. `RUSTUP_TOOLCHAIN` overrides
+    /// it in turn, so there's none while that is set
+    pub fn tool_override(&self, tool: &str) -> Result<Option<String>> {
+        if self.env_override.is_some() {
+            return Ok(None);
+        }
+        let tool = tool_name(tool);
+        self.settings_file.with(|s| Ok(s.tool_overrides.get(tool).cloned()))
+    }
+
+    /// Sets the toolchain providing `tool`, or removes the tool
+    /// override if `toolchain` is `None`
+    pub fn set_tool_override(&self, tool: &str, toolchain: Option<&str>) -> Result<()> {
+        let tool = tool_name(tool);
+        try!(self.settings_file.with_mut(|s| {
+            match toolchain {
+                Some(t) => { s.tool_overrides.insert(tool.to_owned(), t.to_owned()); }
+                None => { s.tool_overrides.remove(tool); }
+            }
+            Ok(())
+        }));
+        (self.notify_handler)(Notification::SetToolOverride(tool, toolchain));
+        Ok(())
+    }
+
+    /// Creates proxies in `CARGO_HOME/bin` for the executables the
+    /// installed components ship, and removes the proxies rustup created
+    /// for executables no toolchain ships any more. Files rustup didn't
+    /// create, such as those put there by `cargo install`, are left
+    /// alone.
+    pub fn update_proxies(&self) -> Result<()> {
+        let bin_dir = try!(utils::cargo_home()).join("bin");
+        let ref rustup_path = bin_dir.join(format!("rustup{}", EXE_SUFFIX));
+        // There's nothing to link to unless rustup is installed
+        if !utils::is_file(rustup_path) {
+            return Ok(());
+        }
+
+        let mut shipped = Vec::new();
+        for name in try!(self.list_toolchains()) {
+            let toolchain = try!(self.get_toolchain(&name, false));
+            for binary in try!(toolchain.installed_binaries()) {
+                if binary != "rustup" && !shipped.contains(&binary) {
+                    shipped.push(binary);
+                }
+            }
+        }
+
+        let proxy_path = |name: &str| bin_dir.join(format!("{}{}", name, EXE_SUFFIX));
+        let old_proxies = try!(self.settings_file.with(|s| Ok(s.proxies.clone())));
+        let mut proxies = Vec::new();
+
+        for name in &old_proxies {
+            let ref path = proxy_path(name);
+            // Something else has replaced the proxy, so it's no longer
+            // rustup's to remove
+            if !is_proxy(path, rustup_path) {
+                continue;
+            }
+            if shipped.contains(name) {
+                proxies.push(name.clone());
+            } else {
+                try!(utils::remove_file("proxy", path));
+                (self.notify_handler)(Notification::RemovedProxy(name));
+            }
+        }
+
+        for name in &shipped {
+            let ref path = proxy_path(name);
+            if proxies.contains(name) {
+                continue;
+            }
+            if utils::path_exists(path) {
+                // The proxies created on installation are already there
+                if !is_proxy(path, rustup_path) {
+                    (self.notify_handler)(Notification::ProxyShadowed(path));
+                }
+                continue;
+            }
+            try!(utils::hard_or_symlink_file(rustup_path, path));
+            (self.notify_handler)(Notification::CreatedProxy(name));
+            proxies.push(name.clone());
+        }
+
+        proxies.sort();
+        if proxies != old_proxies {
+            try!(self.settings_file.with_mut(|s| {
+                s.proxies = proxies;
+                Ok(())
+            }));
+        }
+
+        Ok(())
+    }
+
+    /// Captures the settings and the toolchains installed from dist
+    pub fn export_state(&self) -> Result<State> {
+        let settings = try!(self.settings_file.with(|s| Ok(s.clone())));
+        let mut toolchains = Vec::new();
+        for name in try!(self.list_toolchains()) {
+            let toolchain = try!(self.get_toolchain(&name, false));
+            if let Some((date, dist_config)) = try!(toolchain.dist_state()) {
+                toolchains.push(ToolchainState {
+                    name: name,
+                    date: date,
+                    dist_config: dist_config,
+                });
+            } else {
+                (self.notify_handler)(Notification::NotExportingToolchain(&name));
+            }
+        }
+
+        Ok(State::new(settings, toolchains))
+    }
+
+    /// Installs the toolchains of an exported state, then takes the
+    /// exported settings that aren't specific to the other machine
+    pub fn import_state(&self, state: State) -> Result<()> {
+        for t in &state.toolchains {
+            let toolchain = try!(self.get_toolchain(&t.name, true));
+            try!(toolchain.install_from_dist_at_date(Some(t.dist_config.profile), &t.date));
+
+            let installed: Vec<Component> = try!(toolchain.list_components()).into_iter()
+                .filter(|c| c.installed)
+                .map(|c| c.component)
+                .collect();
+            for component in &t.dist_config.components {
+                if !installed.contains(component) {
+                    try!(toolchain.add_component(component.clone()));
+                }
+            }
+        }
+
+        // Custom toolchains aren't exported, so only some of the
+        // toolchains the settings mention may be here. Settings that use
+        // one that isn't are left out; those using dist toolchains are
+        // kept, since those can be installed on demand.
+        let installed = try!(self.list_toolchains());
+        let mut skipped = Vec::new();
+        let available = |name: &str, skipped: &mut Vec<String>| {
+            let resolved = self.resolve_toolchain(name).unwrap_or_else(|_| name.to_owned());
+            if installed.contains(&resolved) || dist::ToolchainDesc::from_str(&resolved).is_ok() {
+                true
+            } else {
+                if !skipped.contains(&resolved) {
+                    skipped.push(resolved);
+                }
+                false
+            }
+        };
+
+        let imported = state.settings;
+        let default_toolchain = match imported.default_toolchain {
+            Some(t) => if available(&t, &mut skipped) { Some(t) } else { None },
+            None => None,
+        };
+        let overrides: BTreeMap<String, String> = imported.overrides.into_iter()
+            .filter(|&(_, ref t)| available(t, &mut skipped))
+            .collect();
+        // Requirements and variables go with the overrides they're for
+        let override_requirements = imported.override_requirements.into_iter()
+            .filter(|&(ref p, _)| overrides.contains_key(p))
+            .collect();
+        let override_env = imported.override_env.into_iter()
+            .filter(|&(ref p, _)| overrides.contains_key(p))
+            .collect();
+        let tool_overrides = imported.tool_overrides.into_iter()
+            .filter(|&(_, ref t)| available(t, &mut skipped))
+            .collect();
+        let toolchain_env = imported.toolchain_env.into_iter()
+            .filter(|&(ref t, _)| available(t, &mut skipped))
+            .collect();
+        let pinned = imported.pinned.into_iter()
+            .filter(|t| available(t, &mut skipped))
+            .collect();
+        let mut fallbacks = BTreeMap::new();
+        for (t, f) in imported.fallbacks {
+            if available(&t, &mut skipped) {
+                let f = f.into_iter().filter(|f| available(f, &mut skipped)).collect();
+                fallbacks.insert(t, f);
+            }
+        }
+
+        for name in &skipped {
+            (self.notify_handler)(Notification::NotImportingToolchainSettings(name));
+        }
+
+        self.settings_file.with_mut(|s| {
+            // Only the settings choosing and configuring toolchains move
+            // between machines. Those describing this machine, such as
+            // its host triple, keys, download setup, proxies and linked
+            // toolchains, are kept.
+            s.default_toolchain = default_toolchain;
+            s.profile = imported.profile;
+            s.overrides = overrides;
+            s.override_requirements = override_requirements;
+            s.tool_overrides = tool_overrides;
+            s.toolchain_env = toolchain_env;
+            s.override_env = override_env;
+            s.pinned = pinned;
+            s.fallbacks = fallbacks;
+            Ok(())
+        })
+    }
+
+    pub fn set_auto_install(&self, auto_install: bool) -> Result<()> {
+        self.settings_file.with_mut(|s| {
+            s.auto_install = auto_install;
+            Ok(())
+        })
+    }
+
+    pub fn set_manifest_overrides(&self, enabled: bool) -> Result<()> {
+        self.settings_file.with_mut(|s| {
+            s.manifest_overrides = enabled;
+            Ok(())
+        })
+    }
+
+    pub fn set_profile(&self, profile: &str) -> Result<()> {
+        let profile = try!(dist::Profile::from_str(profile));
+        try!(self.settings_file.with_mut(|s| {
+            s.profile = Some(profile.name().to_owned());
+            Ok(())
+        }));
+        (self.notify_handler)(Notification::SetProfile(profile.name()));
+        Ok(())
+    }
+
+    pub fn get_profile(&self) -> Result<dist::Profile> {
+        let profile = try!(self.settings_file.with(|s| Ok(s.profile.clone())));
+        match profile {
+            Some(p) => Ok(try!(dist::Profile::from_str(&p))),
+            None => Ok(dist::Profile::default()),
+        }
+    }
+
     pub fn resolve_toolchain(&self, name: &str) -> Result<String> {
         if let Ok(desc) = dist::PartialToolchainDesc::from_str(name) {
             let host = try!(self.get_default_host_triple());
@@ -450,3 +1177,28 @@ impl Cfg {
         Ok(t)
     }
 }
+
+/// When `path`, or anything in it if it's a directory, was last
+/// modified
+fn last_modified(path: &Path) -> Option<SystemTime> {
+    let mut newest = match fs::symlink_metadata(path).and_then(|m| m.modified()) {
+        Ok(t) => t,
+        Err(_) => return None,
+    };
+    if utils::is_directory(path) {
+        for entry in fs::read_dir(path).into_iter().flat_map(|d| d).filter_map(io::Result::ok) {
+            match last_modified(&entry.path()) {
+                Some(t) => newest = cmp::max(newest, t),
+                None => return None,
+            }
+        }
+    }
+    Some(newest)
+}
+
+/// Whether `path` is a link to the rustup executable. Proxies are
+/// hard links or symlinks, so this checks the file's identity rather
+/// than reading both files
+fn is_proxy(path: &Path, rustup_path: &Path) -> bool {
+    utils::is_same_file(path, rustup_path)
+}
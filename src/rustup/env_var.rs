@@ -1,4 +1,5 @@
 use std::env;
+use std::ffi::OsString;
 use std::path::PathBuf;
 use std::process::Command;
 
@@ -20,6 +21,13 @@ pub fn append_path(name: &str, value: Vec<PathBuf>, cmd: &mut Command) {
 }
 
 pub fn prepend_path(name: &str, value: Vec<PathBuf>, cmd: &mut Command) {
+    if let Some(new_value) = prepended_path(name, value) {
+        cmd.env(name, new_value);
+    }
+}
+
+/// The value of the path variable `name` with `value` prepended
+pub fn prepended_path(name: &str, value: Vec<PathBuf>) -> Option<OsString> {
     let old_value = env::var_os(name);
     let mut parts: Vec<PathBuf>;
     if let Some(ref v) = old_value {
@@ -29,9 +37,7 @@ pub fn prepend_path(name: &str, value: Vec<PathBuf>, cmd: &mut Command) {
         parts = value;
     }
 
-    if let Ok(new_value) = env::join_paths(parts) {
-        cmd.env(name, new_value);
-    }
+    env::join_paths(parts).ok()
 }
 
 pub fn inc(name: &str, cmd: &mut Command) {
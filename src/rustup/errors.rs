@@ -2,6 +2,7 @@ use rustup_dist::{self, temp};
 use rustup_utils;
 use rustup_dist::manifest::Component;
 use toml;
+use std::path::PathBuf;
 
 error_chain! {
     links {
@@ -22,6 +23,10 @@ error_chain! {
             description("toolchain is not installed")
             display("toolchain '{}' is not installed", t)
         }
+        SystemToolchainReadOnly(t: String, path: PathBuf) {
+            description("toolchain is in the read-only system store")
+            display("toolchain '{}' is in the read-only system store at '{}'", t, path.display())
+        }
         OverrideToolchainNotInstalled(t: String) {
             description("override toolchain is not installed")
             display("override toolchain '{}' is not installed", t)
@@ -30,12 +35,34 @@ error_chain! {
             description("toolchain does not contain binary")
             display("toolchain '{}' does not have the binary `{}`", t, bin)
         }
+        BinaryProviderUnknown(t: String, bin: String) {
+            description("toolchain does not contain binary")
+            display("toolchain '{}' does not have the binary `{}`, and the component that provides it is unknown", t, bin)
+        }
+        BinaryComponentNotInstalled(t: String, bin: String, c: String) {
+            description("toolchain does not have the component that provides the binary")
+            display("toolchain '{}' does not have the binary `{}`; it is provided by the '{}' component, install it with `rustup component add --toolchain {} {}`",
+                    t, bin, c, t, c)
+        }
         NeedMetadataUpgrade {
             description("rustup's metadata is out of date. run `rustup self upgrade-data`")
         }
         UpgradeIoError {
             description("I/O error during upgrade")
         }
+        MetadataTooNew(v: String) {
+            description("rustup's metadata is newer than this rustup supports")
+            display("rustup's metadata is version '{}', newer than this rustup supports. run `rustup self update`", v)
+        }
+        MigrationFailed(from: String, to: String) {
+            description("failed to upgrade rustup's metadata")
+            display("failed to upgrade rustup's metadata from version '{}' to '{}'; it has been restored", from, to)
+        }
+        MigrationRestoreFailed(from: String, to: String, backup: PathBuf, restore_error: String) {
+            description("failed to upgrade rustup's metadata, and failed to restore it")
+            display("failed to upgrade rustup's metadata from version '{}' to '{}', and restoring it failed too ({}); the backup is in '{}'",
+                    from, to, restore_error, backup.display())
+        }
         BadInstallerType(s: String) {
             description("invalid extension for installer")
             display("invalid extension for installer: '{}'", s)
@@ -53,9 +80,89 @@ error_chain! {
             display("component {} is required for toolchain '{}' and cannot be re-added",
                     c.description(), t)
         }
+        ParsingLockFile(e: Vec<toml::ParserError>) {
+            description("error parsing toolchain lock file")
+        }
+        ToolchainLockMismatch(p: PathBuf, t: String, date: String, installed: String) {
+            description("installed toolchain does not match the lock file")
+            display("'{}' locks '{}' to the release of {}, but the installed release is from {}; \
+                     install the locked release or run `rustup override lock` to update the lock",
+                    p.display(), t, date, installed)
+        }
+        NoOverrideToLock(p: PathBuf) {
+            description("no directory override to lock")
+            display("no directory override applies to '{}'", p.display())
+        }
+        CannotLockToolchain(t: String) {
+            description("toolchain cannot be locked")
+            display("toolchain '{}' was not installed from a release channel and can't be locked", t)
+        }
+        OverrideRequirementsNotInstalled(p: PathBuf, cmd: String) {
+            description("override requires targets or components that are not installed")
+            display("the directory override for '{}' requires targets or components that are not installed; install them with `{}`",
+                    p.display(), cmd)
+        }
+        UnknownConfigKey(k: String) {
+            description("unknown configuration key")
+            display("unknown configuration key: '{}'", k)
+        }
+        InvalidGpgKey(p: PathBuf) {
+            description("invalid GPG key")
+            display("'{}' is not an ASCII-armored PGP public key", p.display())
+        }
+        InvalidConfigValue(k: String, v: String) {
+            description("invalid configuration value")
+            display("invalid value for '{}': '{}'", k, v)
+        }
         ParsingSettings(e: Vec<toml::ParserError>) {
             description("error parsing settings")
         }
+        InvalidCustomToolchain(p: PathBuf) {
+            description("directory is not a toolchain")
+            display("'{}' is not a toolchain: it has no `bin/rustc`", p.display())
+        }
+        RunningCustomRustc(p: PathBuf) {
+            description("failed to run the toolchain's rustc")
+            display("failed to run '{}' to find the toolchain's version", p.display())
+        }
+        OwnStdTarget(t: String, target: String) {
+            description("custom toolchain already has the standard library")
+            display("toolchain '{}' was built with its own standard library for '{}'", t, target)
+        }
+        UnknownCustomCommit(t: String) {
+            description("custom toolchain doesn't report its commit")
+            display("the rustc of toolchain '{}' doesn't report the commit it was built from, \
+                     so no nightly can be matched to it", t)
+        }
+        NoMatchingNightly(t: String, c: String) {
+            description("no nightly matches the custom toolchain")
+            display("no nightly was built from commit '{}' of toolchain '{}'", c, t)
+        }
+        ParsingCargoManifest(p: PathBuf, e: Vec<toml::ParserError>) {
+            description("error parsing Cargo.toml")
+            display("error parsing '{}'", p.display())
+        }
+        NoCargoManifest(p: PathBuf) {
+            description("no Cargo.toml found")
+            display("no Cargo.toml found in '{}' or its parent directories", p.display())
+        }
+        NoRustVersion(p: PathBuf) {
+            description("Cargo.toml does not declare a rust-version")
+            display("'{}' does not declare a `rust-version` in [package] or [package.metadata]",
+                    p.display())
+        }
+        InvalidRustVersion(p: PathBuf, v: String) {
+            description("invalid rust-version")
+            display("invalid rust-version '{}' in '{}', expected a version such as '1.31' or '1.31.1'",
+                    v, p.display())
+        }
+        ParsingState(e: Vec<toml::ParserError>) {
+            description("error parsing exported rustup state")
+        }
+        UnknownStateVersion(v: String) {
+            description("unknown exported state version")
+            display("unknown exported state version: '{}'", v)
+        }
         RemovingRequiredComponent(t: String, c: Component) {
             description("required component cannot be removed")
             display("component {} is required for toolchain '{}' and cannot be removed",
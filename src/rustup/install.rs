@@ -16,7 +16,7 @@ pub enum InstallMethod<'a> {
     Copy(&'a Path),
     Link(&'a Path),
     Installer(&'a Path, &'a temp::Cfg),
-    Dist(&'a dist::ToolchainDesc, Option<&'a Path>, DownloadCfg<'a>),
+    Dist(&'a dist::ToolchainDesc, Option<dist::Profile>, Option<&'a Path>, DownloadCfg<'a>),
 }
 
 impl<'a> InstallMethod<'a> {
@@ -24,7 +24,7 @@ impl<'a> InstallMethod<'a> {
         if path.exists() {
             // Don't uninstall first for Dist method
             match self {
-                InstallMethod::Dist(_, _, _) |
+                InstallMethod::Dist(_, _, _, _) |
                 InstallMethod::Installer(_, _) => {}
                 _ => {
                     try!(uninstall(path, notify_handler));
@@ -45,19 +45,20 @@ impl<'a> InstallMethod<'a> {
                 try!(InstallMethod::tar_gz(src, path, &temp_cfg, notify_handler));
                 Ok(true)
             }
-            InstallMethod::Dist(toolchain, update_hash, dl_cfg) => {
+            InstallMethod::Dist(toolchain, profile, update_hash, dl_cfg) => {
                 let prefix = &InstallPrefix::from(path.to_owned());
                 let maybe_new_hash =
                     try!(dist::update_from_dist(
                         dl_cfg,
                         update_hash,
                         toolchain,
+                        profile,
                         prefix,
                         &[], &[]));
 
                 if let Some(hash) = maybe_new_hash {
                     if let Some(hash_file) = update_hash {
-                        try!(utils::write_file("update hash", hash_file, &hash));
+                        try!(utils::write_file_atomic("update hash", hash_file, &hash));
                     }
 
                     Ok(true)
@@ -25,7 +25,13 @@ mod notifications;
 mod toolchain;
 mod config;
 mod install;
+pub mod audit;
+pub mod lockfile;
+pub mod migrations;
+pub mod rust_version;
+pub mod proxy_cache;
 pub mod settings;
+pub mod state;
 pub mod telemetry;
 pub mod command;
 pub mod telemetry_analysis;
@@ -0,0 +1,136 @@
+//! The lock file kept next to a directory override, pinning the
+//! toolchain to the exact release it resolved to
+
+use errors::*;
+use toml_utils::*;
+use toml;
+use rustup_dist::dist::{TargetTriple, ToolchainDesc};
+use rustup_dist::manifest::{Manifest, PackageTargets, TargetedPackage};
+use std::collections::BTreeMap;
+
+pub const LOCK_FILE: &'static str = "rust-toolchain.lock";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ToolchainLock {
+    /// The channel, without the host, so the lock is the same whichever
+    /// host it was written on
+    pub toolchain: String,
+    /// The date of the channel manifest
+    pub date: String,
+    /// The hash of the `rust` package and of each of its components,
+    /// keyed by host and then by component name
+    pub hashes: BTreeMap<String, BTreeMap<String, String>>,
+    /// Whether to refuse to run tools when the lock doesn't match
+    pub strict: bool,
+}
+
+impl ToolchainLock {
+    pub fn from_manifest(desc: &ToolchainDesc, manifest: &Manifest, strict: bool) -> Result<Self> {
+        let mut hashes = BTreeMap::new();
+        match try!(manifest.get_package("rust")).targets {
+            PackageTargets::Wildcard(ref tpkg) => {
+                hashes.insert("*".to_owned(), try!(Self::package_hashes(manifest, tpkg)));
+            }
+            PackageTargets::Targeted(ref tpkgs) => {
+                for (target, tpkg) in tpkgs {
+                    hashes.insert(target.to_string(), try!(Self::package_hashes(manifest, tpkg)));
+                }
+            }
+        }
+
+        let toolchain = if let Some(ref date) = desc.date {
+            format!("{}-{}", desc.channel, date)
+        } else {
+            desc.channel.clone()
+        };
+
+        Ok(ToolchainLock {
+            toolchain: toolchain,
+            date: manifest.date.clone(),
+            hashes: hashes,
+            strict: strict,
+        })
+    }
+
+    fn package_hashes(manifest: &Manifest, rust_pkg: &TargetedPackage) -> Result<BTreeMap<String, String>> {
+        let mut hashes = BTreeMap::new();
+        hashes.insert("rust".to_owned(), rust_pkg.hash.clone());
+        for component in &rust_pkg.components {
+            let pkg = try!(try!(manifest.get_package(&component.pkg))
+                           .get_target(component.target.as_ref()));
+            hashes.insert(component.name(), pkg.hash.clone());
+        }
+        Ok(hashes)
+    }
+
+    fn host_hashes(&self, host: &TargetTriple) -> Option<&BTreeMap<String, String>> {
+        self.hashes.get(&host.to_string()).or_else(|| self.hashes.get("*"))
+    }
+
+    /// Whether `installed` is the release this lock pins. Only the
+    /// hashes for `host` are compared; a lock without any for it was
+    /// written from a manifest that didn't build for this host, so it
+    /// pins the channel and date alone.
+    pub fn matches(&self, installed: &ToolchainLock, host: &TargetTriple) -> bool {
+        let hashes_match = match self.host_hashes(host) {
+            Some(hashes) => installed.host_hashes(host) == Some(hashes),
+            None => true,
+        };
+        self.toolchain == installed.toolchain &&
+            self.date == installed.date &&
+            hashes_match
+    }
+
+    pub fn parse(data: &str) -> Result<Self> {
+        let mut parser = toml::Parser::new(data);
+        let value = try!(parser.parse().ok_or_else(move || ErrorKind::ParsingLockFile(parser.errors)));
+
+        Self::from_toml(value, "")
+    }
+    pub fn stringify(self) -> String {
+        toml::Value::Table(self.to_toml()).to_string()
+    }
+
+    pub fn from_toml(mut table: toml::Table, path: &str) -> Result<Self> {
+        let mut hashes = BTreeMap::new();
+        let hashes_path = format!("{}hashes.", path);
+        let mut hashes_table = try!(get_table(&mut table, "hashes", path));
+        let names: Vec<String> = hashes_table.keys().cloned().collect();
+        for host in names {
+            let host_path = format!("{}{}.", hashes_path, host);
+            let mut host_table = try!(get_table(&mut hashes_table, &host, &hashes_path));
+            let mut host_hashes = BTreeMap::new();
+            let components: Vec<String> = host_table.keys().cloned().collect();
+            for name in components {
+                let hash = try!(get_string(&mut host_table, &name, &host_path));
+                host_hashes.insert(name, hash);
+            }
+            hashes.insert(host, host_hashes);
+        }
+
+        Ok(ToolchainLock {
+            toolchain: try!(get_string(&mut table, "toolchain", path)),
+            date: try!(get_string(&mut table, "date", path)),
+            hashes: hashes,
+            strict: try!(get_opt_bool(&mut table, "strict", path)).unwrap_or(false),
+        })
+    }
+    pub fn to_toml(self) -> toml::Table {
+        let mut result = toml::Table::new();
+        result.insert("toolchain".to_owned(), toml::Value::String(self.toolchain));
+        result.insert("date".to_owned(), toml::Value::String(self.date));
+        if self.strict {
+            result.insert("strict".to_owned(), toml::Value::Boolean(true));
+        }
+        let mut hashes = toml::Table::new();
+        for (host, host_hashes) in self.hashes {
+            let mut host_table = toml::Table::new();
+            for (name, hash) in host_hashes {
+                host_table.insert(name, toml::Value::String(hash));
+            }
+            hashes.insert(host, toml::Value::Table(host_table));
+        }
+        result.insert("hashes".to_owned(), toml::Value::Table(hashes));
+        result
+    }
+}
@@ -0,0 +1,169 @@
+//! The upgrades of the metadata in `RUSTUP_HOME` from one version to
+//! the next, run in order by `rustup self upgrade-data`
+
+use errors::*;
+use notifications::*;
+use config::Cfg;
+use settings::DEFAULT_METADATA_VERSION;
+use utils;
+use rustup_utils;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub struct Migration {
+    pub from: &'static str,
+    pub to: &'static str,
+    /// The paths under `RUSTUP_HOME` the migration changes, which are
+    /// backed up before it runs and restored if it fails
+    pub backup: &'static [&'static str],
+    /// Whether the migration deletes the installed toolchains
+    pub removes_toolchains: bool,
+    pub run: fn(&Cfg) -> Result<()>,
+}
+
+/// Every migration, oldest first. A change to the layout of
+/// `RUSTUP_HOME` bumps `DEFAULT_METADATA_VERSION` and adds a step from
+/// the previous version here.
+pub static MIGRATIONS: &'static [Migration] = &[
+    // The toolchain installation format changed. The toolchains aren't
+    // backed up since they can't be used any more and are reinstalled
+    Migration {
+        from: "2",
+        to: "12",
+        backup: &["settings.toml", "update-hashes"],
+        removes_toolchains: true,
+        run: v2_to_v12,
+    },
+];
+
+/// The directory the metadata of `version` is backed up to before
+/// migrating from it
+pub fn backup_dir(cfg: &Cfg, version: &str) -> PathBuf {
+    cfg.multirust_dir.join("backup").join(version)
+}
+
+/// Whether `version` was written by a newer rustup than this one
+pub fn is_newer(version: &str) -> bool {
+    match (version.parse::<u32>(), DEFAULT_METADATA_VERSION.parse::<u32>()) {
+        (Ok(v), Ok(current)) => v > current,
+        _ => false,
+    }
+}
+
+/// The migrations that take the metadata from `version` to the current
+/// version, in order
+pub fn steps_from(version: &str) -> Result<Vec<&'static Migration>> {
+    if is_newer(version) {
+        return Err(ErrorKind::MetadataTooNew(version.to_owned()).into());
+    }
+
+    let mut steps = Vec::new();
+    let mut version = version;
+    while version != DEFAULT_METADATA_VERSION {
+        let step = try!(MIGRATIONS.iter().find(|m| m.from == version)
+                        .ok_or_else(|| ErrorKind::UnknownMetadataVersion(version.to_owned())));
+        steps.push(step);
+        version = step.to;
+    }
+    Ok(steps)
+}
+
+/// Backs up what `migration` changes, runs it and records the new
+/// version. If it fails, the backup is restored and the version is left
+/// as it was.
+pub fn run(cfg: &Cfg, migration: &Migration) -> Result<()> {
+    let notify_handler = &|n: rustup_utils::Notification| (cfg.notify_handler)(n.into());
+
+    if migration.removes_toolchains {
+        (cfg.notify_handler)(Notification::UpgradeRemovesToolchains);
+    }
+
+    let backup_dir = backup_dir(cfg, migration.from);
+    if utils::path_exists(&backup_dir) {
+        try!(utils::remove_dir("backup", &backup_dir, notify_handler));
+    }
+    try!(utils::ensure_dir_exists("backup", &backup_dir, notify_handler));
+    for name in migration.backup {
+        try!(copy(&cfg.multirust_dir.join(name), &backup_dir.join(name), notify_handler));
+    }
+    (cfg.notify_handler)(Notification::BackedUpMetadata(migration.from, &backup_dir));
+
+    let result = (migration.run)(cfg).and_then(|_| {
+        cfg.settings_file.with_mut(|s| {
+            s.version = migration.to.to_owned();
+            Ok(())
+        })
+    });
+
+    if let Err(e) = result {
+        // The restore's own error is only reported alongside the
+        // migration's, which is what went wrong first
+        if let Err(restore_error) = restore(cfg, migration, &backup_dir) {
+            return Err(e).chain_err(|| {
+                ErrorKind::MigrationRestoreFailed(migration.from.to_owned(),
+                                                  migration.to.to_owned(),
+                                                  backup_dir.clone(),
+                                                  restore_error.to_string())
+            });
+        }
+        return Err(e).chain_err(|| {
+            ErrorKind::MigrationFailed(migration.from.to_owned(), migration.to.to_owned())
+        });
+    }
+
+    // The backup isn't needed once the new version is recorded. The
+    // `backup` directory goes too if no other backup is left in it
+    try!(utils::remove_dir("backup", &backup_dir, notify_handler));
+    if let Some(parent) = backup_dir.parent() {
+        let _ = fs::remove_dir(parent);
+    }
+
+    Ok(())
+}
+
+/// Puts back what `migration` changes from the backup in `backup_dir`
+fn restore(cfg: &Cfg, migration: &Migration, backup_dir: &Path) -> Result<()> {
+    let notify_handler = &|n: rustup_utils::Notification| (cfg.notify_handler)(n.into());
+
+    for name in migration.backup {
+        let path = cfg.multirust_dir.join(name);
+        if utils::is_directory(&path) {
+            try!(utils::remove_dir("metadata", &path, notify_handler));
+        } else if utils::path_exists(&path) {
+            try!(utils::remove_file("metadata", &path));
+        }
+        try!(copy(&backup_dir.join(name), &path, notify_handler));
+    }
+    Ok(())
+}
+
+/// Copies the file or directory `src`, if it exists
+fn copy(src: &Path, dest: &Path, notify_handler: &Fn(rustup_utils::Notification)) -> Result<()> {
+    if utils::is_directory(src) {
+        try!(utils::copy_dir(src, dest, notify_handler));
+    } else if utils::is_file(src) {
+        try!(utils::copy_file(src, dest));
+    }
+    Ok(())
+}
+
+fn v2_to_v12(cfg: &Cfg) -> Result<()> {
+    let notify_handler = &|n: rustup_utils::Notification| (cfg.notify_handler)(n.into());
+
+    if utils::is_directory(&cfg.toolchains_dir) {
+        for dir in try!(utils::read_dir("toolchains", &cfg.toolchains_dir)) {
+            let dir = try!(dir.chain_err(|| ErrorKind::UpgradeIoError));
+            try!(utils::remove_dir("toolchain", &dir.path(), notify_handler));
+        }
+    }
+
+    // Also delete the update hashes
+    if utils::is_directory(&cfg.update_hash_dir) {
+        for file in try!(utils::read_dir("update hashes", &cfg.update_hash_dir)) {
+            let file = try!(file.chain_err(|| ErrorKind::UpgradeIoError));
+            try!(utils::remove_file("update hash", &file.path()));
+        }
+    }
+
+    Ok(())
+}
@@ -14,6 +14,21 @@ pub enum Notification<'a> {
     Temp(temp::Notification<'a>),
 
     SetDefaultToolchain(&'a str),
+    SetProfile(&'a str),
+    SetConfig(&'a str, &'a str),
+    SetToolOverride(&'a str, Option<&'a str>),
+    SetFallbacks(&'a str, Option<&'a [String]>),
+    SetPinned(&'a str, bool),
+    SkippingPinnedToolchain(&'a str),
+    CreatedProxy(&'a str),
+    RemovedProxy(&'a str),
+    ProxyShadowed(&'a Path),
+    NotExportingToolchain(&'a str),
+    NotImportingToolchainSettings(&'a str),
+    ToolchainLockMismatch(&'a Path, &'a str, &'a str, &'a str),
+    WroteToolchainLock(&'a Path),
+    InstallingOverrideRequirements(&'a Path),
+    InstallingComponentForBinary(&'a str, &'a str),
     SetOverrideToolchain(&'a Path, &'a str),
     LookingForToolchain(&'a str),
     ToolchainDirectory(&'a Path, &'a str),
@@ -24,6 +39,9 @@ pub enum Notification<'a> {
     UninstallingToolchain(&'a str),
     UninstalledToolchain(&'a str),
     ToolchainNotInstalled(&'a str),
+    CustomToolchainVersion(&'a str, &'a str),
+    CustomToolchainMissing(&'a str, &'a str),
+    FoundMatchingNightly(&'a str, &'a str),
     UpdateHashMatches,
     UpgradingMetadata(&'a str, &'a str),
     MetadataUpgradeNotNeeded(&'a str),
@@ -31,6 +49,7 @@ pub enum Notification<'a> {
     ReadMetadataVersion(&'a str),
     NonFatalError(&'a Error),
     UpgradeRemovesToolchains,
+    BackedUpMetadata(&'a str, &'a Path),
     MissingFileDuringSelfUninstall(PathBuf),
     SetTelemetry(&'a str),
 
@@ -70,16 +89,35 @@ impl<'a> Notification<'a> {
             UpdateHashMatches |
             TelemetryCleanupError(_) => NotificationLevel::Verbose,
             SetDefaultToolchain(_) |
+            SetProfile(_) |
+            SetConfig(_, _) |
+            SetToolOverride(_, _) |
+            SetFallbacks(_, _) |
+            SetPinned(_, _) |
+            SkippingPinnedToolchain(_) |
+            CreatedProxy(_) |
+            RemovedProxy(_) |
+            WroteToolchainLock(_) |
+            InstallingOverrideRequirements(_) |
+            InstallingComponentForBinary(_, _) |
             SetOverrideToolchain(_, _) |
             UsingExistingToolchain(_) |
             UninstallingToolchain(_) |
             UninstalledToolchain(_) |
             ToolchainNotInstalled(_) |
+            CustomToolchainVersion(_, _) |
+            FoundMatchingNightly(_, _) |
             UpgradingMetadata(_, _) |
             MetadataUpgradeNotNeeded(_) |
             SetTelemetry(_) => NotificationLevel::Info,
             NonFatalError(_) => NotificationLevel::Error,
             UpgradeRemovesToolchains |
+            BackedUpMetadata(_, _) |
+            NotExportingToolchain(_) |
+            NotImportingToolchainSettings(_) |
+            ProxyShadowed(_) |
+            CustomToolchainMissing(_, _) |
+            ToolchainLockMismatch(_, _, _, _) |
             MissingFileDuringSelfUninstall(_) => NotificationLevel::Warn,
         }
     }
@@ -93,6 +131,52 @@ impl<'a> Display for Notification<'a> {
             Utils(ref n) => n.fmt(f),
             Temp(ref n) => n.fmt(f),
             SetDefaultToolchain(name) => write!(f, "default toolchain set to '{}'", name),
+            SetProfile(name) => write!(f, "profile set to '{}'", name),
+            SetConfig(key, "") => write!(f, "'{}' unset", key),
+            SetToolOverride(tool, Some(name)) => {
+                write!(f, "override toolchain for '{}' set to '{}'", tool, name)
+            }
+            SetToolOverride(tool, None) => write!(f, "override toolchain for '{}' removed", tool),
+            SetPinned(name, true) => write!(f, "pinned toolchain '{}'", name),
+            SetPinned(name, false) => write!(f, "unpinned toolchain '{}'", name),
+            SkippingPinnedToolchain(name) => {
+                write!(f, "skipping toolchain '{}', which is pinned", name)
+            }
+            SetFallbacks(name, Some(fallbacks)) if fallbacks.is_empty() => {
+                write!(f, "toolchain '{}' no longer falls back to other toolchains", name)
+            }
+            SetFallbacks(name, Some(fallbacks)) => {
+                write!(f, "fallback toolchains for '{}' set to '{}'", name, fallbacks.join(", "))
+            }
+            SetFallbacks(name, None) => {
+                write!(f, "fallback toolchains for '{}' reset to the default", name)
+            }
+            CreatedProxy(tool) => write!(f, "created proxy for '{}'", tool),
+            RemovedProxy(tool) => write!(f, "removed proxy for '{}'", tool),
+            ProxyShadowed(path) => {
+                write!(f, "not creating a proxy over '{}', which was not installed by rustup",
+                       path.display())
+            }
+            ToolchainLockMismatch(path, name, date, installed) => {
+                write!(f, "'{}' locks '{}' to the release of {}, but the installed release is from {}",
+                       path.display(), name, date, installed)
+            }
+            WroteToolchainLock(path) => write!(f, "wrote toolchain lock '{}'", path.display()),
+            NotExportingToolchain(name) => {
+                write!(f, "toolchain '{}' was not installed from dist and will not be exported", name)
+            }
+            NotImportingToolchainSettings(name) => {
+                write!(f, "toolchain '{}' is not installed, so the settings that use it will not be imported",
+                       name)
+            }
+            SetConfig(key, value) => write!(f, "'{}' set to '{}'", key, value),
+            InstallingComponentForBinary(component, binary) => {
+                write!(f, "installing component '{}' to provide `{}`", component, binary)
+            }
+            InstallingOverrideRequirements(path) => {
+                write!(f, "installing targets and components required by the override for '{}'",
+                       path.display())
+            }
             SetOverrideToolchain(path, name) => {
                 write!(f,
                        "override toolchain for '{}' set to '{}'",
@@ -107,6 +191,15 @@ impl<'a> Display for Notification<'a> {
             UsingExistingToolchain(name) => write!(f, "using existing install for '{}'", name),
             UninstallingToolchain(name) => write!(f, "uninstalling toolchain '{}'", name),
             UninstalledToolchain(name) => write!(f, "toolchain '{}' uninstalled", name),
+            CustomToolchainVersion(name, version) => {
+                write!(f, "toolchain '{}' is rustc {}", name, version)
+            }
+            FoundMatchingNightly(name, nightly) => {
+                write!(f, "toolchain '{}' was built from the same commit as '{}'", name, nightly)
+            }
+            CustomToolchainMissing(name, path) => {
+                write!(f, "toolchain '{}' has no `{}`", name, path)
+            }
             ToolchainNotInstalled(name) => write!(f, "no toolchain installed for '{}'", name),
             UpdateHashMatches => {
                 write!(f, "toolchain is already up to date")
@@ -126,6 +219,9 @@ impl<'a> Display for Notification<'a> {
             ReadMetadataVersion(ver) => write!(f, "read metadata version: '{}'", ver),
             NonFatalError(e) => write!(f, "{}", e),
             UpgradeRemovesToolchains => write!(f, "this upgrade will remove all existing toolchains. you will need to reinstall them"),
+            BackedUpMetadata(ver, path) => {
+                write!(f, "backed up metadata version '{}' to '{}'", ver, path.display())
+            }
             MissingFileDuringSelfUninstall(ref p) => {
                 write!(f, "expected file does not exist to uninstall: {}", p.display())
             }
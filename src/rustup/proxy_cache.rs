@@ -0,0 +1,164 @@
+//! A record of the toolchain each directory resolved to, so that the
+//! proxies can run it without reading the settings or searching the
+//! overrides
+//!
+//! Directories are recorded by the path the proxy was run in. Only
+//! resolutions that need nothing from the settings beyond the
+//! toolchain are recorded, so a hit is enough to build the command.
+//!
+//! The first line of the file is a stamp of settings.toml; if the
+//! settings have changed since, the whole cache is stale. Each other
+//! line is `dir\ttoolchain\ttoolchain_path\toverride_path`, where the
+//! override path is empty if the toolchain is the default.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use rustup_utils::{raw, utils};
+
+pub const PROXY_CACHE_FILE: &'static str = "proxy-cache";
+
+/// The most directories remembered at once; the oldest are forgotten
+/// first
+const MAX_ENTRIES: usize = 64;
+
+/// What a directory resolved to
+#[derive(Clone, Debug, PartialEq)]
+pub struct CachedToolchain {
+    pub name: String,
+    pub path: PathBuf,
+    /// The directory of the override that selected the toolchain, or
+    /// `None` for the default
+    pub override_path: Option<PathBuf>,
+}
+
+pub struct ProxyCache {
+    path: PathBuf,
+    stamp: String,
+}
+
+impl ProxyCache {
+    pub fn new(path: PathBuf, settings_path: &Path) -> Self {
+        ProxyCache {
+            path: path,
+            stamp: settings_stamp(settings_path),
+        }
+    }
+
+    /// The toolchain `dir` last resolved to
+    pub fn get(&self, dir: &Path) -> Option<CachedToolchain> {
+        let dir = match dir.to_str() {
+            Some(d) => d,
+            None => return None,
+        };
+        self.entries().into_iter()
+            .find(|&(ref d, _)| d == dir)
+            .map(|(_, toolchain)| toolchain)
+    }
+
+    /// Remembers the resolution of `dir`. Failing to write the cache
+    /// isn't an error, it only means the next lookup will be slower.
+    pub fn insert(&self, dir: &Path, toolchain: &CachedToolchain) {
+        let dir = match dir.to_str() {
+            Some(d) if is_storable(d) => d.to_owned(),
+            _ => return,
+        };
+        let line = match format_entry(&dir, toolchain) {
+            Some(l) => l,
+            None => return,
+        };
+
+        let mut entries = self.entries();
+        entries.retain(|&(ref d, _)| *d != dir);
+        while entries.len() >= MAX_ENTRIES {
+            entries.remove(0);
+        }
+
+        let mut content = format!("{}\n", self.stamp);
+        for (d, t) in entries {
+            if let Some(l) = format_entry(&d, &t) {
+                content.push_str(&l);
+            }
+        }
+        content.push_str(&line);
+
+        let _ = self.write(&content);
+    }
+
+    /// Replaces the cache with `content`. The cache is only a shortcut,
+    /// so it is renamed into place for the sake of concurrent proxies
+    /// but not synced: losing it in a crash costs one slow lookup.
+    fn write(&self, content: &str) -> ::std::io::Result<()> {
+        let tmp_path = self.path.with_file_name(format!(".{}.{}.tmp", PROXY_CACHE_FILE,
+                                                        raw::random_string(8)));
+        let result = fs::File::create(&tmp_path)
+            .and_then(|mut f| f.write_all(content.as_bytes()))
+            .and_then(|()| fs::rename(&tmp_path, &self.path));
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp_path);
+        }
+        result
+    }
+
+    fn entries(&self) -> Vec<(String, CachedToolchain)> {
+        let content = match utils::read_file("proxy cache", &self.path) {
+            Ok(c) => c,
+            Err(_) => return Vec::new(),
+        };
+        let mut lines = content.lines();
+        if lines.next() != Some(&*self.stamp) {
+            return Vec::new();
+        }
+
+        lines.filter_map(|line| {
+            let mut fields = line.split('\t');
+            match (fields.next(), fields.next(), fields.next(), fields.next()) {
+                (Some(d), Some(t), Some(p), Some(o)) => {
+                    let o = if o.is_empty() { None } else { Some(PathBuf::from(o)) };
+                    Some((d.to_owned(), CachedToolchain {
+                        name: t.to_owned(),
+                        path: PathBuf::from(p),
+                        override_path: o,
+                    }))
+                }
+                _ => None,
+            }
+        }).collect()
+    }
+}
+
+fn format_entry(dir: &str, toolchain: &CachedToolchain) -> Option<String> {
+    let path = match toolchain.path.to_str() {
+        Some(p) if is_storable(p) => p,
+        _ => return None,
+    };
+    let override_path = match toolchain.override_path.as_ref().map(|p| p.to_str()) {
+        None => "",
+        Some(Some(p)) if is_storable(p) => p,
+        Some(_) => return None,
+    };
+    if !is_storable(&toolchain.name) {
+        return None;
+    }
+    Some(format!("{}\t{}\t{}\t{}\n", dir, toolchain.name, path, override_path))
+}
+
+/// Identifies the current contents of the settings file by its
+/// modification time and length
+fn settings_stamp(settings_path: &Path) -> String {
+    let metadata = match fs::metadata(settings_path) {
+        Ok(m) => m,
+        Err(_) => return "none".to_owned(),
+    };
+    let mtime = metadata.modified().ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| format!("{}.{:09}", d.as_secs(), d.subsec_nanos()))
+        .unwrap_or_else(String::new);
+    format!("{} {}", mtime, metadata.len())
+}
+
+fn is_storable(s: &str) -> bool {
+    !s.contains('\t') && !s.contains('\n') && !s.contains('\r')
+}
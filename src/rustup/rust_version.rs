@@ -0,0 +1,55 @@
+//! The minimum Rust version a package declares with `rust-version` in
+//! its Cargo.toml
+
+use errors::*;
+use utils;
+use regex::Regex;
+use toml;
+use std::path::{Path, PathBuf};
+
+pub const CARGO_MANIFEST: &'static str = "Cargo.toml";
+
+/// The nearest Cargo.toml in `dir` or its ancestors
+pub fn find_cargo_manifest(dir: &Path) -> Option<PathBuf> {
+    let mut maybe_dir = Some(dir);
+    while let Some(dir) = maybe_dir {
+        let path = dir.join(CARGO_MANIFEST);
+        if utils::is_file(&path) {
+            return Some(path);
+        }
+        maybe_dir = dir.parent();
+    }
+    None
+}
+
+/// The release channel, such as `1.31.0`, for the `rust-version` the
+/// Cargo.toml at `path` declares in `[package]` or `[package.metadata]`
+pub fn declared_channel(path: &Path) -> Result<String> {
+    let content = try!(utils::read_file("Cargo.toml", path));
+    let mut parser = toml::Parser::new(&content);
+    let manifest = try!(parser.parse()
+                        .ok_or_else(|| ErrorKind::ParsingCargoManifest(path.to_owned(),
+                                                                       parser.errors)));
+
+    let package = manifest.get("package").and_then(|p| p.as_table());
+    let version = package.and_then(|p| p.get("rust-version"))
+        .or_else(|| {
+            package.and_then(|p| p.get("metadata"))
+                .and_then(|m| m.as_table())
+                .and_then(|m| m.get("rust-version"))
+        })
+        .and_then(|v| v.as_str());
+    let version = try!(version.ok_or_else(|| ErrorKind::NoRustVersion(path.to_owned())));
+
+    // Releases are named by their full version, and a minimum version
+    // such as `1.31` means its first release
+    let re = Regex::new(r"^\d+\.\d+(\.\d+)?$").unwrap();
+    if !re.is_match(version) {
+        return Err(ErrorKind::InvalidRustVersion(path.to_owned(), version.to_owned()).into());
+    }
+    if version.matches('.').count() == 1 {
+        Ok(format!("{}.0", version))
+    } else {
+        Ok(version.to_owned())
+    }
+}
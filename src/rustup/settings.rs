@@ -1,43 +1,124 @@
 use errors::*;
 use notifications::*;
 use toml_utils::*;
+use migrations;
 use utils;
 use toml;
 use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::str::FromStr;
 
+/// Configuration keys that can also be set through environment
+/// variables, which take priority over the stored value. Each entry is
+/// the key, its variables, and a description.
+pub static CONFIG_KEYS: &'static [(&'static str, &'static [&'static str], &'static str)] = &[
+    ("dist-server", &["RUSTUP_DIST_SERVER", "RUSTUP_DIST_ROOT"],
+     "The server toolchains are downloaded from"),
+    ("update-root", &["RUSTUP_UPDATE_ROOT"],
+     "The root URL rustup self-updates are downloaded from"),
+    ("gpg-key", &["RUSTUP_GPG_KEY"],
+     "Path to the GPG key used to verify signatures"),
+    ("download-backend", &["RUSTUP_USE_HYPER", "RUSTUP_USE_RUSTLS"],
+     "The backend used for downloads: curl, hyper or rustls"),
+    ("staged-manifest", &["RUSTUP_STAGED_MANIFEST"],
+     "Whether to use the staging channel manifests: true or false"),
+    ("override-host-triple", &["RUSTUP_OVERRIDE_HOST_TRIPLE"],
+     "The triple rustup-init uses instead of detecting the host"),
+    ("system-toolchains", &["RUSTUP_SYSTEM_TOOLCHAINS"],
+     "A read-only directory of toolchains shared by all users"),
+];
+
+pub static DOWNLOAD_BACKENDS: &'static [&'static str] = &["curl", "hyper", "rustls"];
+
 pub const SUPPORTED_METADATA_VERSIONS: [&'static str; 2] = ["2", "12"];
 pub const DEFAULT_METADATA_VERSION: &'static str = "12";
 
 
+/// Checks that `path` holds an ASCII-armored public key. The stored key
+/// is read on every invocation, so one that can't be read would break
+/// all of them.
+fn check_gpg_key(path: &Path) -> Result<()> {
+    let key = try!(utils::read_file("public key", path)
+                   .chain_err(|| ErrorKind::InvalidGpgKey(path.to_owned())));
+    if key.contains("-----BEGIN PGP PUBLIC KEY BLOCK-----") &&
+        key.contains("-----END PGP PUBLIC KEY BLOCK-----") {
+        Ok(())
+    } else {
+        Err(ErrorKind::InvalidGpgKey(path.to_owned()).into())
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct SettingsFile {
     path: PathBuf,
-    cache: RefCell<Option<Settings>>
+    cache: RefCell<Option<Settings>>,
+    /// Whether the file on disk is known to parse, and so is worth
+    /// keeping as the backup when it's replaced
+    valid_on_disk: Cell<bool>,
 }
 
 impl SettingsFile {
     pub fn new(path: PathBuf) -> Self {
         SettingsFile {
             path: path,
-            cache: RefCell::new(None)
+            cache: RefCell::new(None),
+            valid_on_disk: Cell::new(false),
         }
     }
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    /// The settings as they were before the last write, used if the
+    /// settings file can't be read
+    fn backup_path(&self) -> PathBuf {
+        self.path.with_extension("toml.bak")
+    }
     fn write_settings(&self) -> Result<()> {
         let s = self.cache.borrow().as_ref().unwrap().clone();
-        try!(utils::write_file("settings", &self.path, &s.stringify()));
+        let ref content = s.stringify();
+        // The file being replaced becomes the backup. Linking it to the
+        // backup's name costs nothing to write, and the atomic write
+        // gives the settings file a new inode, leaving the link as it
+        // was
+        if self.valid_on_disk.get() {
+            let ref backup = self.backup_path();
+            if utils::hardlink_file(&self.path, backup).is_err() {
+                try!(utils::copy_file(&self.path, backup));
+            }
+        }
+        try!(utils::write_file_atomic("settings", &self.path, content));
+        self.valid_on_disk.set(true);
         Ok(())
     }
+    fn read_settings_from(path: &Path) -> Result<Settings> {
+        let content = try!(utils::read_file("settings", path));
+        Settings::parse(&content)
+    }
     fn read_settings(&self) -> Result<()> {
         let mut needs_save = false;
         {
             let mut b = self.cache.borrow_mut();
             if b.is_none() {
                 *b = Some(if utils::is_file(&self.path) {
-                    let content = try!(utils::read_file("settings", &self.path));
-                    try!(Settings::parse(&content))
+                    match Self::read_settings_from(&self.path) {
+                        Ok(s) => {
+                            self.valid_on_disk.set(true);
+                            s
+                        }
+                        // The backup could be older than the newer
+                        // rustup's settings, so don't downgrade them
+                        Err(e @ Error(ErrorKind::MetadataTooNew(_), _)) => return Err(e),
+                        Err(e) => {
+                            let backup = self.backup_path();
+                            if !utils::is_file(&backup) {
+                                return Err(e);
+                            }
+                            // Restore the damaged file from the backup
+                            needs_save = true;
+                            try!(Self::read_settings_from(&backup).map_err(|_| e))
+                        }
+                    }
                 } else {
                     needs_save = true;
                     Default::default()
@@ -49,6 +130,26 @@ impl SettingsFile {
         }
         Ok(())
     }
+    /// Reads the string `key` from settings a newer rustup wrote, which
+    /// can't be read as a whole. `None` if it isn't there or isn't a
+    /// string
+    pub fn read_newer_string(&self, key: &str) -> Option<String> {
+        let content = match utils::read_file("settings", &self.path) {
+            Ok(c) => c,
+            Err(_) => return None,
+        };
+        toml::Parser::new(&content).parse().and_then(|table| {
+            table.get(key).and_then(|v| v.as_str()).map(|s| s.to_owned())
+        })
+    }
+    /// Reads the settings without creating the file if it's missing
+    pub fn peek<T, F: FnOnce(&Settings) -> T>(&self, f: F) -> Result<T> {
+        if utils::is_file(&self.path) {
+            self.with(|s| Ok(f(s)))
+        } else {
+            Ok(f(&Default::default()))
+        }
+    }
     pub fn with<T, F: FnOnce(&Settings) -> Result<T>>(&self, f: F) -> Result<T> {
         try!(self.read_settings());
 
@@ -121,12 +222,105 @@ pub enum TelemetryMode {
     Off,
 }
 
+/// Targets and components that must be installed in the toolchain
+/// of a directory override
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OverrideRequirements {
+    pub targets: Vec<String>,
+    pub components: Vec<String>,
+}
+
+impl OverrideRequirements {
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty() && self.components.is_empty()
+    }
+
+    fn from_toml(mut table: toml::Table, path: &str) -> Result<Self> {
+        Ok(OverrideRequirements {
+            targets: try!(get_string_array(&mut table, "targets", path)),
+            components: try!(get_string_array(&mut table, "components", path)),
+        })
+    }
+
+    fn to_toml(self) -> toml::Table {
+        let mut result = toml::Table::new();
+        let targets = self.targets.into_iter().map(toml::Value::String).collect();
+        result.insert("targets".to_owned(), toml::Value::Array(targets));
+        let components = self.components.into_iter().map(toml::Value::String).collect();
+        result.insert("components".to_owned(), toml::Value::Array(components));
+        result
+    }
+}
+
+/// What the rustc of a custom toolchain reported when the toolchain
+/// was linked or copied in
+#[derive(Clone, Debug, PartialEq)]
+pub struct CustomToolchain {
+    pub version: String,
+    pub host: Option<String>,
+    /// The commit rustc was built from, and its date, unless it was
+    /// built without git
+    pub commit_hash: Option<String>,
+    pub commit_date: Option<String>,
+}
+
+impl CustomToolchain {
+    fn from_toml(mut table: toml::Table, path: &str) -> Result<Self> {
+        Ok(CustomToolchain {
+            version: try!(get_string(&mut table, "version", path)),
+            host: try!(get_opt_string(&mut table, "host", path)),
+            commit_hash: try!(get_opt_string(&mut table, "commit_hash", path)),
+            commit_date: try!(get_opt_string(&mut table, "commit_date", path)),
+        })
+    }
+
+    fn to_toml(self) -> toml::Table {
+        let mut result = toml::Table::new();
+        result.insert("version".to_owned(), toml::Value::String(self.version));
+        if let Some(host) = self.host {
+            result.insert("host".to_owned(), toml::Value::String(host));
+        }
+        if let Some(commit_hash) = self.commit_hash {
+            result.insert("commit_hash".to_owned(), toml::Value::String(commit_hash));
+        }
+        if let Some(commit_date) = self.commit_date {
+            result.insert("commit_date".to_owned(), toml::Value::String(commit_date));
+        }
+        result
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Settings {
     pub version: String,
     pub default_host_triple: Option<String>,
     pub default_toolchain: Option<String>,
+    pub profile: Option<String>,
+    pub dist_server: Option<String>,
+    pub update_root: Option<String>,
+    pub gpg_key: Option<String>,
+    pub download_backend: Option<String>,
+    pub staged_manifest: bool,
+    pub override_host_triple: Option<String>,
+    pub system_toolchains: Option<String>,
     pub overrides: BTreeMap<String, String>,
+    pub override_requirements: BTreeMap<String, OverrideRequirements>,
+    pub toolchain_env: BTreeMap<String, BTreeMap<String, String>>,
+    pub override_env: BTreeMap<String, BTreeMap<String, String>>,
+    pub tool_overrides: BTreeMap<String, String>,
+    /// The toolchains searched, in order, for a binary a toolchain
+    /// doesn't have
+    pub fallbacks: BTreeMap<String, Vec<String>>,
+    /// The proxies rustup created for binaries that components ship,
+    /// which it may remove again
+    pub proxies: Vec<String>,
+    /// The toolchains `rustup update` leaves alone unless named
+    pub pinned: Vec<String>,
+    pub custom_toolchains: BTreeMap<String, CustomToolchain>,
+    pub auto_install: bool,
+    /// Whether a directory without an override uses the `rust-version`
+    /// declared in the nearest Cargo.toml
+    pub manifest_overrides: bool,
     pub telemetry: TelemetryMode
 }
 
@@ -136,7 +330,25 @@ impl Default for Settings {
             version: DEFAULT_METADATA_VERSION.to_owned(),
             default_host_triple: None,
             default_toolchain: None,
+            profile: None,
+            dist_server: None,
+            update_root: None,
+            gpg_key: None,
+            download_backend: None,
+            staged_manifest: false,
+            override_host_triple: None,
+            system_toolchains: None,
             overrides: BTreeMap::new(),
+            override_requirements: BTreeMap::new(),
+            toolchain_env: BTreeMap::new(),
+            override_env: BTreeMap::new(),
+            tool_overrides: BTreeMap::new(),
+            fallbacks: BTreeMap::new(),
+            proxies: Vec::new(),
+            pinned: Vec::new(),
+            custom_toolchains: BTreeMap::new(),
+            auto_install: false,
+            manifest_overrides: false,
             telemetry: TelemetryMode::Off
         }
     }
@@ -153,15 +365,132 @@ impl Settings {
 
     pub fn remove_override(&mut self, path: &Path, notify_handler: &Fn(Notification)) -> bool {
         let key = Self::path_to_key(path, notify_handler);
+        self.override_requirements.remove(&key);
+        self.override_env.remove(&key);
         self.overrides.remove(&key).is_some()
     }
 
+    /// Sets the override for `path`. Requirements set with the previous
+    /// override were for its toolchain, so they're dropped.
     pub fn add_override(&mut self, path: &Path, toolchain: String, notify_handler: &Fn(Notification)) {
         let key = Self::path_to_key(path, notify_handler);
         notify_handler(Notification::SetOverrideToolchain(path, &toolchain));
+        self.override_requirements.remove(&key);
         self.overrides.insert(key, toolchain);
     }
 
+    pub fn set_override_requirements(&mut self, path: &Path, requirements: OverrideRequirements,
+                                     notify_handler: &Fn(Notification)) {
+        let key = Self::path_to_key(path, notify_handler);
+        if requirements.is_empty() {
+            self.override_requirements.remove(&key);
+        } else {
+            self.override_requirements.insert(key, requirements);
+        }
+    }
+
+    /// The requirements of the override set for exactly `path`, as
+    /// returned by `find_override`
+    pub fn override_requirements(&self, path: &Path, notify_handler: &Fn(Notification))
+            -> OverrideRequirements {
+        let key = Self::path_to_key(path, notify_handler);
+        self.override_requirements.get(&key).cloned().unwrap_or_default()
+    }
+
+    fn set_env_var(env: &mut BTreeMap<String, BTreeMap<String, String>>, key: String,
+                   var: &str, value: Option<&str>) {
+        let now_empty = {
+            let vars = env.entry(key.clone()).or_insert_with(BTreeMap::new);
+            match value {
+                Some(v) => { vars.insert(var.to_owned(), v.to_owned()); }
+                None => { vars.remove(var); }
+            }
+            vars.is_empty()
+        };
+        if now_empty {
+            env.remove(&key);
+        }
+    }
+
+    /// Sets the variable `var` for commands run with `toolchain`, or
+    /// unsets it if `value` is `None`
+    pub fn set_toolchain_env(&mut self, toolchain: &str, var: &str, value: Option<&str>) {
+        Self::set_env_var(&mut self.toolchain_env, toolchain.to_owned(), var, value);
+    }
+
+    pub fn toolchain_env(&self, toolchain: &str) -> BTreeMap<String, String> {
+        self.toolchain_env.get(toolchain).cloned().unwrap_or_default()
+    }
+
+    /// Sets the variable `var` for commands run under the override set
+    /// for `path`, or unsets it if `value` is `None`
+    pub fn set_override_env(&mut self, path: &Path, var: &str, value: Option<&str>,
+                            notify_handler: &Fn(Notification)) {
+        let key = Self::path_to_key(path, notify_handler);
+        Self::set_env_var(&mut self.override_env, key, var, value);
+    }
+
+    /// The variables of the override set for exactly `path`, as
+    /// returned by `find_override`
+    pub fn override_env(&self, path: &Path, notify_handler: &Fn(Notification))
+            -> BTreeMap<String, String> {
+        let key = Self::path_to_key(path, notify_handler);
+        self.override_env.get(&key).cloned().unwrap_or_default()
+    }
+
+    /// The stored value of one of the `CONFIG_KEYS`
+    pub fn get_config(&self, key: &str) -> Result<Option<String>> {
+        Ok(match key {
+            "dist-server" => self.dist_server.clone(),
+            "update-root" => self.update_root.clone(),
+            "gpg-key" => self.gpg_key.clone(),
+            "download-backend" => self.download_backend.clone(),
+            "staged-manifest" => if self.staged_manifest { Some("true".to_owned()) } else { None },
+            "override-host-triple" => self.override_host_triple.clone(),
+            "system-toolchains" => self.system_toolchains.clone(),
+            _ => return Err(ErrorKind::UnknownConfigKey(key.to_owned()).into()),
+        })
+    }
+
+    /// Stores one of the `CONFIG_KEYS`, or clears it if `value` is empty
+    pub fn set_config(&mut self, key: &str, value: &str) -> Result<()> {
+        let v = if value.is_empty() { None } else { Some(value.to_owned()) };
+        match key {
+            "dist-server" => self.dist_server = v.map(|s| s.trim_right_matches('/').to_owned()),
+            "update-root" => self.update_root = v.map(|s| s.trim_right_matches('/').to_owned()),
+            "gpg-key" => {
+                // Stored absolute, since it's read wherever rustup runs
+                let path = match v {
+                    Some(ref path) => Some(try!(utils::current_dir()).join(path)),
+                    None => None,
+                };
+                if let Some(ref path) = path {
+                    try!(check_gpg_key(path));
+                }
+                self.gpg_key = path.map(|p| p.to_string_lossy().into_owned());
+            }
+            "download-backend" => {
+                if let Some(ref b) = v {
+                    if !DOWNLOAD_BACKENDS.contains(&&**b) {
+                        return Err(ErrorKind::InvalidConfigValue(key.to_owned(), b.clone()).into());
+                    }
+                }
+                self.download_backend = v;
+            }
+            "staged-manifest" => {
+                self.staged_manifest = match value {
+                    "true" => true,
+                    "false" | "" => false,
+                    _ => return Err(ErrorKind::InvalidConfigValue(key.to_owned(), value.to_owned()).into()),
+                }
+            }
+            "override-host-triple" => self.override_host_triple = v,
+            "system-toolchains" => self.system_toolchains = v,
+            _ => return Err(ErrorKind::UnknownConfigKey(key.to_owned()).into()),
+        }
+        Ok(())
+    }
+
     pub fn find_override(&self, dir_unresolved: &Path, notify_handler: &Fn(Notification))
             -> Option<(String, PathBuf)> {
         let dir = utils::canonicalize_path(dir_unresolved, &|n| notify_handler(n.into()));
@@ -189,13 +518,35 @@ impl Settings {
     pub fn from_toml(mut table: toml::Table, path: &str) -> Result<Self> {
         let version = try!(get_string(&mut table, "version", path));
         if !SUPPORTED_METADATA_VERSIONS.contains(&&*version) {
+            if migrations::is_newer(&version) {
+                return Err(ErrorKind::MetadataTooNew(version).into());
+            }
             return Err(ErrorKind::UnknownMetadataVersion(version).into());
         }
         Ok(Settings {
             version: version,
             default_host_triple: try!(get_opt_string(&mut table, "default_host_triple", path)),
             default_toolchain: try!(get_opt_string(&mut table, "default_toolchain", path)),
+            profile: try!(get_opt_string(&mut table, "profile", path)),
+            dist_server: try!(get_opt_string(&mut table, "dist_server", path)),
+            update_root: try!(get_opt_string(&mut table, "update_root", path)),
+            gpg_key: try!(get_opt_string(&mut table, "gpg_key", path)),
+            download_backend: try!(get_opt_string(&mut table, "download_backend", path)),
+            staged_manifest: try!(get_opt_bool(&mut table, "staged_manifest", path)).unwrap_or(false),
+            override_host_triple: try!(get_opt_string(&mut table, "override_host_triple", path)),
+            system_toolchains: try!(get_opt_string(&mut table, "system_toolchains", path)),
             overrides: try!(Self::table_to_overrides(&mut table, path)),
+            override_requirements: try!(Self::table_to_override_requirements(&mut table, path)),
+            toolchain_env: try!(Self::table_to_env(&mut table, "toolchain_env", path)),
+            override_env: try!(Self::table_to_env(&mut table, "override_env", path)),
+            tool_overrides: try!(Self::table_to_tool_overrides(&mut table, path)),
+            fallbacks: try!(Self::table_to_fallbacks(&mut table, path)),
+            proxies: try!(get_string_array(&mut table, "proxies", path)),
+            pinned: try!(get_string_array(&mut table, "pinned", path)),
+            custom_toolchains: try!(Self::table_to_custom_toolchains(&mut table, path)),
+            auto_install: try!(get_opt_bool(&mut table, "auto_install", path)).unwrap_or(false),
+            manifest_overrides: try!(get_opt_bool(&mut table, "manifest_overrides", path))
+                .unwrap_or(false),
             telemetry: if try!(get_opt_bool(&mut table, "telemetry", path)).unwrap_or(false) {
                 TelemetryMode::On
             } else {
@@ -217,9 +568,93 @@ impl Settings {
             result.insert("default_toolchain".to_owned(), toml::Value::String(v));
         }
 
+        if let Some(v) = self.profile {
+            result.insert("profile".to_owned(), toml::Value::String(v));
+        }
+
+        if let Some(v) = self.dist_server {
+            result.insert("dist_server".to_owned(), toml::Value::String(v));
+        }
+
+        if let Some(v) = self.update_root {
+            result.insert("update_root".to_owned(), toml::Value::String(v));
+        }
+
+        if let Some(v) = self.gpg_key {
+            result.insert("gpg_key".to_owned(), toml::Value::String(v));
+        }
+
+        if let Some(v) = self.download_backend {
+            result.insert("download_backend".to_owned(), toml::Value::String(v));
+        }
+
+        if self.staged_manifest {
+            result.insert("staged_manifest".to_owned(), toml::Value::Boolean(true));
+        }
+
+        if let Some(v) = self.override_host_triple {
+            result.insert("override_host_triple".to_owned(), toml::Value::String(v));
+        }
+
+        if let Some(v) = self.system_toolchains {
+            result.insert("system_toolchains".to_owned(), toml::Value::String(v));
+        }
+
         let overrides = Self::overrides_to_table(self.overrides);
         result.insert("overrides".to_owned(), toml::Value::Table(overrides));
 
+        if !self.override_requirements.is_empty() {
+            let requirements = Self::override_requirements_to_table(self.override_requirements);
+            result.insert("override_requirements".to_owned(), toml::Value::Table(requirements));
+        }
+
+        if !self.toolchain_env.is_empty() {
+            let env = Self::env_to_table(self.toolchain_env);
+            result.insert("toolchain_env".to_owned(), toml::Value::Table(env));
+        }
+
+        if !self.override_env.is_empty() {
+            let env = Self::env_to_table(self.override_env);
+            result.insert("override_env".to_owned(), toml::Value::Table(env));
+        }
+
+        if !self.tool_overrides.is_empty() {
+            let tools = Self::overrides_to_table(self.tool_overrides);
+            result.insert("tool_overrides".to_owned(), toml::Value::Table(tools));
+        }
+
+        if !self.fallbacks.is_empty() {
+            let mut fallbacks = toml::Table::new();
+            for (k, v) in self.fallbacks {
+                let v = v.into_iter().map(toml::Value::String).collect();
+                fallbacks.insert(k, toml::Value::Array(v));
+            }
+            result.insert("fallbacks".to_owned(), toml::Value::Table(fallbacks));
+        }
+
+        if !self.proxies.is_empty() {
+            let proxies = self.proxies.into_iter().map(toml::Value::String).collect();
+            result.insert("proxies".to_owned(), toml::Value::Array(proxies));
+        }
+
+        if !self.pinned.is_empty() {
+            let pinned = self.pinned.into_iter().map(toml::Value::String).collect();
+            result.insert("pinned".to_owned(), toml::Value::Array(pinned));
+        }
+
+        if !self.custom_toolchains.is_empty() {
+            let custom = Self::custom_toolchains_to_table(self.custom_toolchains);
+            result.insert("custom_toolchains".to_owned(), toml::Value::Table(custom));
+        }
+
+        if self.auto_install {
+            result.insert("auto_install".to_owned(), toml::Value::Boolean(true));
+        }
+
+        if self.manifest_overrides {
+            result.insert("manifest_overrides".to_owned(), toml::Value::Boolean(true));
+        }
+
         let telemetry = self.telemetry == TelemetryMode::On;
         result.insert("telemetry".to_owned(), toml::Value::Boolean(telemetry));
 
@@ -239,6 +674,33 @@ impl Settings {
         Ok(result)
     }
 
+    fn table_to_tool_overrides(table: &mut toml::Table, path: &str) -> Result<BTreeMap<String, String>> {
+        let mut result = BTreeMap::new();
+        let tools_table = try!(get_table(table, "tool_overrides", path));
+
+        for (k, v) in tools_table {
+            if let toml::Value::String(t) = v {
+                result.insert(k, t);
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn table_to_fallbacks(table: &mut toml::Table, path: &str) -> Result<BTreeMap<String, Vec<String>>> {
+        let mut result = BTreeMap::new();
+        let mut fallbacks_table = try!(get_table(table, "fallbacks", path));
+        let path = format!("{}fallbacks.", path);
+        let names: Vec<String> = fallbacks_table.keys().cloned().collect();
+
+        for name in names {
+            let fallbacks = try!(get_string_array(&mut fallbacks_table, &name, &path));
+            result.insert(name, fallbacks);
+        }
+
+        Ok(result)
+    }
+
     fn overrides_to_table(overrides: BTreeMap<String, String>) -> toml::Table {
         let mut result = toml::Table::new();
         for (k, v) in overrides {
@@ -246,4 +708,83 @@ impl Settings {
         }
         result
     }
+
+    fn table_to_override_requirements(table: &mut toml::Table, path: &str)
+            -> Result<BTreeMap<String, OverrideRequirements>> {
+        let mut result = BTreeMap::new();
+        let req_table = try!(get_table(table, "override_requirements", path));
+
+        for (k, v) in req_table {
+            if let toml::Value::Table(t) = v {
+                let path = format!("{}override_requirements.{}.", path, k);
+                result.insert(k, try!(OverrideRequirements::from_toml(t, &path)));
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn override_requirements_to_table(requirements: BTreeMap<String, OverrideRequirements>)
+            -> toml::Table {
+        let mut result = toml::Table::new();
+        for (k, v) in requirements {
+            result.insert(k, toml::Value::Table(v.to_toml()));
+        }
+        result
+    }
+
+    fn table_to_custom_toolchains(table: &mut toml::Table, path: &str)
+            -> Result<BTreeMap<String, CustomToolchain>> {
+        let mut result = BTreeMap::new();
+        let custom_table = try!(get_table(table, "custom_toolchains", path));
+
+        for (k, v) in custom_table {
+            if let toml::Value::Table(t) = v {
+                let path = format!("{}custom_toolchains.{}.", path, k);
+                result.insert(k, try!(CustomToolchain::from_toml(t, &path)));
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn custom_toolchains_to_table(custom: BTreeMap<String, CustomToolchain>) -> toml::Table {
+        let mut result = toml::Table::new();
+        for (k, v) in custom {
+            result.insert(k, toml::Value::Table(v.to_toml()));
+        }
+        result
+    }
+
+    fn table_to_env(table: &mut toml::Table, key: &str, path: &str)
+            -> Result<BTreeMap<String, BTreeMap<String, String>>> {
+        let mut result = BTreeMap::new();
+        let env_table = try!(get_table(table, key, path));
+
+        for (k, v) in env_table {
+            if let toml::Value::Table(t) = v {
+                let mut vars = BTreeMap::new();
+                for (var, value) in t {
+                    if let toml::Value::String(value) = value {
+                        vars.insert(var, value);
+                    }
+                }
+                result.insert(k, vars);
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn env_to_table(env: BTreeMap<String, BTreeMap<String, String>>) -> toml::Table {
+        let mut result = toml::Table::new();
+        for (k, vars) in env {
+            let mut t = toml::Table::new();
+            for (var, value) in vars {
+                t.insert(var, toml::Value::String(value));
+            }
+            result.insert(k, toml::Value::Table(t));
+        }
+        result
+    }
 }
@@ -0,0 +1,106 @@
+//! The snapshot of rustup's state written by `rustup export` and
+//! restored by `rustup import`
+
+use errors::*;
+use settings::Settings;
+use toml_utils::*;
+use toml;
+use rustup_dist::config::Config as DistConfig;
+
+pub const SUPPORTED_STATE_VERSIONS: [&'static str; 1] = ["1"];
+pub const DEFAULT_STATE_VERSION: &'static str = "1";
+
+#[derive(Clone, Debug)]
+pub struct State {
+    pub version: String,
+    pub settings: Settings,
+    pub toolchains: Vec<ToolchainState>,
+}
+
+/// A toolchain installed from dist, pinned to the manifest it was
+/// installed from
+#[derive(Clone, Debug)]
+pub struct ToolchainState {
+    pub name: String,
+    pub date: String,
+    pub dist_config: DistConfig,
+}
+
+impl State {
+    pub fn new(settings: Settings, toolchains: Vec<ToolchainState>) -> Self {
+        State {
+            version: DEFAULT_STATE_VERSION.to_owned(),
+            settings: settings,
+            toolchains: toolchains,
+        }
+    }
+
+    pub fn parse(data: &str) -> Result<Self> {
+        let mut parser = toml::Parser::new(data);
+        let value = try!(parser.parse().ok_or_else(move || ErrorKind::ParsingState(parser.errors)));
+
+        Self::from_toml(value, "")
+    }
+    pub fn stringify(self) -> String {
+        toml::Value::Table(self.to_toml()).to_string()
+    }
+
+    pub fn from_toml(mut table: toml::Table, path: &str) -> Result<Self> {
+        let version = try!(get_string(&mut table, "state_version", path));
+        if !SUPPORTED_STATE_VERSIONS.contains(&&*version) {
+            return Err(ErrorKind::UnknownStateVersion(version).into());
+        }
+
+        let settings = try!(get_table(&mut table, "settings", path));
+        let settings = try!(Settings::from_toml(settings, &format!("{}settings.", path)));
+
+        let mut toolchains = Vec::new();
+        let path = format!("{}toolchains", path);
+        for (i, v) in try!(get_array(&mut table, "toolchains", &path)).into_iter().enumerate() {
+            if let toml::Value::Table(t) = v {
+                let path = format!("{}[{}].", path, i);
+                toolchains.push(try!(ToolchainState::from_toml(t, &path)));
+            }
+        }
+
+        Ok(State {
+            version: version,
+            settings: settings,
+            toolchains: toolchains,
+        })
+    }
+    pub fn to_toml(self) -> toml::Table {
+        let mut result = toml::Table::new();
+        result.insert("state_version".to_owned(),
+                      toml::Value::String(self.version));
+        result.insert("settings".to_owned(),
+                      toml::Value::Table(self.settings.to_toml()));
+
+        let toolchains = self.toolchains.into_iter()
+                                        .map(|t| toml::Value::Table(t.to_toml()))
+                                        .collect::<Vec<_>>();
+        if !toolchains.is_empty() {
+            result.insert("toolchains".to_owned(), toml::Value::Array(toolchains));
+        }
+        result
+    }
+}
+
+impl ToolchainState {
+    pub fn from_toml(mut table: toml::Table, path: &str) -> Result<Self> {
+        let dist_config = try!(get_table(&mut table, "dist_config", path));
+        Ok(ToolchainState {
+            name: try!(get_string(&mut table, "name", path)),
+            date: try!(get_string(&mut table, "date", path)),
+            dist_config: try!(DistConfig::from_toml(dist_config, &format!("{}dist_config.", path))),
+        })
+    }
+    pub fn to_toml(self) -> toml::Table {
+        let mut result = toml::Table::new();
+        result.insert("name".to_owned(), toml::Value::String(self.name));
+        result.insert("date".to_owned(), toml::Value::String(self.date));
+        result.insert("dist_config".to_owned(),
+                      toml::Value::Table(self.dist_config.to_toml()));
+        result
+    }
+}
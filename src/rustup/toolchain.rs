@@ -4,14 +4,17 @@ use rustup_dist;
 use rustup_dist::download::DownloadCfg;
 use rustup_utils::utils;
 use rustup_dist::prefix::InstallPrefix;
-use rustup_dist::dist::{ToolchainDesc};
-use rustup_dist::manifestation::{Manifestation, Changes};
-use rustup_dist::manifest::Component;
+use rustup_dist::dist::{self, ToolchainDesc, Profile, TargetTriple};
+use rustup_dist::manifestation::{Manifestation, Changes, DIST_MANIFEST};
+use rustup_dist::manifest::{Component, Manifest};
+use rustup_dist::config::Config as DistConfig;
 use config::Cfg;
+use settings::CustomToolchain;
 use env_var;
 use install::{self, InstallMethod};
 use telemetry;
 use telemetry::{Telemetry, TelemetryEvent};
+use time;
 
 use std::env::consts::EXE_SUFFIX;
 use std::ffi::OsString;
@@ -19,6 +22,9 @@ use std::process::Command;
 use std::path::{Path, PathBuf};
 use std::ffi::OsStr;
 use std::env;
+use std::fs;
+use std::io;
+use std::time::UNIX_EPOCH;
 
 use url::Url;
 
@@ -27,6 +33,7 @@ pub struct Toolchain<'a> {
     cfg: &'a Cfg,
     name: String,
     path: PathBuf,
+    system: bool,
     telemetry: telemetry::Telemetry,
     dist_handler: Box<Fn(rustup_dist::Notification) + 'a>,
 }
@@ -39,6 +46,51 @@ pub struct ComponentStatus {
     pub available: bool,
 }
 
+/// The binaries rustup knows to be shipped by optional components,
+/// and the component that ships each. This is a hardcoded table, not a
+/// lookup in the manifest, which doesn't say what binaries a component
+/// contains, so binaries missing from it have no known component.
+static COMPONENT_BINARIES: &'static [(&'static str, &'static str)] = &[
+    ("rls", "rls"),
+    ("rustfmt", "rustfmt"),
+    ("cargo-fmt", "rustfmt"),
+    ("cargo-clippy", "clippy"),
+    ("clippy-driver", "clippy"),
+    ("miri", "miri"),
+    ("cargo-miri", "miri"),
+];
+
+/// The component that ships `binary`, if it's in `COMPONENT_BINARIES`
+fn component_name_for_binary(binary: &str) -> Option<&'static str> {
+    COMPONENT_BINARIES.iter()
+        .find(|&&(bin, _)| bin == binary)
+        .map(|&(_, pkg)| pkg)
+}
+
+/// Used by the `info` function
+pub struct ToolchainInfo {
+    pub name: String,
+    /// The host triple, which for a custom toolchain is what its rustc
+    /// reported when it was linked
+    pub host: Option<String>,
+    pub custom: bool,
+    /// The directory a linked toolchain links to
+    pub link_target: Option<PathBuf>,
+    pub sysroot: PathBuf,
+    /// The date of the installed channel manifest
+    pub manifest_date: Option<String>,
+    pub profile: Option<String>,
+    /// The version of each installed package
+    pub packages: Vec<(String, String)>,
+    pub components: Vec<String>,
+    pub targets: Vec<String>,
+    /// When the toolchain was last installed or updated, in seconds
+    /// since the Unix epoch
+    pub install_time: Option<u64>,
+    /// The size of the toolchain on disk in bytes
+    pub size: u64,
+}
+
 pub enum UpdateStatus {
     Installed,
     Updated,
@@ -48,16 +100,32 @@ pub enum UpdateStatus {
 impl<'a> Toolchain<'a> {
     pub fn from(cfg: &'a Cfg, name: &str) -> Result<Self> {
         let resolved_name = try!(cfg.resolve_toolchain(name));
-        let path = cfg.toolchains_dir.join(&resolved_name);
-        Ok(Toolchain {
+        let mut path = cfg.toolchains_dir.join(&resolved_name);
+        let mut system = false;
+        // A toolchain the user hasn't installed themselves may be
+        // provided by the system store
+        if !utils::path_exists(&path) {
+            if let Some(ref system_dir) = cfg.system_toolchains_dir {
+                let system_path = system_dir.join(&resolved_name);
+                if utils::is_directory(&system_path) {
+                    path = system_path;
+                    system = true;
+                }
+            }
+        }
+        Ok(Toolchain::at_path(cfg, resolved_name, path, system))
+    }
+    fn at_path(cfg: &'a Cfg, name: String, path: PathBuf, system: bool) -> Self {
+        Toolchain {
             cfg: cfg,
-            name: resolved_name,
-            path: path.clone(),
+            name: name,
+            path: path,
+            system: system,
             telemetry: Telemetry::new(cfg.multirust_dir.join("telemetry")),
             dist_handler: Box::new(move |n| {
                 (cfg.notify_handler)(n.into())
             })
-        })
+        }
     }
     pub fn name(&self) -> &str {
         &self.name
@@ -73,7 +141,6 @@ impl<'a> Toolchain<'a> {
         // lead me to believe `fs::metadata`, used by `is_directory` does not
         // seem to follow symlinks on windows.
         let is_symlink = if cfg!(windows) {
-            use std::fs;
             fs::symlink_metadata(&self.path).map(|m| m.file_type().is_symlink()).unwrap_or(false)
         } else {
             false
@@ -83,7 +150,19 @@ impl<'a> Toolchain<'a> {
     pub fn verify(&self) -> Result<()> {
         Ok(try!(utils::assert_is_directory(&self.path)))
     }
+    /// Whether the toolchain comes from the read-only system store
+    pub fn is_system(&self) -> bool {
+        self.system
+    }
+    fn ensure_writable(&self) -> Result<()> {
+        if self.system {
+            Err(ErrorKind::SystemToolchainReadOnly(self.name.to_string(), self.path.clone()).into())
+        } else {
+            Ok(())
+        }
+    }
     pub fn remove(&self) -> Result<()> {
+        try!(self.ensure_writable());
         if self.exists() {
             (self.cfg.notify_handler)(Notification::UninstallingToolchain(&self.name));
         } else {
@@ -98,10 +177,34 @@ impl<'a> Toolchain<'a> {
         if !self.exists() {
             (self.cfg.notify_handler)(Notification::UninstalledToolchain(&self.name));
         }
-        Ok(try!(result))
+        try!(result);
+        if self.is_custom() {
+            try!(self.remove_overlay());
+            try!(self.cfg.settings_file.with_mut(|s| {
+                s.custom_toolchains.remove(&self.name);
+                Ok(())
+            }));
+        }
+        self.update_proxies();
+        Ok(())
     }
     fn install(&self, install_method: InstallMethod) -> Result<UpdateStatus> {
         assert!(self.is_valid_install_method(install_method));
+        // The system store is only for running toolchains the user
+        // hasn't installed; installs and updates go to the user's own
+        // store, where the new copy takes precedence
+        if self.system {
+            let user_path = self.cfg.toolchains_dir.join(&self.name);
+            let user_copy = Toolchain::at_path(self.cfg, self.name.clone(), user_path, false);
+            // An update hash left from an earlier user copy would make
+            // the install look up to date
+            if let Some(ref update_hash) = try!(user_copy.update_hash()) {
+                if utils::is_file(update_hash) {
+                    try!(utils::remove_file("update hash", update_hash));
+                }
+            }
+            return user_copy.install(install_method);
+        }
         let exists = self.exists();
         if exists {
             (self.cfg.notify_handler)(Notification::UpdatingToolchain(&self.name));
@@ -110,13 +213,21 @@ impl<'a> Toolchain<'a> {
         }
         (self.cfg.notify_handler)
             (Notification::ToolchainDirectory(&self.path, &self.name));
-        let updated = try!(install_method.run(&self.path,
-                                              &|n| (self.cfg.notify_handler)(n.into())));
+        let updated = match install_method.run(&self.path,
+                                               &|n| (self.cfg.notify_handler)(n.into())) {
+            Ok(updated) => updated,
+            Err(e) => {
+                let action = if exists { "toolchain update" } else { "toolchain install" };
+                self.cfg.audit_log.record(action, Some(&self.name), None, Some(&e));
+                return Err(e);
+            }
+        };
 
         if !updated {
             (self.cfg.notify_handler)(Notification::UpdateHashMatches);
         } else {
             (self.cfg.notify_handler)(Notification::InstalledToolchain(&self.name));
+            self.update_proxies();
         }
 
         let status = match (updated, exists) {
@@ -128,6 +239,13 @@ impl<'a> Toolchain<'a> {
 
         Ok(status)
     }
+    /// Brings the proxies in line with the executables now installed.
+    /// The toolchain itself is fine if this fails, so it isn't fatal.
+    fn update_proxies(&self) {
+        if let Err(ref e) = self.cfg.update_proxies() {
+            (self.cfg.notify_handler)(Notification::NonFatalError(e));
+        }
+    }
     fn install_if_not_installed(&self, install_method: InstallMethod) -> Result<UpdateStatus> {
         assert!(self.is_valid_install_method(install_method));
         (self.cfg.notify_handler)(Notification::LookingForToolchain(&self.name));
@@ -143,7 +261,7 @@ impl<'a> Toolchain<'a> {
             InstallMethod::Copy(_) |
             InstallMethod::Link(_) |
             InstallMethod::Installer(_, _) => self.is_custom(),
-            InstallMethod::Dist(_, _, _) => !self.is_custom(),
+            InstallMethod::Dist(_, _, _, _) => !self.is_custom(),
         }
     }
     fn update_hash(&self) -> Result<Option<PathBuf>> {
@@ -159,26 +277,185 @@ impl<'a> Toolchain<'a> {
             dist_root: &self.cfg.dist_root_url,
             temp_cfg: &self.cfg.temp_cfg,
             download_dir: &self.cfg.download_dir,
+            download_backend: self.cfg.download_backend.as_ref().map(|s| &**s),
+            staged_manifest: self.cfg.staged_manifest,
             notify_handler: &*self.dist_handler,
         }
     }
 
     pub fn install_from_dist(&self) -> Result<UpdateStatus> {
+        self.install_from_dist_with_profile(None)
+    }
+
+    /// Like `install_from_dist`, but a fresh install uses `profile`
+    /// instead of the configured default profile
+    pub fn install_from_dist_with_profile(&self, profile: Option<Profile>) -> Result<UpdateStatus> {
         if try!(self.cfg.telemetry_enabled()) {
-            return self.install_from_dist_with_telemetry();
+            return self.install_from_dist_with_telemetry(profile);
         }
-        self.install_from_dist_inner()
+        self.install_from_dist_inner(profile)
     }
 
-    pub fn install_from_dist_inner(&self) -> Result<UpdateStatus> {
+    pub fn install_from_dist_inner(&self, profile: Option<Profile>) -> Result<UpdateStatus> {
         let update_hash = try!(self.update_hash());
+        let profile = match profile {
+            Some(p) => p,
+            None => try!(self.cfg.get_profile()),
+        };
         self.install(InstallMethod::Dist(&try!(self.desc()),
+                                         Some(profile),
+                                         update_hash.as_ref().map(|p| &**p),
+                                         self.download_cfg()))
+    }
+
+    /// Installs the toolchain from the channel manifest published on
+    /// `date`, keeping the toolchain's own name
+    pub fn install_from_dist_at_date(&self, profile: Option<Profile>, date: &str) -> Result<UpdateStatus> {
+        let update_hash = try!(self.update_hash());
+        let profile = match profile {
+            Some(p) => p,
+            None => try!(self.cfg.get_profile()),
+        };
+        let mut desc = try!(self.desc());
+        desc.date = Some(date.to_owned());
+        self.install(InstallMethod::Dist(&desc,
+                                         Some(profile),
                                          update_hash.as_ref().map(|p| &**p),
                                          self.download_cfg()))
     }
 
-    pub fn install_from_dist_with_telemetry(&self) -> Result<UpdateStatus> {
-        let result = self.install_from_dist_inner();
+    /// The channel manifest the toolchain was installed from, if any
+    pub fn installed_manifest(&self) -> Result<Option<Manifest>> {
+        let toolchain = match ToolchainDesc::from_str(&self.name) {
+            Ok(t) => t,
+            Err(_) => return Ok(None),
+        };
+        if !self.exists() {
+            return Ok(None);
+        }
+        let prefix = InstallPrefix::from(self.path.to_owned());
+        let manifestation = try!(Manifestation::open(prefix, toolchain.target.clone()));
+        Ok(try!(manifestation.load_manifest()))
+    }
+
+    /// The executables the toolchain's components install in `bin/`,
+    /// if installed from dist
+    pub fn installed_binaries(&self) -> Result<Vec<String>> {
+        let toolchain = match ToolchainDesc::from_str(&self.name) {
+            Ok(t) => t,
+            Err(_) => return Ok(Vec::new()),
+        };
+        if !self.exists() {
+            return Ok(Vec::new());
+        }
+        let prefix = InstallPrefix::from(self.path.to_owned());
+        let manifestation = try!(Manifestation::open(prefix, toolchain.target.clone()));
+        Ok(try!(manifestation.installed_binaries()))
+    }
+
+    /// The component of the toolchain's dist manifest that would
+    /// provide `binary`, if it's available and not installed. Only
+    /// binaries in `COMPONENT_BINARIES` have a known component
+    pub fn component_for_binary(&self, binary: &str) -> Result<Option<Component>> {
+        let host = match ToolchainDesc::from_str(&self.name) {
+            Ok(t) => t.target,
+            Err(_) => return Ok(None),
+        };
+        if !self.exists() || try!(self.get_manifest()).is_none() {
+            return Ok(None);
+        }
+
+        let pkg = match component_name_for_binary(binary) {
+            Some(pkg) => pkg,
+            None => return Ok(None),
+        };
+        let preview = format!("{}-preview", pkg);
+
+        let component = try!(self.list_components()).into_iter().find(|s| {
+            (s.component.pkg == pkg || s.component.pkg == preview) &&
+                s.component.target.as_ref().map(|t| *t == host).unwrap_or(true)
+        });
+        Ok(component.and_then(|s| {
+            if !s.installed && s.available { Some(s.component) } else { None }
+        }))
+    }
+
+    /// Describes the toolchain from its installed manifest and dist
+    /// config
+    pub fn info(&self) -> Result<ToolchainInfo> {
+        if !self.exists() {
+            return Err(ErrorKind::ToolchainNotInstalled(self.name.to_owned()).into());
+        }
+
+        let mut info = ToolchainInfo {
+            name: self.name.clone(),
+            host: try!(self.custom_info()).and_then(|c| c.host),
+            custom: self.is_custom(),
+            link_target: self.link_target(),
+            sysroot: self.path.clone(),
+            manifest_date: None,
+            profile: None,
+            packages: Vec::new(),
+            components: Vec::new(),
+            targets: Vec::new(),
+            install_time: None,
+            size: utils::dir_size(&self.path),
+        };
+
+        let mut modified = fs::symlink_metadata(&self.path).and_then(|m| m.modified()).ok();
+        if let Ok(desc) = ToolchainDesc::from_str(&self.name) {
+            info.host = Some(desc.target.to_string());
+
+            let prefix = InstallPrefix::from(self.path.to_owned());
+            let manifest_path = prefix.manifest_file(DIST_MANIFEST);
+            let manifestation = try!(Manifestation::open(prefix, desc.target.clone()));
+            if let (Some(manifest), Some(config)) = (try!(manifestation.load_manifest()),
+                                                     try!(manifestation.read_config())) {
+                info.manifest_date = Some(manifest.date.clone());
+                info.profile = Some(config.profile.to_string());
+                for component in &config.components {
+                    if let Ok(pkg) = manifest.get_package(&component.pkg) {
+                        let package = (component.pkg.clone(), pkg.version.clone());
+                        if !info.packages.contains(&package) {
+                            info.packages.push(package);
+                        }
+                    }
+                    if component.pkg == "rust-std" {
+                        if let Some(ref target) = component.target {
+                            info.targets.push(target.to_string());
+                        }
+                    }
+                    info.components.push(component.name());
+                }
+                info.packages.sort();
+                info.components.sort();
+                info.targets.sort();
+                modified = fs::metadata(&manifest_path).and_then(|m| m.modified()).ok().or(modified);
+            }
+        }
+        info.install_time = modified.and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                                    .map(|d| d.as_secs());
+
+        Ok(info)
+    }
+
+    /// The date of the installed channel manifest and the dist config
+    /// recording the installed components, if installed from dist
+    pub fn dist_state(&self) -> Result<Option<(String, DistConfig)>> {
+        let toolchain = match ToolchainDesc::from_str(&self.name) {
+            Ok(t) => t,
+            Err(_) => return Ok(None),
+        };
+        let prefix = InstallPrefix::from(self.path.to_owned());
+        let manifestation = try!(Manifestation::open(prefix, toolchain.target.clone()));
+        match (try!(manifestation.load_manifest()), try!(manifestation.read_config())) {
+            (Some(manifest), Some(config)) => Ok(Some((manifest.date, config))),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn install_from_dist_with_telemetry(&self, profile: Option<Profile>) -> Result<UpdateStatus> {
+        let result = self.install_from_dist_inner(profile);
 
         match result {
             Ok(us) => {
@@ -205,7 +482,9 @@ impl<'a> Toolchain<'a> {
 
     pub fn install_from_dist_if_not_installed(&self) -> Result<UpdateStatus> {
         let update_hash = try!(self.update_hash());
+        let profile = try!(self.cfg.get_profile());
         self.install_if_not_installed(InstallMethod::Dist(&try!(self.desc()),
+                                                          Some(profile),
                                                           update_hash.as_ref().map(|p| &**p),
                                                           self.download_cfg()))
     }
@@ -253,9 +532,11 @@ impl<'a> Toolchain<'a> {
 
                 // Download to a local file
                 let local_installer = try!(self.cfg.temp_cfg.new_file_with_ext("", ".tar.gz"));
-                try!(utils::download_file(&url,
+                try!(utils::download_file_with_backend(&url,
                                           &local_installer,
                                           None,
+                                          false,
+                                          self.cfg.download_backend.as_ref().map(|s| &**s),
                                           &|n| (self.cfg.notify_handler)(n.into())));
                 try!(self.install(InstallMethod::Installer(&local_installer, &self.cfg.temp_cfg)));
             } else {
@@ -275,15 +556,22 @@ impl<'a> Toolchain<'a> {
     pub fn install_from_dir(&self, src: &Path, link: bool) -> Result<()> {
         try!(self.ensure_custom());
 
-        let mut pathbuf = PathBuf::from(src);
+        let rustc = src.join("bin").join(format!("rustc{}", EXE_SUFFIX));
+        if !utils::is_file(&rustc) {
+            return Err(ErrorKind::InvalidCustomToolchain(src.to_owned()).into());
+        }
+        let custom = try!(custom_rustc_version(&rustc));
 
-        pathbuf.push("lib");
-        try!(utils::assert_is_directory(&pathbuf));
-        pathbuf.pop();
-        pathbuf.push("bin");
-        try!(utils::assert_is_directory(&pathbuf));
-        pathbuf.push(format!("rustc{}", EXE_SUFFIX));
-        try!(utils::assert_is_file(&pathbuf));
+        // A toolchain can be used without these, as a partial build often
+        // is, but most tools will fail or fall back to another toolchain
+        if !utils::is_directory(&src.join("lib").join("rustlib")) {
+            (self.cfg.notify_handler)(Notification::CustomToolchainMissing(&self.name,
+                                                                            "lib/rustlib"));
+        }
+        if !utils::is_file(&src.join("bin").join(format!("cargo{}", EXE_SUFFIX))) {
+            (self.cfg.notify_handler)(Notification::CustomToolchainMissing(&self.name,
+                                                                            "bin/cargo"));
+        }
 
         if link {
             try!(self.install(InstallMethod::Link(&try!(utils::to_absolute(src)))));
@@ -291,9 +579,48 @@ impl<'a> Toolchain<'a> {
             try!(self.install(InstallMethod::Copy(src)));
         }
 
+        // Standard libraries added for the previous build may not work
+        // with this one
+        try!(self.remove_overlay());
+
+        (self.cfg.notify_handler)(Notification::CustomToolchainVersion(&self.name,
+                                                                       &custom.version));
+        try!(self.cfg.settings_file.with_mut(|s| {
+            s.custom_toolchains.insert(self.name.clone(), custom);
+            Ok(())
+        }));
+
+        Ok(())
+    }
+
+    /// The directory a linked toolchain links to, whether or not it
+    /// still exists
+    pub fn link_target(&self) -> Option<PathBuf> {
+        fs::symlink_metadata(&self.path).ok()
+            .and_then(|m| if m.file_type().is_symlink() { fs::read_link(&self.path).ok() } else { None })
+    }
+
+    /// The sysroot holding the standard libraries added to a custom
+    /// toolchain, beside links to the toolchain's own
+    pub fn overlay_dir(&self) -> PathBuf {
+        self.cfg.overlays_dir.join(&self.name)
+    }
+
+    fn remove_overlay(&self) -> Result<()> {
+        let overlay_dir = self.overlay_dir();
+        if utils::path_exists(&overlay_dir) {
+            try!(utils::remove_dir("overlay", &overlay_dir,
+                                   &|n| (self.cfg.notify_handler)(n.into())));
+        }
         Ok(())
     }
 
+    /// What the toolchain's rustc reported when the toolchain was linked
+    /// or copied in, if it is custom
+    pub fn custom_info(&self) -> Result<Option<CustomToolchain>> {
+        self.cfg.settings_file.with(|s| Ok(s.custom_toolchains.get(&self.name).cloned()))
+    }
+
     pub fn create_command<T: AsRef<OsStr>>(&self, binary: T) -> Result<Command> {
         if !self.exists() {
             return Err(ErrorKind::ToolchainNotInstalled(self.name.to_owned()).into());
@@ -318,10 +645,14 @@ impl<'a> Toolchain<'a> {
             let recursion_count = env::var("RUST_RECURSION_COUNT").ok()
                 .and_then(|s| s.parse().ok()).unwrap_or(0);
             if recursion_count > env_var::RUST_RECURSION_COUNT_MAX - 1 {
-                return Err(ErrorKind::BinaryNotFound(self.name.clone(),
-                                                     binary.to_string_lossy()
-                                                           .into())
-                            .into())
+                let binary_str = binary.to_string_lossy().into_owned();
+                let stem = Path::new(&binary).file_stem().and_then(|s| s.to_str());
+                // Only binaries in the table have a component to suggest
+                if !self.is_custom() && stem.and_then(component_name_for_binary).is_none() {
+                    return Err(ErrorKind::BinaryProviderUnknown(self.name.clone(),
+                                                                binary_str).into());
+                }
+                return Err(ErrorKind::BinaryNotFound(self.name.clone(), binary_str).into())
             }
             Path::new(&binary)
         };
@@ -330,13 +661,29 @@ impl<'a> Toolchain<'a> {
         Ok(cmd)
     }
 
+    /// Points rustc and rustdoc at the overlay, which is a complete
+    /// sysroot, so they find the standard libraries added to the
+    /// toolchain. A sysroot chosen in `args` is left to take effect.
+    pub fn set_overlay_sysroot<S: AsRef<OsStr>>(&self, cmd: &mut Command, binary: &str,
+                                                args: &[S]) {
+        let stem = Path::new(binary).file_stem().and_then(|s| s.to_str());
+        if stem != Some("rustc") && stem != Some("rustdoc") {
+            return;
+        }
+        let has_sysroot = args.iter().any(|a| {
+            let a = a.as_ref().to_str().unwrap_or("");
+            a == "--sysroot" || a.starts_with("--sysroot=")
+        });
+        if !has_sysroot && self.is_custom() && utils::is_directory(&self.overlay_dir()) {
+            cmd.arg("--sysroot").arg(self.overlay_dir());
+        }
+    }
+
     // Create a command as a fallback for another toolchain. This is used
-    // to give custom toolchains access to cargo
+    // to give toolchains access to the tools they lack, like cargo for
+    // custom toolchains
     pub fn create_fallback_command<T: AsRef<OsStr>>(&self, binary: T,
                                                     primary_toolchain: &Toolchain) -> Result<Command> {
-        // With the hacks below this only works for cargo atm
-        assert!(binary.as_ref() == "cargo" || binary.as_ref() == "cargo.exe");
-
         if !self.exists() {
             return Err(ErrorKind::ToolchainNotInstalled(self.name.to_owned()).into());
         }
@@ -344,25 +691,28 @@ impl<'a> Toolchain<'a> {
             return Err(ErrorKind::ToolchainNotInstalled(primary_toolchain.name.to_owned()).into());
         }
 
-        let src_file = self.path.join("bin").join(format!("cargo{}", EXE_SUFFIX));
+        let binary = binary.as_ref().to_string_lossy();
+        let binary = tool_name(&binary);
+        let src_file = self.binary_file(binary);
 
-        // MAJOR HACKS: Copy cargo.exe to its own directory on windows before
+        // MAJOR HACKS: Copy the exe to its own directory on windows before
         // running it. This is so that the fallback cargo, when it in turn runs
         // rustc.exe, will run the rustc.exe out of the PATH environment
         // variable, _not_ the rustc.exe sitting in the same directory as the
         // fallback. See the `fallback_cargo_calls_correct_rustc` testcase and
-        // PR 812.
+        // PR 812. Any other tool that runs the tools beside it has the same
+        // problem.
         //
         // On Windows, spawning a process will search the running application's
         // directory for the exe to spawn before searching PATH, and we don't want
-        // it to do that, because cargo's directory contains the _wrong_ rustc. See
-        // the documantation for the lpCommandLine argument of CreateProcess.
+        // it to do that, because the fallback's directory contains the _wrong_
+        // tools. See the documantation for the lpCommandLine argument of
+        // CreateProcess.
         let exe_path = if cfg!(windows) {
-            use std::fs;
             let fallback_dir = self.cfg.multirust_dir.join("fallback");
             try!(fs::create_dir_all(&fallback_dir)
                  .chain_err(|| "unable to create dir to hold fallback exe"));
-            let fallback_file = fallback_dir.join("cargo.exe");
+            let fallback_file = fallback_dir.join(format!("{}{}", binary, EXE_SUFFIX));
             if fallback_file.exists() {
                 try!(fs::remove_file(&fallback_file)
                      .chain_err(|| "unable to unlink old fallback exe"));
@@ -380,49 +730,31 @@ impl<'a> Toolchain<'a> {
     }
 
     fn set_env(&self, cmd: &mut Command) {
-        self.set_ldpath(cmd);
-
-        // Because multirust and cargo use slightly different
-        // definitions of cargo home (multirust doesn't read HOME on
-        // windows), we must set it here to ensure cargo and
-        // multirust agree.
-        if let Ok(cargo_home) = utils::cargo_home() {
-            cmd.env("CARGO_HOME", &cargo_home);
+        for (var, value) in self.env_vars() {
+            cmd.env(var, value);
         }
 
         env_var::inc("RUST_RECURSION_COUNT", cmd);
-
-        cmd.env("RUSTUP_TOOLCHAIN", &self.name);
-        cmd.env("RUSTUP_HOME", &self.cfg.multirust_dir);
     }
 
-    pub fn set_ldpath(&self, cmd: &mut Command) {
-        let new_path = self.path.join("lib");
+    /// The variables set for the commands run with the toolchain
+    pub fn env_vars(&self) -> Vec<(String, OsString)> {
+        let mut vars = base_env_vars(&self.name, &self.path, &self.cfg.multirust_dir);
 
-        #[cfg(not(target_os = "macos"))]
-        mod sysenv {
-            pub const LOADER_PATH: &'static str = "LD_LIBRARY_PATH";
-        }
-        #[cfg(target_os = "macos")]
-        mod sysenv {
-            pub const LOADER_PATH: &'static str = "DYLD_LIBRARY_PATH";
+        // Variables set for this toolchain with `rustup toolchain env`
+        if let Ok(env) = self.cfg.settings_file.with(|s| Ok(s.toolchain_env(&self.name))) {
+            for (var, value) in env {
+                vars.push((var, OsString::from(value)));
+            }
         }
-        env_var::prepend_path(sysenv::LOADER_PATH, vec![new_path.clone()], cmd);
 
-        // Prepend CARGO_HOME/bin to the PATH variable so that we're sure to run
-        // cargo/rustc via the proxy bins. There is no fallback case for if the
-        // proxy bins don't exist. We'll just be running whatever happens to
-        // be on the PATH.
-        let mut path_entries = vec![];
-        if let Ok(cargo_home) = utils::cargo_home() {
-            path_entries.push(cargo_home.join("bin").to_path_buf());
-        }
+        vars
+    }
 
-        if cfg!(target_os = "windows") {
-            path_entries.push(self.path.join("bin"));
+    pub fn set_ldpath(&self, cmd: &mut Command) {
+        for (var, value) in ldpath_vars(&self.path) {
+            cmd.env(var, value);
         }
-
-        env_var::prepend_path("PATH", path_entries, cmd);
     }
 
     pub fn doc_path(&self, relative: &str) -> Result<PathBuf> {
@@ -453,10 +785,28 @@ impl<'a> Toolchain<'a> {
         })))
     }
 
+    /// The components the toolchain's dist config records as installed.
+    /// Unlike `list_components` this doesn't read the channel manifest.
+    pub fn installed_components(&self) -> Result<Vec<Component>> {
+        let toolchain = match ToolchainDesc::from_str(&self.name) {
+            Ok(t) => t,
+            Err(_) => return Ok(Vec::new()),
+        };
+        if !self.exists() {
+            return Ok(Vec::new());
+        }
+        let prefix = InstallPrefix::from(self.path.to_owned());
+        let manifestation = try!(Manifestation::open(prefix, toolchain.target.clone()));
+        Ok(try!(manifestation.read_config()).map(|c| c.components).unwrap_or(Vec::new()))
+    }
+
     pub fn list_components(&self) -> Result<Vec<ComponentStatus>> {
         if !self.exists() {
             return Err(ErrorKind::ToolchainNotInstalled(self.name.to_owned()).into());
         }
+        if self.is_custom() {
+            return self.list_overlay_components();
+        }
 
         let toolchain = &self.name;
         let ref toolchain = try!(ToolchainDesc::from_str(toolchain)
@@ -466,6 +816,7 @@ impl<'a> Toolchain<'a> {
 
         if let Some(manifest) = try!(manifestation.load_manifest()) {
             let config = try!(manifestation.read_config());
+            let profile = config.as_ref().map(|c| c.profile).unwrap_or(Profile::Default);
 
             // Return all optional components of the "rust" package for the
             // toolchain's target triple.
@@ -489,7 +840,7 @@ impl<'a> Toolchain<'a> {
 
                 res.push(ComponentStatus {
                     component: component.clone(),
-                    required: true,
+                    required: profile.includes_component(component),
                     installed: installed,
                     available: component_target_pkg.available,
                 });
@@ -523,10 +874,26 @@ impl<'a> Toolchain<'a> {
     }
 
     pub fn add_component(&self, component: Component) -> Result<()> {
-        if try!(self.cfg.telemetry_enabled()) {
-            return self.telemetry_add_component(component);
-        }
-        self.add_component_without_telemetry(component)
+        let audited = component.clone();
+        let result = if try!(self.cfg.telemetry_enabled()) {
+            self.telemetry_add_component(component)
+        } else {
+            self.add_component_without_telemetry(component)
+        };
+        self.audit_component_change(true, &audited, &result);
+        result
+    }
+
+    /// Records a component being added or removed in the audit log,
+    /// where `rust-std` components are recorded as targets
+    fn audit_component_change(&self, added: bool, component: &Component, result: &Result<()>) {
+        let (action, detail) = match component.target {
+            Some(ref target) if component.pkg == "rust-std" => {
+                (if added { "target add" } else { "target remove" }, target.to_string())
+            }
+            _ => (if added { "component add" } else { "component remove" }, component.name()),
+        };
+        self.cfg.audit_log.record(action, Some(&self.name), Some(&detail), result.as_ref().err());
     }
 
     fn telemetry_add_component(&self, component: Component) -> Result<()> {
@@ -565,6 +932,11 @@ impl<'a> Toolchain<'a> {
         if !self.exists() {
             return Err(ErrorKind::ToolchainNotInstalled(self.name.to_owned()).into());
         }
+        try!(self.ensure_writable());
+
+        if self.is_custom() && component.pkg == "rust-std" {
+            return self.add_overlay_std(component);
+        }
 
         let toolchain = &self.name;
         let ref toolchain = try!(ToolchainDesc::from_str(toolchain)
@@ -579,12 +951,21 @@ impl<'a> Toolchain<'a> {
                 .expect("manifest should cantain a rust package");
             let targ_pkg = rust_pkg.targets.get(&toolchain.target)
                 .expect("installed manifest should have a known target");
+            let profile = try!(manifestation.read_config()).map(|c| c.profile)
+                .unwrap_or(Profile::Default);
+
+            // Follow the manifest if the component has been renamed
+            if let Some(renamed) = manifest.rename_component(&component) {
+                component = renamed;
+            }
 
-            if targ_pkg.components.contains(&component) {
+            // Required components can only be added if the profile
+            // the toolchain was installed with left them out
+            if targ_pkg.components.contains(&component) && profile.includes_component(&component) {
                 return Err(ErrorKind::AddingRequiredComponent(self.name.to_string(), component).into());
             }
 
-            if !targ_pkg.extensions.contains(&component) {
+            if !targ_pkg.extensions.contains(&component) && !targ_pkg.components.contains(&component) {
                 let wildcard_component = Component { target: None, ..component.clone() };
                 if targ_pkg.extensions.contains(&wildcard_component) {
                     component = wildcard_component;
@@ -595,13 +976,15 @@ impl<'a> Toolchain<'a> {
 
             let changes = Changes {
                 add_extensions: vec![component],
-                remove_extensions: vec![]
+                remove_extensions: vec![],
+                profile: None,
             };
 
             try!(manifestation.update(&manifest,
                                       changes,
                                       &self.download_cfg(),
                                       self.download_cfg().notify_handler.clone()));
+            self.update_proxies();
 
             Ok(())
         } else {
@@ -609,10 +992,196 @@ impl<'a> Toolchain<'a> {
         }
     }
 
-    pub fn remove_component(&self, mut component: Component) -> Result<()> {
+    /// Installs a standard library into the overlay of a custom
+    /// toolchain, from the nightly built from the same commit as the
+    /// toolchain's rustc
+    fn add_overlay_std(&self, component: Component) -> Result<()> {
+        // The overlay's copy would be shadowed by the toolchain's own,
+        // which `link_overlay_rustlib` leaves in place
+        if let Some(ref target) = component.target {
+            if try!(self.own_std_targets()).contains(target) {
+                return Err(ErrorKind::OwnStdTarget(self.name.to_string(), target.to_string()).into());
+            }
+        }
+
+        let custom = try!(self.custom_info());
+        let commit_hash = try!(custom.as_ref().and_then(|c| c.commit_hash.clone())
+                               .ok_or_else(|| ErrorKind::UnknownCustomCommit(self.name.to_string())));
+        let commit_date = custom.as_ref().and_then(|c| c.commit_date.clone());
+        let host = try!(self.custom_host());
+
+        let (nightly, manifest) = try!(self.find_nightly(&commit_hash, commit_date.as_ref().map(|d| &**d),
+                                                         &host));
+        (self.cfg.notify_handler)(Notification::FoundMatchingNightly(&self.name,
+                                                                     &nightly.to_string()));
+
+        let prefix = InstallPrefix::from(self.overlay_dir());
+        let manifestation = try!(Manifestation::open(prefix, host));
+        try!(manifestation.install_standalone(&manifest,
+                                              vec![component],
+                                              &self.download_cfg(),
+                                              self.download_cfg().notify_handler.clone()));
+
+        self.link_overlay_rustlib()
+    }
+
+    /// Removes a standard library installed into the overlay of a
+    /// custom toolchain
+    fn remove_overlay_std(&self, component: Component) -> Result<()> {
+        if let Some(ref target) = component.target {
+            if try!(self.own_std_targets()).contains(target) {
+                return Err(ErrorKind::RemovingRequiredComponent(self.name.to_string(), component.clone()).into());
+            }
+        }
+
+        let prefix = InstallPrefix::from(self.overlay_dir());
+        let manifestation = try!(Manifestation::open(prefix, try!(self.custom_host())));
+        let installed = try!(manifestation.read_config()).map(|c| c.components).unwrap_or(Vec::new());
+        if !installed.contains(&component) {
+            return Err(ErrorKind::UnknownComponent(self.name.to_string(), component).into());
+        }
+
+        try!(manifestation.uninstall_standalone(vec![component],
+                                                &self.cfg.temp_cfg,
+                                                self.download_cfg().notify_handler.clone()));
+        Ok(())
+    }
+
+    /// The standard libraries of a custom toolchain: those it was built
+    /// with, and those added to its overlay. Only the targets the
+    /// overlay's nightly has are listed as available, so until one is
+    /// added, only the toolchain's own are listed.
+    fn list_overlay_components(&self) -> Result<Vec<ComponentStatus>> {
+        let rust_std = |target: TargetTriple| Component {
+            pkg: "rust-std".to_owned(),
+            target: Some(target),
+        };
+
+        let own = try!(self.own_std_targets());
+        let mut res: Vec<ComponentStatus> = own.iter().map(|target| ComponentStatus {
+            component: rust_std(target.clone()),
+            required: true,
+            installed: true,
+            available: true,
+        }).collect();
+
+        let host = try!(self.custom_host());
+        let prefix = InstallPrefix::from(self.overlay_dir());
+        let manifestation = try!(Manifestation::open(prefix, host.clone()));
+        if let Some(manifest) = try!(manifestation.load_manifest()) {
+            let installed = try!(manifestation.read_config()).map(|c| c.components).unwrap_or(Vec::new());
+            let rust_pkg = try!(try!(manifest.get_package("rust")).get_target(Some(&host)));
+            let stds = rust_pkg.components.iter().chain(rust_pkg.extensions.iter())
+                .filter(|c| c.pkg == "rust-std");
+            for component in stds {
+                if component.target.as_ref().map(|t| own.contains(t)).unwrap_or(true) {
+                    continue;
+                }
+                let available = try!(try!(manifest.get_package(&component.pkg))
+                                     .get_target(component.target.as_ref())).available;
+                res.push(ComponentStatus {
+                    component: component.clone(),
+                    required: false,
+                    installed: installed.contains(component),
+                    available: available,
+                });
+            }
+        }
+
+        res.sort_by(|a, b| a.component.cmp(&b.component));
+        Ok(res)
+    }
+
+    /// The targets a custom toolchain has a standard library for in its
+    /// own `lib/rustlib`
+    fn own_std_targets(&self) -> Result<Vec<TargetTriple>> {
+        let rustlib = self.path.join("lib").join("rustlib");
+        if !utils::is_directory(&rustlib) {
+            return Ok(Vec::new());
+        }
+        let entries = try!(utils::read_dir("rustlib", &rustlib)).filter_map(io::Result::ok);
+        Ok(entries.filter(|e| e.path().join("lib").is_dir())
+           .filter_map(|e| e.file_name().into_string().ok())
+           .map(|t| TargetTriple::from_str(&t))
+           .collect())
+    }
+
+    /// The host of a custom toolchain, as its rustc reported it
+    fn custom_host(&self) -> Result<TargetTriple> {
+        match try!(self.custom_info()).and_then(|c| c.host) {
+            Some(host) => Ok(TargetTriple::from_str(&host)),
+            None => self.cfg.get_default_host_triple(),
+        }
+    }
+
+    /// The nightly built from `commit_hash`, and its manifest. A
+    /// nightly is built from the commits of the day before, so the
+    /// nightlies of the days after the commit are tried, then the
+    /// latest.
+    fn find_nightly(&self, commit_hash: &str, commit_date: Option<&str>, host: &TargetTriple)
+                    -> Result<(ToolchainDesc, Manifest)> {
+        let mut dates = Vec::new();
+        if let Some(date) = commit_date.and_then(|d| time::strptime(d, "%Y-%m-%d").ok()) {
+            for days in 0..3 {
+                if let Ok(date) = time::strftime("%Y-%m-%d", &(date + time::Duration::days(days))) {
+                    dates.push(Some(date));
+                }
+            }
+        }
+        dates.push(None);
+
+        for date in dates {
+            let nightly = ToolchainDesc {
+                channel: "nightly".to_owned(),
+                date: date,
+                target: host.clone(),
+            };
+            if let Some(manifest) = try!(dist::dl_manifest(self.download_cfg(), &nightly)) {
+                if built_from_commit(&manifest, commit_hash) {
+                    return Ok((nightly, manifest));
+                }
+            }
+        }
+
+        Err(ErrorKind::NoMatchingNightly(self.name.to_string(), commit_hash.to_owned()).into())
+    }
+
+    /// Links the toolchain's own standard libraries into the overlay,
+    /// making it a complete sysroot
+    fn link_overlay_rustlib(&self) -> Result<()> {
+        let rustlib = self.path.join("lib").join("rustlib");
+        let overlay_rustlib = self.overlay_dir().join("lib").join("rustlib");
+        if !utils::is_directory(&rustlib) {
+            return Ok(());
+        }
+
+        let entries = try!(utils::read_dir("rustlib", &rustlib)).filter_map(io::Result::ok);
+        for entry in entries.filter(|e| e.path().is_dir()) {
+            let dest = overlay_rustlib.join(entry.file_name());
+            if fs::symlink_metadata(&dest).is_err() {
+                try!(utils::symlink_dir(&entry.path(), &dest,
+                                        &|n| (self.cfg.notify_handler)(n.into())));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn remove_component(&self, component: Component) -> Result<()> {
+        let audited = component.clone();
+        let result = self.bare_remove_component(component);
+        self.audit_component_change(false, &audited, &result);
+        result
+    }
+
+    fn bare_remove_component(&self, mut component: Component) -> Result<()> {
         if !self.exists() {
             return Err(ErrorKind::ToolchainNotInstalled(self.name.to_owned()).into());
         }
+        try!(self.ensure_writable());
+
+        if self.is_custom() && component.pkg == "rust-std" {
+            return self.remove_overlay_std(component);
+        }
 
         let toolchain = &self.name;
         let ref toolchain = try!(ToolchainDesc::from_str(toolchain)
@@ -627,15 +1196,24 @@ impl<'a> Toolchain<'a> {
                 .expect("manifest should cantain a rust package");
             let targ_pkg = rust_pkg.targets.get(&toolchain.target)
                 .expect("installed manifest should have a known target");
+            let dist_config = try!(manifestation.read_config()).unwrap();
 
-            if targ_pkg.components.contains(&component) {
+            // Follow the manifest if the component has been renamed
+            if let Some(renamed) = manifest.rename_component(&component) {
+                component = renamed;
+            }
+            let installed: Vec<Component> = dist_config.components.iter().map(|c| {
+                manifest.rename_component(c).unwrap_or_else(|| c.clone())
+            }).collect();
+
+            if targ_pkg.components.contains(&component) &&
+                dist_config.profile.includes_component(&component) {
                 return Err(ErrorKind::RemovingRequiredComponent(self.name.to_string(), component).into());
             }
 
-            let dist_config = try!(manifestation.read_config()).unwrap();
-            if !dist_config.components.contains(&component) {
+            if !installed.contains(&component) {
                 let wildcard_component = Component { target: None, ..component.clone() };
-                if dist_config.components.contains(&wildcard_component) {
+                if installed.contains(&wildcard_component) {
                     component = wildcard_component;
                 } else {
                     return Err(ErrorKind::UnknownComponent(self.name.to_string(), component).into());
@@ -644,13 +1222,15 @@ impl<'a> Toolchain<'a> {
 
             let changes = Changes {
                 add_extensions: vec![],
-                remove_extensions: vec![component]
+                remove_extensions: vec![component],
+                profile: None,
             };
 
             try!(manifestation.update(&manifest,
                                       changes,
                                       &self.download_cfg(),
                                       self.download_cfg().notify_handler.clone()));
+            self.update_proxies();
 
             Ok(())
         } else {
@@ -665,3 +1245,127 @@ impl<'a> Toolchain<'a> {
         path
     }
 }
+
+/// The name of `tool` without the executable suffix it might be run
+/// with
+pub fn tool_name(tool: &str) -> &str {
+    if !EXE_SUFFIX.is_empty() && tool.to_lowercase().ends_with(EXE_SUFFIX) {
+        &tool[..tool.len() - EXE_SUFFIX.len()]
+    } else {
+        tool
+    }
+}
+
+/// The variables set for the commands run with the toolchain `name`
+/// installed at `path`, apart from those set with `rustup toolchain
+/// env`
+pub fn base_env_vars(name: &str, path: &Path, multirust_dir: &Path) -> Vec<(String, OsString)> {
+    let mut vars = ldpath_vars(path);
+
+    // Because multirust and cargo use slightly different
+    // definitions of cargo home (multirust doesn't read HOME on
+    // windows), we must set it here to ensure cargo and
+    // multirust agree.
+    if let Ok(cargo_home) = utils::cargo_home() {
+        vars.push(("CARGO_HOME".to_owned(), cargo_home.into_os_string()));
+    }
+
+    vars.push(("RUSTUP_TOOLCHAIN".to_owned(), OsString::from(name)));
+    vars.push(("RUSTUP_HOME".to_owned(), multirust_dir.to_owned().into_os_string()));
+
+    vars
+}
+
+fn ldpath_vars(path: &Path) -> Vec<(String, OsString)> {
+    let mut vars = Vec::new();
+    let new_path = path.join("lib");
+
+    #[cfg(not(target_os = "macos"))]
+    mod sysenv {
+        pub const LOADER_PATH: &'static str = "LD_LIBRARY_PATH";
+    }
+    #[cfg(target_os = "macos")]
+    mod sysenv {
+        pub const LOADER_PATH: &'static str = "DYLD_LIBRARY_PATH";
+    }
+    if let Some(value) = env_var::prepended_path(sysenv::LOADER_PATH, vec![new_path.clone()]) {
+        vars.push((sysenv::LOADER_PATH.to_owned(), value));
+    }
+
+    // Prepend CARGO_HOME/bin to the PATH variable so that we're sure to run
+    // cargo/rustc via the proxy bins. There is no fallback case for if the
+    // proxy bins don't exist. We'll just be running whatever happens to
+    // be on the PATH.
+    let mut path_entries = vec![];
+    if let Ok(cargo_home) = utils::cargo_home() {
+        path_entries.push(cargo_home.join("bin").to_path_buf());
+    }
+
+    if cfg!(target_os = "windows") {
+        path_entries.push(path.join("bin"));
+    }
+
+    if let Some(value) = env_var::prepended_path("PATH", path_entries) {
+        vars.push(("PATH".to_owned(), value));
+    }
+
+    vars
+}
+
+/// Runs `rustc -vV` for the version and host triple of a custom
+/// toolchain
+fn custom_rustc_version(rustc: &Path) -> Result<CustomToolchain> {
+    let output = try!(Command::new(rustc).arg("-vV").output()
+                      .chain_err(|| ErrorKind::RunningCustomRustc(rustc.to_owned())));
+    if !output.status.success() {
+        return Err(ErrorKind::RunningCustomRustc(rustc.to_owned()).into());
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    let mut version = None;
+    let mut host = None;
+    let mut commit_hash = None;
+    let mut commit_date = None;
+    for line in stdout.lines() {
+        let mut parts = line.splitn(2, ": ");
+        let (key, value) = match (parts.next(), parts.next()) {
+            (Some(key), Some(value)) => (key, value.trim()),
+            _ => continue,
+        };
+        // Builds without git report their commit as `unknown`
+        if value == "unknown" {
+            continue;
+        }
+        let value = Some(value.to_owned());
+        match key {
+            "release" => version = value,
+            "host" => host = value,
+            "commit-hash" => commit_hash = value,
+            "commit-date" => commit_date = value,
+            _ => {}
+        }
+    }
+    // Only the first line is certain to be there
+    let version = version.or_else(|| {
+        stdout.lines().next().and_then(|l| l.split_whitespace().nth(1)).map(|v| v.to_owned())
+    });
+
+    Ok(CustomToolchain {
+        version: try!(version.ok_or_else(|| ErrorKind::RunningCustomRustc(rustc.to_owned()))),
+        host: host,
+        commit_hash: commit_hash,
+        commit_date: commit_date,
+    })
+}
+
+/// Whether the rustc in `manifest`, versioned like `1.31.0-nightly
+/// (abe02cefd 2018-12-04)` with a short commit hash, was built from
+/// `commit_hash`
+fn built_from_commit(manifest: &Manifest, commit_hash: &str) -> bool {
+    manifest.get_package("rustc").ok()
+        .and_then(|p| p.version.split('(').nth(1))
+        .and_then(|v| v.split_whitespace().next())
+        .map(|short| short.trim_right_matches(')'))
+        .map(|short| !short.is_empty() && commit_hash.starts_with(short))
+        .unwrap_or(false)
+}
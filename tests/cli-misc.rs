@@ -8,6 +8,7 @@ extern crate time;
 use rustup_mock::clitools::{self, Config, Scenario,
                                expect_stdout_ok, expect_stderr_ok,
                                expect_ok, expect_err, expect_timeout_ok,
+                               expect_not_stdout_ok,
                                run, this_host_triple};
 use rustup_utils::{raw, utils};
 
@@ -109,6 +110,32 @@ fn upgrade_v2_metadata_to_v12() {
         expect_ok(config, &["rustup", "update", "nightly"]);
         expect_stdout_ok(config, &["rustc", "--version"],
                          "hash-n-2");
+        // The backup goes once the upgrade has succeeded
+        assert!(!config.rustupdir.join("backup").exists());
+    });
+}
+
+#[test]
+fn running_with_newer_metadata() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "default", "nightly"]);
+        let settings = config.rustupdir.join("settings.toml");
+        let content = rustup_utils::raw::read_file(&settings).unwrap();
+        let content = content.replace("version = \"12\"", "version = \"99\"");
+        rustup_utils::raw::write_file(&settings, &content).unwrap();
+
+        expect_err(config, &["rustup", "default", "nightly"],
+                   "rustup's metadata is version '99', newer than this rustup supports");
+        expect_err(config, &["rustc", "--version"],
+                   "rustup's metadata is version '99', newer than this rustup supports");
+        expect_err(config, &["rustup", "self", "upgrade-data"],
+                   "newer than this rustup supports");
+        // Updating rustup is the way out, so it must still run
+        expect_ok(config, &["rustup", "--version"]);
+        expect_err(config, &["rustup", "self", "update"],
+                   "rustup is not installed");
+        // The older backup mustn't replace the newer settings
+        assert_eq!(rustup_utils::raw::read_file(&settings).unwrap(), content);
     });
 }
 
@@ -284,6 +311,46 @@ fn custom_toolchain_cargo_fallback_run() {
     });
 }
 
+#[test]
+fn custom_toolchain_configured_fallback() {
+    setup(&|config| {
+        let path = config.customdir.join("custom-1");
+
+        expect_ok(config, &["rustup", "toolchain", "link", "mytoolchain",
+                            &path.to_string_lossy()]);
+        expect_ok(config, &["rustup", "default", "mytoolchain"]);
+        expect_ok(config, &["rustup", "update", "stable"]);
+        expect_ok(config, &["rustup", "update", "nightly"]);
+        expect_ok(config, &["rustup", "component", "add", "rls", "--toolchain", "stable"]);
+
+        expect_ok(config, &["rustup", "toolchain", "fallback", "mytoolchain", "stable", "nightly"]);
+        expect_stdout_ok(config, &["rustup", "toolchain", "fallback", "mytoolchain"],
+                         for_host!("stable-{}"));
+        expect_stdout_ok(config, &["cargo", "--version"], "hash-s-2");
+        expect_stdout_ok(config, &["rls", "--version"], "hash-s-2");
+
+        expect_ok(config, &["rustup", "toolchain", "fallback", "mytoolchain", "--none"]);
+        expect_err(config, &["cargo", "--version"], "does not have the binary");
+
+        expect_ok(config, &["rustup", "toolchain", "fallback", "mytoolchain", "--unset"]);
+        expect_stdout_ok(config, &["cargo", "--version"], "hash-n-2");
+    });
+}
+
+#[test]
+fn dist_toolchain_configured_fallback() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "default", "nightly"]);
+        expect_ok(config, &["rustup", "update", "stable"]);
+        expect_ok(config, &["rustup", "component", "add", "rls", "--toolchain", "stable"]);
+        expect_err(config, &["rls", "--version"], "does not have the binary");
+
+        expect_ok(config, &["rustup", "toolchain", "fallback", "nightly", "stable"]);
+        expect_stdout_ok(config, &["rls", "--version"], "hash-s-2");
+        expect_stdout_ok(config, &["rustc", "--version"], "hash-n-2");
+    });
+}
+
 #[test]
 fn rustup_run_searches_path() {
     setup(&|config| {
@@ -317,6 +384,127 @@ fn rustup_failed_path_search() {
     });
 }
 
+#[test]
+fn link_records_version() {
+    setup(&|config| {
+        let path = config.customdir.join("custom-1");
+        expect_stderr_ok(config, &["rustup", "toolchain", "link", "custom",
+                                   &path.to_string_lossy()],
+                         "toolchain 'custom' is rustc 1.0.0");
+        expect_stdout_ok(config, &["rustup", "toolchain", "list", "-v"],
+                         "rustc 1.0.0");
+        expect_stdout_ok(config, &["rustup", "toolchain", "list", "-v"],
+                         &format!("-> {}", path.display()));
+    });
+}
+
+#[test]
+fn link_warns_about_missing_cargo() {
+    setup(&|config| {
+        let path = config.customdir.join("custom-1");
+        expect_stderr_ok(config, &["rustup", "toolchain", "link", "custom",
+                                   &path.to_string_lossy()],
+                         "warning: toolchain 'custom' has no `bin/cargo`");
+    });
+}
+
+#[test]
+fn link_requires_rustc() {
+    setup(&|config| {
+        let path = config.customdir.join("custom-3");
+        utils::ensure_dir_exists("custom", &path.join("bin"), &|_| ()).unwrap();
+        expect_err(config, &["rustup", "toolchain", "link", "custom",
+                             &path.to_string_lossy()],
+                   "is not a toolchain: it has no `bin/rustc`");
+    });
+}
+
+#[test]
+fn add_target_to_linked_toolchain() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "toolchain", "install", "nightly"]);
+        let path = config.customdir.join("custom-3");
+        let nightly = config.rustupdir.join("toolchains").join(for_host!("nightly-{}"));
+        utils::copy_dir(&nightly, &path, &|_| ()).unwrap();
+        expect_ok(config, &["rustup", "toolchain", "link", "custom",
+                            &path.to_string_lossy()]);
+        expect_stderr_ok(config, &["rustup", "target", "add", "--toolchain", "custom",
+                                   clitools::CROSS_ARCH1],
+                         for_host!("toolchain 'custom' was built from the same commit as 'nightly-{}'"));
+
+        let std = format!("overlays/custom/lib/rustlib/{}/lib/libstd.rlib", clitools::CROSS_ARCH1);
+        assert!(config.rustupdir.join(std).exists());
+        assert!(!path.join("lib/rustlib").join(clitools::CROSS_ARCH1).exists());
+        expect_stdout_ok(config, &["rustup", "run", "custom", "rustc", "--version"],
+                         "hash-n-2");
+        // A sysroot given on the command line replaces the overlay
+        expect_stdout_ok(config, &["rustup", "run", "custom", "rustc",
+                                   "--sysroot", "elsewhere", "--version"],
+                         "hash-n-2");
+    });
+}
+
+#[test]
+fn list_and_remove_targets_of_linked_toolchain() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "toolchain", "install", "nightly"]);
+        let path = config.customdir.join("custom-3");
+        let nightly = config.rustupdir.join("toolchains").join(for_host!("nightly-{}"));
+        utils::copy_dir(&nightly, &path, &|_| ()).unwrap();
+        expect_ok(config, &["rustup", "toolchain", "link", "custom",
+                            &path.to_string_lossy()]);
+        expect_stdout_ok(config, &["rustup", "target", "list", "--toolchain", "custom"],
+                         for_host!("{} (default)"));
+
+        // The toolchain's own std can't be replaced from the nightly
+        expect_err(config, &["rustup", "target", "add", "--toolchain", "custom",
+                             &this_host_triple()],
+                   for_host!("toolchain 'custom' was built with its own standard library for '{}'"));
+        expect_err(config, &["rustup", "target", "remove", "--toolchain", "custom",
+                             &this_host_triple()],
+                   "is required for toolchain 'custom'");
+
+        expect_ok(config, &["rustup", "target", "add", "--toolchain", "custom",
+                            clitools::CROSS_ARCH1]);
+        expect_stdout_ok(config, &["rustup", "target", "list", "--toolchain", "custom"],
+                         &format!("{} (installed)", clitools::CROSS_ARCH1));
+
+        expect_ok(config, &["rustup", "target", "remove", "--toolchain", "custom",
+                            clitools::CROSS_ARCH1]);
+        let std = format!("overlays/custom/lib/rustlib/{}/lib/libstd.rlib", clitools::CROSS_ARCH1);
+        assert!(!config.rustupdir.join(std).exists());
+        expect_not_stdout_ok(config, &["rustup", "target", "list", "--toolchain", "custom"],
+                             &format!("{} (installed)", clitools::CROSS_ARCH1));
+        expect_stdout_ok(config, &["rustup", "target", "list", "--toolchain", "custom"],
+                         clitools::CROSS_ARCH1);
+    });
+}
+
+#[test]
+fn add_target_to_linked_toolchain_without_nightly() {
+    setup(&|config| {
+        let path = config.customdir.join("custom-1");
+        expect_ok(config, &["rustup", "toolchain", "link", "custom",
+                            &path.to_string_lossy()]);
+        expect_err(config, &["rustup", "target", "add", "--toolchain", "custom",
+                             clitools::CROSS_ARCH1],
+                   "no nightly was built from commit 'hash-c-1' of toolchain 'custom'");
+    });
+}
+
+#[test]
+fn list_shows_missing_link_target() {
+    setup(&|config| {
+        let path = config.customdir.join("custom-3");
+        utils::copy_dir(&config.customdir.join("custom-1"), &path, &|_| ()).unwrap();
+        expect_ok(config, &["rustup", "toolchain", "link", "custom",
+                            &path.to_string_lossy()]);
+        utils::remove_dir("custom", &path, &|_| ()).unwrap();
+        expect_stdout_ok(config, &["rustup", "toolchain", "list", "-v"],
+                         "(missing)");
+    });
+}
+
 #[test]
 fn multirust_env_compat() {
     setup(&|config| {
@@ -421,11 +609,123 @@ fn rls_exists_in_toolchain() {
 #[test]
 fn rls_does_not_exist_in_toolchain() {
     setup(&|config| {
-        // FIXME: If rls exists in the toolchain, this should suggest a command
-        // to run to install it
         expect_ok(config, &["rustup", "default", "stable"]);
         expect_err(config, &["rls", "--version"],
                    &format!("toolchain 'stable-{}' does not have the binary `rls{}`",
                             this_host_triple(), EXE_SUFFIX));
+        expect_err(config, &["rls", "--version"],
+                   for_host!("install it with `rustup component add --toolchain stable-{} rls`"));
+        assert!(!config.exedir.join(format!("rls{}", EXE_SUFFIX)).exists());
+    });
+}
+
+#[test]
+fn rls_does_not_exist_in_toolchain_with_rustup_run() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "default", "stable"]);
+        expect_err(config, &["rustup", "run", "stable", "rls", "--version"],
+                   for_host!("install it with `rustup component add --toolchain stable-{} rls`"));
+    });
+}
+
+#[test]
+fn binary_without_known_component() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "default", "stable"]);
+        let ref rustup_path = config.exedir.join(&format!("rustup{}", EXE_SUFFIX));
+        let ref tool_path = config.exedir.join(&format!("fake_proxy{}", EXE_SUFFIX));
+        utils::hardlink_file(rustup_path, tool_path).expect("Failed to create fake proxy for test");
+        expect_err(config, &["fake_proxy", "--version"],
+                   "the component that provides it is unknown");
+    });
+}
+
+#[test]
+fn rls_auto_installed_in_toolchain() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "default", "stable"]);
+        expect_ok(config, &["rustup", "set", "auto-install", "true"]);
+        expect_stderr_ok(config, &["rls", "--version"],
+                         "installing component 'rls' to provide `rls`");
+        expect_stdout_ok(config, &["rls", "--version"], "hash-s-2");
+        expect_stdout_ok(config, &["rustup", "component", "list"],
+                         for_host!("rls-{} (installed)"));
+    });
+}
+
+#[test]
+fn set_config_is_listed() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "set", "update-root", "https://example.com/rustup/"]);
+        expect_stdout_ok(config, &["rustup", "config", "list"],
+                         "update-root = https://example.com/rustup (settings)");
+        expect_stdout_ok(config, &["rustup", "config", "list"],
+                         "staged-manifest = false (default)");
+        expect_ok(config, &["rustup", "set", "update-root", ""]);
+        expect_stdout_ok(config, &["rustup", "config", "list"],
+                         "update-root = https://static.rust-lang.org/rustup (default)");
+    });
+}
+
+#[test]
+fn environment_overrides_set_config() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "set", "dist-server", "https://example.com"]);
+        expect_stdout_ok(config, &["rustup", "config", "list"],
+                         "(environment: RUSTUP_DIST_SERVER)");
+        // The stored server is not used while the environment sets one
+        expect_ok(config, &["rustup", "default", "nightly"]);
+    });
+}
+
+#[test]
+fn set_config_rejects_invalid_values() {
+    setup(&|config| {
+        expect_err(config, &["rustup", "set", "download-backend", "wget"],
+                   "invalid value for 'download-backend': 'wget'");
+        expect_err(config, &["rustup", "set", "staged-manifest", "maybe"],
+                   "invalid value for 'staged-manifest': 'maybe'");
+        expect_ok(config, &["rustup", "set", "download-backend", "hyper"]);
+        expect_stdout_ok(config, &["rustup", "config", "list"],
+                         "download-backend = hyper (settings)");
+    });
+}
+
+#[test]
+fn set_gpg_key_checks_the_key() {
+    setup(&|config| {
+        let key = config.homedir.join("key.asc");
+        expect_err(config, &["rustup", "set", "gpg-key", &key.to_string_lossy()],
+                   "is not an ASCII-armored PGP public key");
+        raw::write_file(&key, "not a key").unwrap();
+        expect_err(config, &["rustup", "set", "gpg-key", &key.to_string_lossy()],
+                   "is not an ASCII-armored PGP public key");
+        expect_stdout_ok(config, &["rustup", "config", "list"], "gpg-key = (built-in) (default)");
+
+        raw::write_file(&key, "-----BEGIN PGP PUBLIC KEY BLOCK-----\n\n\
+                               -----END PGP PUBLIC KEY BLOCK-----\n").unwrap();
+        expect_ok(config, &["rustup", "set", "gpg-key", &key.to_string_lossy()]);
+        expect_stdout_ok(config, &["rustup", "config", "list"],
+                         &format!("gpg-key = {} (settings)", key.display()));
+    });
+}
+
+#[test]
+fn damaged_settings_are_recovered_from_backup() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "default", "nightly"]);
+        // The backup is the settings as they were before the last change
+        expect_ok(config, &["rustup", "set", "auto-install", "true"]);
+        let settings = config.rustupdir.join("settings.toml");
+        let backup = config.rustupdir.join("settings.toml.bak");
+        assert!(!raw::read_file(&backup).unwrap().contains("auto_install"));
+        raw::write_file(&settings, "").unwrap();
+        expect_stdout_ok(config, &["rustup", "default"], "nightly");
+        let content = raw::read_file(&settings).unwrap();
+        assert!(content.contains("nightly"));
+        // A damaged file doesn't replace the backup
+        raw::write_file(&settings, "").unwrap();
+        expect_ok(config, &["rustup", "set", "auto-install", "true"]);
+        assert!(raw::read_file(&backup).unwrap().contains("nightly"));
     });
 }
@@ -4,14 +4,18 @@ extern crate rustup_dist;
 extern crate rustup_utils;
 extern crate rustup_mock;
 extern crate tempdir;
+extern crate filetime;
 
 use std::fs;
+use std::path::Path;
 use std::env::consts::EXE_SUFFIX;
 use std::process;
 use rustup_utils::raw;
+use filetime::FileTime;
 use rustup_mock::clitools::{self, Config, Scenario,
                                expect_ok, expect_ok_ex,
                                expect_stderr_ok, expect_stdout_ok,
+                               expect_not_stdout_ok,
                                expect_err,
                                set_current_dist_date,
                                this_host_triple};
@@ -845,3 +849,230 @@ fn multirust_upgrade_works_with_proxy() {
         assert!(rustup_dir.exists());
     });
 }
+
+#[test]
+fn env_prints_toolchain_environment() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "default", "nightly"]);
+        expect_stdout_ok(config, &["rustup", "env", "--shell", "bash"],
+                         for_host!("export RUSTUP_TOOLCHAIN='nightly-{}';"));
+        expect_stdout_ok(config, &["rustup", "env", "--shell", "fish"],
+                         for_host!("set -gx RUSTUP_TOOLCHAIN 'nightly-{}';"));
+        expect_stdout_ok(config, &["rustup", "env", "--shell", "powershell"],
+                         for_host!("$env:RUSTUP_TOOLCHAIN = 'nightly-{}'"));
+        expect_stdout_ok(config, &["rustup", "env", "--shell", "json"],
+                         for_host!(r#""toolchain": "nightly-{}""#));
+        expect_stdout_ok(config, &["rustup", "env", "--shell", "bash"], "# sysroot: ");
+        expect_not_stdout_ok(config, &["rustup", "env", "--shell", "bash"], "SYSROOT");
+    });
+}
+
+#[test]
+fn env_for_other_toolchain() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "default", "nightly"]);
+        expect_ok(config, &["rustup", "update", "stable"]);
+        expect_ok(config, &["rustup", "toolchain", "env", "stable", "RUSTFLAGS=-Copt-level=2"]);
+        expect_stdout_ok(config, &["rustup", "env", "--toolchain", "stable", "--shell", "bash"],
+                         "export RUSTFLAGS='-Copt-level=2';");
+        expect_err(config, &["rustup", "env", "--toolchain", "beta"],
+                   for_host!("toolchain 'beta-{}' is not installed"));
+    });
+}
+
+#[test]
+#[cfg(unix)]
+fn shell_runs_with_toolchain_environment() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "default", "nightly"]);
+        expect_ok(config, &["rustup", "update", "stable"]);
+
+        let ref shell = config.homedir.join("shell");
+        raw::write_file(shell, "#!/bin/sh\necho \"toolchain: $RUSTUP_TOOLCHAIN\"\n").unwrap();
+        rustup_utils::utils::make_executable(shell).unwrap();
+
+        let out = clitools::run(config, "rustup", &["shell", "stable"],
+                                &[("SHELL", &shell.to_string_lossy())]);
+        assert!(out.ok);
+        assert!(out.stdout.contains(for_host!("toolchain: stable-{}")));
+    });
+}
+
+#[test]
+fn run_each_toolchain() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "update", "stable"]);
+        expect_ok(config, &["rustup", "update", "nightly"]);
+        expect_stdout_ok(config, &["rustup", "run", "--each", "stable,nightly",
+                                   "rustc", "--version"], "hash-s-2");
+        expect_stdout_ok(config, &["rustup", "run", "--each", "stable,nightly",
+                                   "rustc", "--version"], "hash-n-2");
+        expect_stdout_ok(config, &["rustup", "run", "--each", "*",
+                                   "rustc", "--version"],
+                         for_host!("nightly-{} passed"));
+    });
+}
+
+#[test]
+fn run_each_includes_linked_toolchains() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "update", "stable"]);
+        let path = config.customdir.join("custom-1");
+        expect_ok(config, &["rustup", "toolchain", "link", "custom",
+                            &path.to_string_lossy()]);
+        expect_stdout_ok(config, &["rustup", "run", "--each", "*",
+                                   "rustc", "--version"], "hash-c-1");
+        expect_stdout_ok(config, &["rustup", "run", "--each", "all",
+                                   "rustc", "--version"], "custom passed");
+    });
+}
+
+#[test]
+fn run_each_reports_failures() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "update", "stable"]);
+        expect_err(config, &["rustup", "run", "--each", "stable,beta", "rustc", "--version"],
+                   "the command failed with 1 toolchain(s)");
+        expect_stdout_ok(config, &["rustup", "run", "--each", "stable,beta", "rustc", "--version"],
+                         for_host!("beta-{} failed - toolchain 'beta-{0}' is not installed"));
+        expect_err(config, &["rustup", "run", "--each", "stable", "rustc", "--bogus"],
+                   "the command failed with 1 toolchain(s)");
+    });
+}
+
+#[test]
+fn run_each_installs_missing_toolchains() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "update", "stable"]);
+        expect_stdout_ok(config, &["rustup", "run", "--each", "stable,beta", "--install",
+                                   "rustc", "--version"], "hash-b-2");
+    });
+}
+
+#[test]
+fn toolchain_info() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "default", "nightly"]);
+        expect_stdout_ok(config, &["rustup", "toolchain", "info"],
+                         for_host!("toolchain: nightly-{0}"));
+        expect_stdout_ok(config, &["rustup", "toolchain", "info", "nightly"],
+                         "manifest date: 2015-01-02");
+        expect_stdout_ok(config, &["rustup", "toolchain", "info", "nightly"],
+                         "kind: dist");
+        expect_stdout_ok(config, &["rustup", "toolchain", "info", "nightly"],
+                         for_host!("  rust-std-{0}"));
+    });
+}
+
+#[test]
+fn toolchain_info_json() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "default", "nightly"]);
+        expect_stdout_ok(config, &["rustup", "toolchain", "info", "--json"],
+                         for_host!(r#""toolchain": "nightly-{0}""#));
+        expect_stdout_ok(config, &["rustup", "toolchain", "info", "--json"],
+                         r#""manifest_date": "2015-01-02""#);
+    });
+}
+
+#[test]
+fn toolchain_info_linked() {
+    setup(&|config| {
+        let path = config.customdir.join("custom-1");
+        let path = path.to_string_lossy();
+        expect_ok(config, &["rustup", "toolchain", "link", "custom", &path]);
+        expect_stdout_ok(config, &["rustup", "toolchain", "info", "custom"],
+                         "kind: linked -> ");
+        expect_stdout_ok(config, &["rustup", "toolchain", "info", "custom"],
+                         "manifest date: (unknown)");
+    });
+}
+
+#[test]
+fn toolchain_info_not_installed() {
+    setup(&|config| {
+        expect_err(config, &["rustup", "toolchain", "info", "nightly"],
+                   for_host!("toolchain 'nightly-{0}' is not installed"));
+    });
+}
+
+/// Sets the modification time of `path` to a day ago
+fn make_old(path: &Path) {
+    let mtime = FileTime::from_last_modification_time(&fs::metadata(path).unwrap());
+    let old = FileTime::from_seconds_since_1970(mtime.seconds_relative_to_1970() - 24 * 60 * 60, 0);
+    filetime::set_file_times(path, old, old).unwrap();
+}
+
+#[test]
+fn clean_removes_leftovers() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "default", "stable"]);
+
+        let tmp = config.rustupdir.join("tmp");
+        fs::create_dir_all(tmp.join("abc_dir")).unwrap();
+        raw::write_file(&tmp.join("abc_dir/file"), "leftover").unwrap();
+        let partial = config.rustupdir.join("downloads/abc.partial");
+        fs::create_dir_all(partial.parent().unwrap()).unwrap();
+        raw::write_file(&partial, "partial").unwrap();
+        let hashes = config.rustupdir.join("update-hashes");
+        let stale = hashes.join(for_host!("nightly-{}"));
+        raw::write_file(&stale, "hash").unwrap();
+        let recent = config.rustupdir.join("downloads/def.partial");
+        raw::write_file(&recent, "in progress").unwrap();
+        make_old(&tmp.join("abc_dir"));
+        make_old(&tmp.join("abc_dir/file"));
+        make_old(&partial);
+
+        expect_stdout_ok(config, &["rustup", "clean", "--dry-run"], "abc.partial");
+        expect_stderr_ok(config, &["rustup", "clean", "--dry-run"], "would be freed");
+        assert!(partial.exists());
+
+        expect_ok(config, &["rustup", "clean"]);
+        assert!(!tmp.join("abc_dir").exists());
+        assert!(!partial.exists());
+        assert!(!stale.exists());
+        assert!(recent.exists());
+        assert!(hashes.join(for_host!("stable-{}")).exists());
+        expect_stdout_ok(config, &["rustc", "--version"], "hash-s-2");
+    });
+}
+
+#[test]
+fn log_records_changes() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "default", "stable"]);
+        expect_ok(config, &["rustup", "target", "add", clitools::CROSS_ARCH1]);
+        expect_ok(config, &["rustup", "component", "add", "rust-src"]);
+        expect_err(config, &["rustup", "component", "add", "no-such-component"],
+                   "toolchain 'stable");
+        expect_ok(config, &["rustup", "toolchain", "remove", "stable"]);
+
+        expect_stdout_ok(config, &["rustup", "log"],
+                         for_host!("toolchain install stable-{0} (2015-01-02): ok"));
+        expect_stdout_ok(config, &["rustup", "log"],
+                         for_host!("default stable-{0}"));
+        expect_stdout_ok(config, &["rustup", "log"],
+                         &format!("target add stable-{} {} (2015-01-02): ok",
+                                  this_host_triple(), clitools::CROSS_ARCH1));
+        expect_stdout_ok(config, &["rustup", "log"], "component add");
+        expect_stdout_ok(config, &["rustup", "log"], "rust-src");
+        expect_stdout_ok(config, &["rustup", "log"],
+                         "component add no-such-component`");
+        expect_stdout_ok(config, &["rustup", "log"],
+                         for_host!("toolchain uninstall stable-{0}: ok"));
+    });
+}
+
+#[test]
+fn log_filters_and_json() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "update", "stable"]);
+        expect_ok(config, &["rustup", "update", "nightly"]);
+
+        expect_stdout_ok(config, &["rustup", "log", "--toolchain", "nightly"], "nightly");
+        expect_not_stdout_ok(config, &["rustup", "log", "--toolchain", "nightly"], "stable");
+        expect_not_stdout_ok(config, &["rustup", "log", "-n", "1"], "stable");
+        expect_stdout_ok(config, &["rustup", "log", "--json"],
+                         r#""action":"toolchain install""#);
+    });
+}
@@ -534,6 +534,17 @@ info: rustup updated successfully to ".to_owned() + version + "
     });
 }
 
+#[test]
+fn update_with_newer_metadata() {
+    update_setup(&|config, _| {
+        expect_ok(config, &["rustup-init", "-y"]);
+        let ref settings = config.rustupdir.join("settings.toml");
+        let content = raw::read_file(settings).unwrap();
+        raw::write_file(settings, &content.replace("version = \"12\"", "version = \"99\"")).unwrap();
+        expect_ok(config, &["rustup", "self", "update"]);
+    });
+}
+
 #[test]
 fn update_but_not_installed() {
     update_setup(&|config, _| {
@@ -1223,3 +1234,44 @@ fn rls_proxy_set_up_after_update() {
         assert!(rls_path.exists());
     });
 }
+
+#[test]
+fn proxy_created_and_removed_with_component() {
+    setup(&|config| {
+        let ref rls_path = config.cargodir.join(format!("bin/rls{}", EXE_SUFFIX));
+        expect_ok(config, &["rustup-init", "-y"]);
+        fs::remove_file(rls_path).unwrap();
+        expect_ok(config, &["rustup", "component", "add", "rls"]);
+        assert!(rls_path.exists());
+        expect_ok(config, &["rustup", "component", "remove", "rls"]);
+        assert!(!rls_path.exists());
+    });
+}
+
+#[test]
+fn proxy_removed_with_component_after_self_update() {
+    update_setup(&|config, _| {
+        let ref rls_path = config.cargodir.join(format!("bin/rls{}", EXE_SUFFIX));
+        expect_ok(config, &["rustup-init", "-y"]);
+        fs::remove_file(rls_path).unwrap();
+        expect_ok(config, &["rustup", "component", "add", "rls"]);
+        expect_ok(config, &["rustup", "self", "update"]);
+        expect_ok(config, &["rls", "--version"]);
+        expect_ok(config, &["rustup", "component", "remove", "rls"]);
+        assert!(!rls_path.exists());
+    });
+}
+
+#[test]
+fn proxy_does_not_overwrite_cargo_installed_binary() {
+    setup(&|config| {
+        let ref rls_path = config.cargodir.join(format!("bin/rls{}", EXE_SUFFIX));
+        expect_ok(config, &["rustup-init", "-y"]);
+        fs::remove_file(rls_path).unwrap();
+        raw::write_file(rls_path, "cargo installed").unwrap();
+        expect_ok(config, &["rustup", "component", "add", "rls"]);
+        assert_eq!(raw::read_file(rls_path).unwrap(), "cargo installed");
+        expect_ok(config, &["rustup", "component", "remove", "rls"]);
+        assert_eq!(raw::read_file(rls_path).unwrap(), "cargo installed");
+    });
+}
@@ -11,6 +11,7 @@ use tempdir::TempDir;
 use rustup_mock::clitools::{self, Config, Scenario,
                                expect_ok, expect_stdout_ok, expect_err,
                                expect_stderr_ok, expect_not_stdout_ok,
+                               expect_not_stderr_ok,
                                set_current_dist_date, change_dir,
                                this_host_triple};
 
@@ -688,3 +689,652 @@ fn update_unavailable_std() {
                    &format!("component 'rust-std' for '{}' is unavailable for download", trip));
     });
 }
+
+#[test]
+fn install_with_minimal_profile() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "toolchain", "install", "nightly", "--profile", "minimal"]);
+        let path = config.rustupdir.join("toolchains").join(for_host!("nightly-{}"));
+        assert!(!path.join("share/doc/rust/html/index.html").exists());
+        expect_stdout_ok(config, &["rustup", "run", "nightly", "rustc", "--version"], "1.3.0");
+    });
+}
+
+#[test]
+fn install_with_complete_profile() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "toolchain", "install", "nightly", "--profile", "complete"]);
+        let path = config.rustupdir.join("toolchains").join(for_host!("nightly-{}"));
+        assert!(path.join("share/doc/rust/html/index.html").exists());
+        assert!(path.join(&format!("bin/rls{}", std::env::consts::EXE_SUFFIX)).exists());
+    });
+}
+
+#[test]
+fn install_bogus_profile() {
+    setup(&|config| {
+        expect_err(config, &["rustup", "toolchain", "install", "nightly", "--profile", "bogus"],
+                   "'bogus' isn't a valid value");
+    });
+}
+
+#[test]
+fn set_profile_is_used_by_install() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "set", "profile", "minimal"]);
+        expect_ok(config, &["rustup", "default", "nightly"]);
+        let path = config.rustupdir.join("toolchains").join(for_host!("nightly-{}"));
+        assert!(!path.join("share/doc/rust/html/index.html").exists());
+    });
+}
+
+#[test]
+fn update_keeps_install_profile() {
+    clitools::setup(Scenario::ArchivesV2, &|config| {
+        set_current_dist_date(config, "2015-01-01");
+        expect_ok(config, &["rustup", "toolchain", "install", "nightly", "--profile", "minimal"]);
+        set_current_dist_date(config, "2015-01-02");
+        expect_ok(config, &["rustup", "update", "nightly"]);
+        let path = config.rustupdir.join("toolchains").join(for_host!("nightly-{}"));
+        assert!(!path.join("share/doc/rust/html/index.html").exists());
+    });
+}
+
+#[test]
+fn update_keeps_extension_removed_from_complete_profile() {
+    clitools::setup(Scenario::ArchivesV2, &|config| {
+        set_current_dist_date(config, "2015-01-01");
+        expect_ok(config, &["rustup", "toolchain", "install", "nightly", "--profile", "complete"]);
+        expect_ok(config, &["rustup", "component", "remove", "rls", "--toolchain", "nightly"]);
+        set_current_dist_date(config, "2015-01-02");
+        expect_ok(config, &["rustup", "update", "nightly"]);
+        let path = config.rustupdir.join("toolchains").join(for_host!("nightly-{}"));
+        assert!(!path.join(&format!("bin/rls{}", std::env::consts::EXE_SUFFIX)).exists());
+        expect_not_stdout_ok(config, &["rustup", "component", "list", "--toolchain", "nightly"],
+                             for_host!("rls-{} (installed)"));
+    });
+}
+
+#[test]
+fn add_component_left_out_by_profile() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "toolchain", "install", "nightly", "--profile", "minimal"]);
+        expect_ok(config, &["rustup", "default", "nightly"]);
+        expect_ok(config, &["rustup", "component", "add", "rust-docs"]);
+        let path = config.rustupdir.join("toolchains").join(for_host!("nightly-{}"));
+        assert!(path.join("share/doc/rust/html/index.html").exists());
+        expect_ok(config, &["rustup", "component", "remove", "rust-docs"]);
+        assert!(!path.join("share/doc/rust/html/index.html").exists());
+    });
+}
+
+#[test]
+fn add_target_to_toolchain_list() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "toolchain", "install", "nightly", "stable"]);
+        expect_ok(config, &["rustup", "target", "add", clitools::CROSS_ARCH1,
+                            "--toolchain", "nightly,stable"]);
+        for channel in &["nightly", "stable"] {
+            let path = format!("toolchains/{}-{}/lib/rustlib/{}/lib/libstd.rlib",
+                               channel, this_host_triple(), clitools::CROSS_ARCH1);
+            assert!(config.rustupdir.join(path).exists());
+        }
+    });
+}
+
+#[test]
+fn add_target_to_all_toolchains() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "toolchain", "install", "nightly", "beta"]);
+        expect_stdout_ok(config, &["rustup", "target", "add", clitools::CROSS_ARCH1,
+                                   "--toolchain", "all"],
+                         for_host!("nightly-{} changed"));
+        for channel in &["nightly", "beta"] {
+            let path = format!("toolchains/{}-{}/lib/rustlib/{}/lib/libstd.rlib",
+                               channel, this_host_triple(), clitools::CROSS_ARCH1);
+            assert!(config.rustupdir.join(path).exists());
+        }
+    });
+}
+
+#[test]
+fn add_component_to_toolchain_glob() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "toolchain", "install", "nightly", "stable"]);
+        expect_ok(config, &["rustup", "component", "add", "rust-src", "--toolchain", "night*"]);
+        let path = format!("toolchains/nightly-{}/lib/rustlib/src/rust-src/foo.rs",
+                           this_host_triple());
+        assert!(config.rustupdir.join(path).exists());
+        let path = format!("toolchains/stable-{}/lib/rustlib/src/rust-src/foo.rs",
+                           this_host_triple());
+        assert!(!config.rustupdir.join(path).exists());
+    });
+}
+
+#[test]
+fn add_target_toolchain_glob_no_match() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "toolchain", "install", "nightly"]);
+        expect_err(config, &["rustup", "target", "add", clitools::CROSS_ARCH1,
+                             "--toolchain", "beta-*"],
+                   "no installed toolchains match 'beta-*'");
+    });
+}
+
+#[test]
+fn add_target_to_toolchain_list_reports_failures() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "toolchain", "install", "nightly", "stable"]);
+        expect_err(config, &["rustup", "target", "add", "bogus",
+                             "--toolchain", "nightly,stable"],
+                   "changes could not be applied to 2 toolchain(s)");
+    });
+}
+
+#[test]
+fn override_with_target_installs_target() {
+    setup(&|config| {
+        let tempdir = TempDir::new("rustup").unwrap();
+        change_dir(tempdir.path(), &|| {
+            expect_ok(config, &["rustup", "override", "set", "nightly",
+                                "--target", clitools::CROSS_ARCH1]);
+        });
+        let path = format!("toolchains/nightly-{}/lib/rustlib/{}/lib/libstd.rlib",
+                           this_host_triple(), clitools::CROSS_ARCH1);
+        assert!(config.rustupdir.join(path).exists());
+    });
+}
+
+#[test]
+fn override_requirements_missing_err() {
+    setup(&|config| {
+        let tempdir = TempDir::new("rustup").unwrap();
+        change_dir(tempdir.path(), &|| {
+            expect_ok(config, &["rustup", "override", "set", "nightly",
+                                "--target", clitools::CROSS_ARCH1,
+                                "--component", "rust-src"]);
+            expect_ok(config, &["rustup", "target", "remove", clitools::CROSS_ARCH1]);
+            expect_ok(config, &["rustup", "component", "remove", "rust-src"]);
+            expect_err(config, &["rustc", "--version"],
+                       &format!("rustup target add --toolchain nightly-{0} {1} && \
+                                 rustup component add --toolchain nightly-{0} rust-src",
+                                this_host_triple(), clitools::CROSS_ARCH1));
+        });
+    });
+}
+
+#[test]
+fn override_requirements_auto_install() {
+    setup(&|config| {
+        let tempdir = TempDir::new("rustup").unwrap();
+        change_dir(tempdir.path(), &|| {
+            expect_ok(config, &["rustup", "override", "set", "nightly",
+                                "--target", clitools::CROSS_ARCH1]);
+            expect_ok(config, &["rustup", "target", "remove", clitools::CROSS_ARCH1]);
+            expect_ok(config, &["rustup", "set", "auto-install", "true"]);
+            expect_stdout_ok(config, &["rustc", "--version"], "hash-n-2");
+        });
+        let path = format!("toolchains/nightly-{}/lib/rustlib/{}/lib/libstd.rlib",
+                           this_host_triple(), clitools::CROSS_ARCH1);
+        assert!(config.rustupdir.join(path).exists());
+    });
+}
+
+#[test]
+fn override_set_again_replaces_requirements() {
+    setup(&|config| {
+        let tempdir = TempDir::new("rustup").unwrap();
+        change_dir(tempdir.path(), &|| {
+            expect_ok(config, &["rustup", "override", "set", "nightly",
+                                "--target", clitools::CROSS_ARCH1]);
+            expect_ok(config, &["rustup", "override", "set", "stable"]);
+            expect_not_stdout_ok(config, &["rustup", "override", "list"], clitools::CROSS_ARCH1);
+            expect_stdout_ok(config, &["rustc", "--version"], "hash-s-2");
+        });
+        let path = format!("toolchains/stable-{}/lib/rustlib/{}",
+                           this_host_triple(), clitools::CROSS_ARCH1);
+        assert!(!config.rustupdir.join(path).exists());
+    });
+}
+
+#[test]
+fn override_unset_removes_requirements() {
+    setup(&|config| {
+        let tempdir = TempDir::new("rustup").unwrap();
+        change_dir(tempdir.path(), &|| {
+            expect_ok(config, &["rustup", "override", "set", "nightly",
+                                "--target", clitools::CROSS_ARCH1]);
+            expect_stdout_ok(config, &["rustup", "override", "list"], clitools::CROSS_ARCH1);
+            expect_ok(config, &["rustup", "override", "unset"]);
+            expect_ok(config, &["rustup", "override", "set", "nightly"]);
+            expect_ok(config, &["rustup", "target", "remove", clitools::CROSS_ARCH1]);
+            expect_ok(config, &["rustc", "--version"]);
+        });
+    });
+}
+
+#[test]
+fn override_set_from_manifest() {
+    setup(&|config| {
+        let tempdir = TempDir::new("rustup").unwrap();
+        let src = tempdir.path().join("src");
+        fs::create_dir(&src).unwrap();
+        rustup_utils::raw::write_file(&tempdir.path().join("Cargo.toml"),
+                                      "[package]\nname = \"foo\"\nrust-version = \"1.1\"\n").unwrap();
+        change_dir(&src, &|| {
+            expect_ok(config, &["rustup", "override", "set", "--from-manifest"]);
+        });
+        change_dir(tempdir.path(), &|| {
+            expect_stdout_ok(config, &["rustc", "--version"], "hash-s-2");
+        });
+    });
+}
+
+#[test]
+fn override_set_from_manifest_without_rust_version() {
+    setup(&|config| {
+        let tempdir = TempDir::new("rustup").unwrap();
+        rustup_utils::raw::write_file(&tempdir.path().join("Cargo.toml"),
+                                      "[package]\nname = \"foo\"\n").unwrap();
+        change_dir(tempdir.path(), &|| {
+            expect_err(config, &["rustup", "override", "set", "--from-manifest"],
+                       "does not declare a `rust-version`");
+        });
+    });
+}
+
+#[test]
+fn manifest_overrides_setting() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "default", "nightly"]);
+        expect_ok(config, &["rustup", "toolchain", "install", "1.1.0"]);
+        let tempdir = TempDir::new("rustup").unwrap();
+        rustup_utils::raw::write_file(&tempdir.path().join("Cargo.toml"),
+                                      "[package]\nname = \"foo\"\n\n\
+                                       [package.metadata]\nrust-version = \"1.1.0\"\n").unwrap();
+        change_dir(tempdir.path(), &|| {
+            expect_stdout_ok(config, &["rustc", "--version"], "hash-n-2");
+            expect_ok(config, &["rustup", "set", "manifest-overrides", "true"]);
+            expect_stdout_ok(config, &["rustc", "--version"], "hash-s-2");
+            expect_stdout_ok(config, &["rustup", "show"], "rust-version declared in");
+        });
+    });
+}
+
+#[test]
+fn system_toolchains_are_shared_and_read_only() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "toolchain", "install", "nightly"]);
+        let system_dir = config.homedir.join("system-toolchains");
+        fs::create_dir_all(&system_dir).unwrap();
+        let name = format!("nightly-{}", this_host_triple());
+        fs::rename(config.rustupdir.join("toolchains").join(&name),
+                   system_dir.join(&name)).unwrap();
+        expect_ok(config, &["rustup", "set", "system-toolchains",
+                            system_dir.to_str().unwrap()]);
+        expect_stdout_ok(config, &["rustup", "toolchain", "list"],
+                         for_host!("nightly-{} (system)"));
+        expect_ok(config, &["rustup", "default", "nightly"]);
+        expect_stdout_ok(config, &["rustc", "--version"], "hash-n-2");
+        expect_err(config, &["rustup", "toolchain", "remove", "nightly"],
+                   "is in the read-only system store");
+        expect_err(config, &["rustup", "component", "add", "rust-src"],
+                   "is in the read-only system store");
+
+        // Installing it again puts a copy in the user's own store
+        expect_ok(config, &["rustup", "toolchain", "install", "nightly"]);
+        assert!(config.rustupdir.join("toolchains").join(&name).exists());
+        assert!(system_dir.join(&name).exists());
+        expect_not_stdout_ok(config, &["rustup", "toolchain", "list"], "(system)");
+        expect_ok(config, &["rustup", "component", "add", "rust-src"]);
+    });
+}
+
+#[test]
+fn export_and_import_state() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "default", "nightly"]);
+        expect_ok(config, &["rustup", "target", "add", clitools::CROSS_ARCH1]);
+        expect_ok(config, &["rustup", "component", "add", "rust-src"]);
+        let out = clitools::run(config, "rustup", &["export"], &[]);
+        assert!(out.ok);
+        assert!(out.stdout.contains(for_host!("name = \"nightly-{}\"")));
+
+        let state_file = config.homedir.join("rustup-state.toml");
+        rustup_utils::raw::write_file(&state_file, &out.stdout).unwrap();
+        fs::remove_dir_all(&config.rustupdir).unwrap();
+
+        expect_ok(config, &["rustup", "import", state_file.to_str().unwrap()]);
+        expect_stdout_ok(config, &["rustup", "default"], "nightly");
+        expect_stdout_ok(config, &["rustc", "--version"], "hash-n-2");
+        let path = format!("toolchains/nightly-{}/lib/rustlib/{}/lib/libstd.rlib",
+                           this_host_triple(), clitools::CROSS_ARCH1);
+        assert!(config.rustupdir.join(path).exists());
+        let path = format!("toolchains/nightly-{}/lib/rustlib/src/rust-src/foo.rs",
+                           this_host_triple());
+        assert!(config.rustupdir.join(path).exists());
+    });
+}
+
+#[test]
+fn import_state_keeps_machine_settings() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "default", "nightly"]);
+        expect_ok(config, &["rustup", "set", "update-root", "https://example.com/old"]);
+        expect_ok(config, &["rustup", "toolchain", "link", "custom",
+                            &config.customdir.join("custom-1").to_string_lossy()]);
+        expect_ok(config, &["rustup", "toolchain", "fallback", "custom", "nightly"]);
+        let out = clitools::run(config, "rustup", &["export"], &[]);
+        assert!(out.ok);
+
+        let state_file = config.homedir.join("rustup-state.toml");
+        rustup_utils::raw::write_file(&state_file, &out.stdout).unwrap();
+        fs::remove_dir_all(&config.rustupdir).unwrap();
+        expect_ok(config, &["rustup", "set", "update-root", "https://example.com/new"]);
+
+        expect_ok(config, &["rustup", "import", state_file.to_str().unwrap()]);
+        expect_stdout_ok(config, &["rustup", "default"], "nightly");
+        expect_stdout_ok(config, &["rustup", "config", "list"],
+                         "update-root = https://example.com/new");
+        let settings = rustup_utils::raw::read_file(&config.rustupdir.join("settings.toml")).unwrap();
+        assert!(!settings.contains("custom"));
+    });
+}
+
+#[test]
+fn import_state_leaves_out_linked_toolchains() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "toolchain", "install", "nightly"]);
+        expect_ok(config, &["rustup", "toolchain", "link", "custom",
+                            &config.customdir.join("custom-1").to_string_lossy()]);
+        expect_ok(config, &["rustup", "default", "custom"]);
+        expect_ok(config, &["rustup", "override", "tool", "cargo", "custom"]);
+        expect_ok(config, &["rustup", "toolchain", "pin", "custom"]);
+        expect_ok(config, &["rustup", "toolchain", "fallback", "nightly", "custom", "stable"]);
+        let out = clitools::run(config, "rustup", &["export"], &[]);
+        assert!(out.ok);
+
+        let state_file = config.homedir.join("rustup-state.toml");
+        rustup_utils::raw::write_file(&state_file, &out.stdout).unwrap();
+        fs::remove_dir_all(&config.rustupdir).unwrap();
+
+        expect_stderr_ok(config, &["rustup", "import", state_file.to_str().unwrap()],
+                         "toolchain 'custom' is not installed, \
+                          so the settings that use it will not be imported");
+        let settings = rustup_utils::raw::read_file(&config.rustupdir.join("settings.toml")).unwrap();
+        assert!(!settings.contains("custom"));
+        assert!(settings.contains("stable"));
+    });
+}
+
+#[test]
+fn override_lock_warns_on_mismatch() {
+    clitools::setup(Scenario::ArchivesV2, &|config| {
+        set_current_dist_date(config, "2015-01-01");
+        let tempdir = TempDir::new("rustup").unwrap();
+        change_dir(tempdir.path(), &|| {
+            expect_ok(config, &["rustup", "override", "set", "nightly"]);
+            expect_ok(config, &["rustup", "override", "lock"]);
+            assert!(tempdir.path().join("rust-toolchain.lock").exists());
+            expect_stdout_ok(config, &["rustc", "--version"], "hash-n-1");
+
+            set_current_dist_date(config, "2015-01-02");
+            expect_ok(config, &["rustup", "update", "nightly"]);
+            expect_stderr_ok(config, &["rustc", "--version"],
+                             "locks 'nightly' to the release of 2015-01-01, \
+                              but the installed release is from 2015-01-02");
+
+            expect_ok(config, &["rustup", "override", "lock"]);
+            expect_not_stderr_ok(config, &["rustc", "--version"], "locks 'nightly");
+        });
+    });
+}
+
+#[test]
+fn override_lock_from_another_host() {
+    clitools::setup(Scenario::ArchivesV2, &|config| {
+        set_current_dist_date(config, "2015-01-01");
+        let tempdir = TempDir::new("rustup").unwrap();
+        change_dir(tempdir.path(), &|| {
+            expect_ok(config, &["rustup", "override", "set", "nightly"]);
+            expect_ok(config, &["rustup", "override", "lock", "--strict"]);
+
+            // The mock channel only builds `rust` for this host, so pass
+            // its hashes off as another host's to get the lock another
+            // host would write for it
+            let ref lock_path = tempdir.path().join("rust-toolchain.lock");
+            let lock = rustup_utils::raw::read_file(lock_path).unwrap();
+            assert!(lock.contains("toolchain = \"nightly\""));
+            let lock = lock.replace(&this_host_triple(), "powerpc-unknown-openbsd");
+            rustup_utils::raw::write_file(lock_path, &lock).unwrap();
+
+            expect_stdout_ok(config, &["rustc", "--version"], "hash-n-1");
+            expect_not_stderr_ok(config, &["rustc", "--version"], "locks 'nightly");
+
+            set_current_dist_date(config, "2015-01-02");
+            expect_ok(config, &["rustup", "update", "nightly"]);
+            expect_err(config, &["rustc", "--version"],
+                       "run `rustup override lock` to update the lock");
+        });
+    });
+}
+
+#[test]
+fn override_lock_strict_refuses_mismatch() {
+    clitools::setup(Scenario::ArchivesV2, &|config| {
+        set_current_dist_date(config, "2015-01-01");
+        let tempdir = TempDir::new("rustup").unwrap();
+        change_dir(tempdir.path(), &|| {
+            expect_ok(config, &["rustup", "override", "set", "nightly"]);
+            expect_ok(config, &["rustup", "override", "lock", "--strict"]);
+            set_current_dist_date(config, "2015-01-02");
+            expect_ok(config, &["rustup", "update", "nightly"]);
+            expect_err(config, &["rustc", "--version"],
+                       "run `rustup override lock` to update the lock");
+        });
+    });
+}
+
+#[test]
+fn override_lock_without_override() {
+    setup(&|config| {
+        let tempdir = TempDir::new("rustup").unwrap();
+        change_dir(tempdir.path(), &|| {
+            expect_err(config, &["rustup", "override", "lock"],
+                       "no directory override applies to");
+        });
+    });
+}
+
+#[test]
+fn toolchain_env_is_set_by_proxies() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "default", "nightly"]);
+        expect_ok(config, &["rustup", "toolchain", "install", "stable"]);
+        expect_ok(config, &["rustup", "toolchain", "env", "nightly", "RUSTFLAGS=-Cdebuginfo=2"]);
+        expect_stdout_ok(config, &["rustup", "toolchain", "env", "nightly"],
+                         "RUSTFLAGS=-Cdebuginfo=2");
+        expect_stdout_ok(config, &["rustc", "--echo-env", "RUSTFLAGS"],
+                         "RUSTFLAGS=-Cdebuginfo=2");
+        expect_not_stdout_ok(config, &["rustup", "run", "stable", "rustc", "--echo-env", "RUSTFLAGS"],
+                             "-Cdebuginfo=2");
+        expect_ok(config, &["rustup", "toolchain", "env", "nightly", "--unset", "RUSTFLAGS"]);
+        expect_not_stdout_ok(config, &["rustc", "--echo-env", "RUSTFLAGS"], "-Cdebuginfo=2");
+    });
+}
+
+#[test]
+fn override_env_takes_priority() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "toolchain", "env", "nightly", "CARGO_TARGET_DIR=toolchain"]);
+        let tempdir = TempDir::new("rustup").unwrap();
+        change_dir(tempdir.path(), &|| {
+            expect_ok(config, &["rustup", "override", "set", "nightly",
+                                "--env", "CARGO_TARGET_DIR=override"]);
+            expect_stdout_ok(config, &["rustc", "--echo-env", "CARGO_TARGET_DIR"],
+                             "CARGO_TARGET_DIR=override");
+            expect_stdout_ok(config, &["rustup", "override", "list"],
+                             "CARGO_TARGET_DIR=override");
+        });
+    });
+}
+
+#[test]
+fn toolchain_env_rejects_bad_assignment() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "default", "nightly"]);
+        expect_err(config, &["rustup", "toolchain", "env", "nightly", "RUSTFLAGS"],
+                   "invalid environment variable assignment 'RUSTFLAGS'");
+    });
+}
+
+#[test]
+fn tool_override_wins_over_directory_override() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "default", "stable"]);
+        expect_ok(config, &["rustup", "override", "tool", "rustc", "nightly"]);
+        let tempdir = TempDir::new("rustup").unwrap();
+        change_dir(tempdir.path(), &|| {
+            expect_ok(config, &["rustup", "override", "set", "stable"]);
+            expect_stdout_ok(config, &["rustc", "--version"], "hash-n-2");
+            expect_stdout_ok(config, &["cargo", "--version"], "hash-s-2");
+            expect_stdout_ok(config, &["rustup", "which", "rustc"],
+                             for_host!("nightly-{}"));
+            expect_stdout_ok(config, &["rustc", "+stable", "--version"], "hash-s-2");
+
+            // RUSTUP_TOOLCHAIN wins too, and `which` agrees with the proxy
+            let toolchain = [("RUSTUP_TOOLCHAIN", "stable")];
+            let out = clitools::run(config, "rustc", &["--version"], &toolchain);
+            assert!(out.stdout.contains("hash-s-2"));
+            let out = clitools::run(config, "rustup", &["which", "rustc"], &toolchain);
+            assert!(out.stdout.contains(for_host!("stable-{}")));
+        });
+        expect_stdout_ok(config, &["rustup", "override", "list"], "rustc (tool)");
+
+        // A tool the overriding toolchain doesn't have isn't reported
+        // from it
+        expect_ok(config, &["rustup", "override", "tool", "rustfmt", "nightly"]);
+        expect_err(config, &["rustup", "which", "rustfmt"],
+                   for_host!("toolchain 'nightly-{}' does not have the binary `rustfmt`"));
+        expect_ok(config, &["rustup", "override", "tool", "rustc", "--unset"]);
+        expect_stdout_ok(config, &["rustc", "--version"], "hash-s-2");
+    });
+}
+
+#[test]
+fn proxy_cache_is_invalidated_by_settings_changes() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "default", "stable"]);
+        let tempdir = TempDir::new("rustup").unwrap();
+        change_dir(tempdir.path(), &|| {
+            expect_stdout_ok(config, &["rustc", "--version"], "hash-s-2");
+            assert!(config.rustupdir.join("proxy-cache").exists());
+            expect_ok(config, &["rustup", "override", "set", "nightly"]);
+            expect_stdout_ok(config, &["rustc", "--version"], "hash-n-2");
+            expect_ok(config, &["rustup", "override", "unset"]);
+            expect_stdout_ok(config, &["rustc", "--version"], "hash-s-2");
+        });
+    });
+}
+
+#[test]
+fn proxy_cache_records_the_toolchain_path() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "default", "stable"]);
+        let tempdir = TempDir::new("rustup").unwrap();
+        change_dir(tempdir.path(), &|| {
+            expect_stdout_ok(config, &["rustc", "--version"], "hash-s-2");
+        });
+        let cache = rustup_utils::raw::read_file(&config.rustupdir.join("proxy-cache")).unwrap();
+        let dir = fs::canonicalize(tempdir.path()).unwrap();
+        let toolchain = config.rustupdir.join("toolchains").join(for_host!("stable-{}"));
+        assert!(cache.lines().any(|l| {
+            l == format!("{}\t{}\t{}\t", dir.display(), for_host!("stable-{}"), toolchain.display())
+        }));
+    });
+}
+
+#[test]
+fn proxy_cache_leaves_out_toolchains_with_settings() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "default", "stable"]);
+        expect_ok(config, &["rustup", "toolchain", "env", "stable", "FOO=bar"]);
+        let tempdir = TempDir::new("rustup").unwrap();
+        change_dir(tempdir.path(), &|| {
+            expect_stdout_ok(config, &["rustc", "--version"], "hash-s-2");
+        });
+        let cache = rustup_utils::raw::read_file(&config.rustupdir.join("proxy-cache"))
+            .unwrap_or(String::new());
+        assert!(!cache.contains(for_host!("stable-{}")));
+    });
+}
+
+#[test]
+fn proxy_cache_checks_locks_written_since() {
+    clitools::setup(Scenario::ArchivesV2, &|config| {
+        set_current_dist_date(config, "2015-01-01");
+        let tempdir = TempDir::new("rustup").unwrap();
+        change_dir(tempdir.path(), &|| {
+            expect_ok(config, &["rustup", "override", "set", "nightly"]);
+            expect_stdout_ok(config, &["rustc", "--version"], "hash-n-1");
+            expect_ok(config, &["rustup", "override", "lock", "--strict"]);
+            set_current_dist_date(config, "2015-01-02");
+            expect_ok(config, &["rustup", "update", "nightly"]);
+            expect_err(config, &["rustc", "--version"],
+                       "run `rustup override lock` to update the lock");
+        });
+    });
+}
+
+#[test]
+fn proxy_cache_ignores_uninstalled_toolchains() {
+    setup(&|config| {
+        expect_ok(config, &["rustup", "default", "stable"]);
+        let tempdir = TempDir::new("rustup").unwrap();
+        change_dir(tempdir.path(), &|| {
+            expect_ok(config, &["rustup", "override", "set", "nightly"]);
+            expect_stdout_ok(config, &["rustc", "--version"], "hash-n-2");
+            fs::remove_dir_all(config.rustupdir.join("toolchains")
+                                   .join(for_host!("nightly-{}"))).unwrap();
+            expect_err(config, &["rustc", "--version"],
+                       for_host!("override toolchain 'nightly-{}' is not installed"));
+        });
+    });
+}
+
+#[test]
+fn update_skips_pinned_toolchains() {
+    setup(&|config| {
+        set_current_dist_date(config, "2015-01-01");
+        expect_ok(config, &["rustup", "default", "nightly"]);
+        expect_ok(config, &["rustup", "update", "beta"]);
+        expect_ok(config, &["rustup", "toolchain", "pin", "nightly"]);
+        expect_stdout_ok(config, &["rustup", "toolchain", "list"],
+                         for_host!("nightly-{0} (default) (pinned)"));
+
+        set_current_dist_date(config, "2015-01-02");
+        expect_stderr_ok(config, &["rustup", "update", "--no-self-update"],
+                         for_host!("skipping toolchain 'nightly-{0}', which is pinned"));
+        expect_stdout_ok(config, &["rustc", "--version"], "hash-n-1");
+        expect_stdout_ok(config, &["rustup", "run", "beta", "rustc", "--version"], "hash-b-2");
+
+        // Naming the toolchain still updates it
+        expect_ok(config, &["rustup", "update", "nightly"]);
+        expect_stdout_ok(config, &["rustc", "--version"], "hash-n-2");
+    });
+}
+
+#[test]
+fn update_unpinned_toolchain() {
+    setup(&|config| {
+        set_current_dist_date(config, "2015-01-01");
+        expect_ok(config, &["rustup", "default", "nightly"]);
+        expect_ok(config, &["rustup", "toolchain", "pin", "nightly"]);
+        expect_ok(config, &["rustup", "toolchain", "unpin", "nightly"]);
+        expect_not_stdout_ok(config, &["rustup", "toolchain", "list"], "(pinned)");
+
+        set_current_dist_date(config, "2015-01-02");
+        expect_ok(config, &["rustup", "update", "--no-self-update"]);
+        expect_stdout_ok(config, &["rustc", "--version"], "hash-n-2");
+    });
+}